@@ -7,10 +7,83 @@ var liveCells = 0;
 var m_width = 0;
 var m_height = 0;
 var last_generations_array = [];
+var session = new URLSearchParams(window.location.search).get("session");
+var socket = null;
+// Id of the player this tab joined the game as, cells then go in the player's colour
+var player = null;
 
 function init() {
     "use strict";
-    newBoard();
+    if (session === null) {
+        createSession();
+    } else {
+        newBoard();
+        openSocket();
+    }
+}
+
+// Every tab plays on a board of its own, the session stays in the address so a reload keeps the board
+function createSession() {
+    "use strict";
+    xhttp.onreadystatechange = function () {
+        if (xhttp.readyState == 4) {
+            // Without a session of its own the tab shares the board of the browser's cookie
+            if (xhttp.status == 201) {
+                session = JSON.parse(xhttp.responseText).id;
+                window.history.replaceState(null, "", "?session=" + session);
+            }
+            newBoard();
+            openSocket();
+        }
+    };
+    xhttp.open("POST", "sessions");
+    xhttp.send();
+}
+
+// The server plays the game and sends every generation over the socket
+function openSocket() {
+    "use strict";
+    var protocol = window.location.protocol === "https:" ? "wss://" : "ws://";
+    var path = window.location.pathname.replace(/[^/]*$/, "");
+    socket = new WebSocket(protocol + window.location.host + path + withSession("live"));
+    socket.onmessage = function (event) {
+        var json = JSON.parse(event.data);
+        if (json.array !== undefined) {
+            show_board(json);
+        } else if (json.playing !== undefined) {
+            started = json.playing;
+            document.getElementById("startButton").innerHTML = started ? "Stop" : "Start";
+            document.getElementById("speed").value = json.interval_ms;
+        }
+    };
+    socket.onclose = function () {
+        socket = null;
+    };
+}
+
+function sendCommand(command) {
+    "use strict";
+    if (socket !== null && socket.readyState === WebSocket.OPEN) {
+        socket.send(JSON.stringify(command));
+        return true;
+    }
+    return false;
+}
+
+function setSpeed() {
+    "use strict";
+    var interval = parseInt(document.getElementById("speed").value);
+    if (interval > 0) {
+        sendCommand({command: "speed", interval_ms: interval});
+    }
+}
+
+function withSession(url) {
+    "use strict";
+    if (session === null) {
+        return url;
+    }
+    return url + (url.includes("?") ? "&" : "?") + "session=" + session;
 }
 
 function newBoard() {
@@ -27,47 +100,54 @@ function newBoard() {
     }
     if (height != null && width != null) {
         xhttp.onreadystatechange = recieve_JSON;
-        xhttp.open("GET", "NewBoard?height=" + height + "&width=" + width);
+        xhttp.open("GET", withSession("NewBoard?height=" + height + "&width=" + width));
         xhttp.send();
     }
 }
 
 function reset() {
     "use strict";
+    sendCommand({command: "pause"});
     started = false;
     document.getElementById("startButton").innerHTML = "Start";
     xhttp.onreadystatechange = recieve_JSON;
-    xhttp.open("GET", "ResetBoard");
+    xhttp.open("GET", withSession("ResetBoard"));
     xhttp.send();
 }
 
 function Advance() {
     "use strict";
+    sendCommand({command: "pause"});
     started = false;
     document.getElementById("startButton").innerHTML = "Start";
     xhttp.onreadystatechange = recieve_JSON;
-    xhttp.open("GET", "Advance");
+    xhttp.open("GET", withSession("Advance"));
     xhttp.send();
 }
 
 function randomize() {
     "use strict";
+    sendCommand({command: "pause"});
     started = false;
     generations = 0;
     document.getElementById("startButton").innerHTML = "Start";
     xhttp.onreadystatechange = recieve_JSON;
-    xhttp.open("GET", "Randomize");
+    xhttp.open("GET", withSession("Randomize"));
     xhttp.send();
 }
 
 async function start() {
     "use strict";
+    // With the socket the server keeps the time, the button follows what it reports
+    if (sendCommand({command: started ? "pause" : "play"})) {
+        return;
+    }
     if (document.getElementById("startButton").innerHTML === "Start") {
         document.getElementById("startButton").innerHTML = "Stop";
         started = true;
         while (started === true) {
             xhttp.onreadystatechange = recieve_JSON;
-            xhttp.open("GET", "Advance");
+            xhttp.open("GET", withSession("Advance"));
             xhttp.send();
             await new Promise(r => setTimeout(r, 750));
         }
@@ -81,32 +161,36 @@ function recieve_JSON() {
     "use strict";
     if (xhttp.readyState == 4 && xhttp.status == 200) {
         //document.getElementById("game").textContent = xhttp.responseText;
-        var json = JSON.parse(xhttp.responseText);
-        m_height = parseInt(json.height);
-        m_width = parseInt(json.width);
-        //console.log(json);
-        generations = parseInt(json.generations);
-        liveCells = parseInt(json.live_cells);
-        document.getElementById("generations").innerHTML = "Generations: " + generations;
-        document.getElementById("liveCells").innerHTML = "Live Cells: " + liveCells;
-        //console.log(parseInt(json.GameOfLife.width));
-        var array = new Array(parseInt(json.width));
-        for (var i = 0; i < array.length; i++) {
-            array[i] = new Array(parseInt(json.height));
-        }
-        var j = 0;
-        for (var x = 0; x < array.length; x++) {
-            for (var y = 0; y < array[x].length; y++) {
-                array[x][y] = json.array[j];
-                if (array[x][y] == "1") {
-                    last_generations_array[x][y] = json.array[j];
-                }
-                j++;
+        show_board(JSON.parse(xhttp.responseText));
+    }
+}
+
+function show_board(json) {
+    "use strict";
+    m_height = parseInt(json.height);
+    m_width = parseInt(json.width);
+    //console.log(json);
+    generations = parseInt(json.generations);
+    liveCells = parseInt(json.live_cells);
+    document.getElementById("generations").innerHTML = "Generations: " + generations;
+    document.getElementById("liveCells").innerHTML = "Live Cells: " + liveCells;
+    //console.log(parseInt(json.GameOfLife.width));
+    var array = new Array(parseInt(json.width));
+    for (var i = 0; i < array.length; i++) {
+        array[i] = new Array(parseInt(json.height));
+    }
+    var j = 0;
+    for (var x = 0; x < array.length; x++) {
+        for (var y = 0; y < array[x].length; y++) {
+            array[x][y] = json.array[j];
+            if (array[x][y] == "1") {
+                last_generations_array[x][y] = json.array[j];
             }
+            j++;
         }
-        //console.log(array);
-        generate_table(array);
     }
+    //console.log(array);
+    generate_table(array);
 }
 
 function swap_data(id) {
@@ -122,12 +206,58 @@ function swap_data(id) {
         cell.classList.remove("activatedbefore");
         cell.classList.add("on");
     }
+    if (player !== null) {
+        placeCell(id, cell.classList.contains("on"));
+        return;
+    }
     xhttp.onreadystatechange = state_change;
-    xhttp.open("GET", "ChangeValue?id=" + id);
+    xhttp.open("GET", withSession("ChangeValue?id=" + id));
     xhttp.send();
 
 }
 
+// Games created with colours take players, each one gets a colour of their own
+function joinGame() {
+    "use strict";
+    let name = prompt("Please enter your name", "");
+    if (name === null || session === null) {
+        return;
+    }
+    xhttp.onreadystatechange = function () {
+        if (xhttp.readyState == 4) {
+            var json = JSON.parse(xhttp.responseText);
+            if (xhttp.status == 201) {
+                player = json.id;
+                document.getElementById("player").innerHTML = "Playing as " + json.name + " in colour " + json.colour;
+            } else {
+                alert(json.error);
+            }
+        }
+    };
+    xhttp.open("POST", "api/v1/games/" + session + "/players");
+    xhttp.setRequestHeader("Content-Type", "application/json");
+    xhttp.send(JSON.stringify({name: name}));
+}
+
+function placeCell(id, alive) {
+    "use strict";
+    // Cells are numbered row by row like on the server
+    var cell = {row: Math.floor(id / m_width), column: id % m_width, alive: alive};
+    xhttp.onreadystatechange = function () {
+        if (xhttp.readyState == 4) {
+            var json = JSON.parse(xhttp.responseText);
+            if (xhttp.status == 200) {
+                show_board(json);
+            } else {
+                alert(json.error);
+            }
+        }
+    };
+    xhttp.open("PATCH", "api/v1/games/" + session + "/players/" + player + "/cells");
+    xhttp.setRequestHeader("Content-Type", "application/json");
+    xhttp.send(JSON.stringify({cells: [cell]}));
+}
+
 function state_change() {
     "use strict";
     if (xhttp.readyState == 4 && xhttp.status == 200) {
@@ -167,6 +297,10 @@ function generate_table(array) {
                 }
             } else {
                 cell.classList.add("on");
+                // Colour games send numbers, one per colour
+                if (typeof array[i][j] === "number") {
+                    cell.classList.add("colour" + array[i][j]);
+                }
             }
             cell.setAttribute("onclick", "swap_data(" + x + ")");
 
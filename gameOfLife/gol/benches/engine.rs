@@ -0,0 +1,172 @@
+/*
+Benchmarks of the engines, run with cargo bench, or cargo bench -- tick to only run
+the benchmarks whose name contains "tick". Every board is filled from the same seed,
+so two runs measure the same work and their numbers can be compared.
+*/
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use gol::elementary::ElementaryAutomaton;
+use gol::life3d::{Rule3D, Universe3D};
+use gol::margolus::{BlockRule, MargolusAutomaton};
+use gol::ruletable::{RuleTable, RuleTableAutomaton};
+use gol::stochastic::{Noise, StochasticLife, UpdateMode};
+use gol::turmite::{TurmiteRule, TurmiteWorld};
+use gol::{CellularAutomaton, Game, Universe};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const SEED: u64 = 42;
+const SIZES: [u32; 3] = [64, 256, 1024];
+const DENSITIES: [f64; 3] = [0.1, 0.3, 0.5];
+// Each benchmark runs at least this long after a short warm up
+const MEASURE_TIME: Duration = Duration::from_secs(2);
+const WARM_UP_TIME: Duration = Duration::from_millis(300);
+
+fn main() {
+    let filter: Option<String> = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let selected = |name: &str| filter.as_ref().is_none_or(|filter| name.contains(filter));
+    println!(
+        "{:<40} {:>14} {:>16} {:>14}",
+        "benchmark", "generations/s", "cells/s", "time/iter"
+    );
+
+    for size in SIZES {
+        for density in DENSITIES {
+            let name = format!("tick/{}x{}/{}", size, size, density);
+            if selected(&name) {
+                let mut universe = Universe::new(size, size);
+                fill(&mut universe, density);
+                let cells = size as u64 * size as u64;
+                bench(&name, cells, || universe.tick());
+            }
+        }
+    }
+
+    for size in SIZES {
+        let name = format!("live_neighbor_count/{}x{}", size, size);
+        if selected(&name) {
+            let mut universe = Universe::new(size, size);
+            fill(&mut universe, 0.3);
+            // One iteration counts the neighbours of every cell, like a tick does
+            bench(&name, size as u64 * size as u64, || {
+                for row in 0..size {
+                    for column in 0..size {
+                        black_box(universe.live_neighbor_count(row, column));
+                    }
+                }
+            });
+        }
+    }
+
+    for size in SIZES {
+        let name = format!("serialize/{}x{}", size, size);
+        if selected(&name) {
+            let mut universe = Universe::new(size, size);
+            fill(&mut universe, 0.3);
+            let mut game = Game::with_automaton(Box::new(universe));
+            bench(&name, size as u64 * size as u64, || {
+                black_box(game.serialize());
+            });
+        }
+    }
+
+    // The other engines on a board of the same size, stepped through Game like the servers do
+    let size = 256;
+    let engines: Vec<(&str, Box<dyn CellularAutomaton>)> = vec![
+        ("life", Box::new(Universe::new(size, size))),
+        (
+            "stochastic",
+            Box::new(
+                StochasticLife::new(
+                    size,
+                    size,
+                    UpdateMode::RandomSequential,
+                    Noise::default(),
+                    SEED,
+                )
+                .unwrap(),
+            ),
+        ),
+        (
+            "elementary",
+            Box::new(ElementaryAutomaton::new(30, size, size)),
+        ),
+        (
+            "wireworld",
+            Box::new(RuleTableAutomaton::new(RuleTable::wireworld(), size, size)),
+        ),
+        (
+            "margolus",
+            Box::new(MargolusAutomaton::new(BlockRule::critters(), size, size)),
+        ),
+        (
+            "life3d",
+            Box::new(Universe3D::new(64, 64, 64, Rule3D::default()).unwrap()),
+        ),
+        (
+            "turmite",
+            Box::new(TurmiteWorld::new(TurmiteRule::langtons_ant(), size, size)),
+        ),
+    ];
+    for (engine, mut automaton) in engines {
+        let name = format!(
+            "engine/{}/{}x{}x{}",
+            engine,
+            automaton.width(),
+            automaton.height(),
+            automaton.layers()
+        );
+        if !selected(&name) {
+            continue;
+        }
+        fill(automaton.as_mut(), 0.3);
+        let cells =
+            automaton.width() as u64 * automaton.height() as u64 * automaton.layers() as u64;
+        let mut game = Game::with_automaton(automaton);
+        bench(&name, cells, || game.tick());
+    }
+}
+
+// Fills every layer from the seeded generator, live cells get a random state above 0
+fn fill(automaton: &mut dyn CellularAutomaton, density: f64) {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let states = automaton.states().max(2);
+    for layer in 0..automaton.layers() {
+        automaton.select_layer(layer);
+        for row in 0..automaton.height() {
+            for column in 0..automaton.width() {
+                let state = if rng.gen_bool(density) {
+                    rng.gen_range(1..states)
+                } else {
+                    0
+                };
+                automaton.set_cell(row, column, state);
+            }
+        }
+    }
+    automaton.select_layer(0);
+}
+
+// Runs one iteration, one generation for the engines, over and over and prints the rates
+fn bench(name: &str, cells: u64, mut iteration: impl FnMut()) {
+    let start = Instant::now();
+    while start.elapsed() < WARM_UP_TIME {
+        iteration();
+    }
+    let mut iterations = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < MEASURE_TIME {
+        iteration();
+        iterations += 1;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let rate = iterations as f64 / seconds;
+    println!(
+        "{:<40} {:>14.1} {:>16.0} {:>12.3}ms",
+        name,
+        rate,
+        rate * cells as f64,
+        1000.0 / rate
+    );
+}
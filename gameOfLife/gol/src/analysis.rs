@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+
+use crate::plane::Plane;
+use crate::Universe;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    StillLife,
+    Oscillator,
+    Spaceship,
+}
+
+/*
+What a pattern does when it runs on its own, away from the edges of the board.
+dx and dy are the columns and rows it moves per period, heat is the average number of cells
+changing per generation and volatility the share of the cells ever alive that change at all.
+Spaceships have no volatility, all their cells move.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub kind: PatternKind,
+    pub period: u32,
+    pub dx: i64,
+    pub dy: i64,
+    // Like c/4 diagonal, None for patterns that stay in place
+    pub speed: Option<String>,
+    // Width and height of the smallest phase
+    pub bounding_box: (u32, u32),
+    pub min_population: u32,
+    pub max_population: u32,
+    pub heat: f64,
+    pub volatility: Option<f64>,
+}
+
+impl Universe {
+    /*
+    Runs the live cells of the board on the unbounded plane under the board's rule for up to
+    max_generations generations until they repeat and describes them. The cells are taken
+    as they are on the board, a pattern crossing the edge of the torus comes out in pieces.
+    */
+    pub fn analyze(&self, max_generations: u32) -> Result<Analysis, String> {
+        if self.rule.next_state(false, 0) {
+            return Err(format!(
+                "rule {} with B0 fills the whole plane, its patterns cannot be analysed",
+                self.rule
+            ));
+        }
+        let cells = self
+            .array
+            .iter()
+            .enumerate()
+            .filter(|&(_, &alive)| alive)
+            .map(|(idx, _)| {
+                let idx = idx as u32;
+                ((idx / self.width) as i64, (idx % self.width) as i64)
+            });
+        let start = Plane::from_cells(cells);
+        if start.is_empty() {
+            return Err("the board is empty".to_owned());
+        }
+        let periodicity = start
+            .periodicity(max_generations, self.rule)
+            .ok_or_else(|| {
+                format!(
+                    "the pattern does not repeat within {} generations",
+                    max_generations
+                )
+            })?;
+        let mut phases = vec![start];
+        for _ in 0..periodicity.period {
+            let next = phases.last().unwrap().step(self.rule);
+            phases.push(next);
+        }
+        let (dx, dy) = (periodicity.columns, periodicity.rows);
+        let kind = match (periodicity.period, dx, dy) {
+            (1, 0, 0) => PatternKind::StillLife,
+            (_, 0, 0) => PatternKind::Oscillator,
+            _ => PatternKind::Spaceship,
+        };
+        Ok(Analysis {
+            kind,
+            period: periodicity.period,
+            dx,
+            dy,
+            speed: speed(periodicity.period, dx, dy),
+            bounding_box: smallest_box(&phases[..phases.len() - 1]),
+            min_population: phases.iter().map(|p| p.population() as u32).min().unwrap(),
+            max_population: phases.iter().map(|p| p.population() as u32).max().unwrap(),
+            heat: heat(&phases),
+            volatility: match kind {
+                PatternKind::Spaceship => None,
+                _ => Some(volatility(&phases)),
+            },
+        })
+    }
+}
+
+/*
+Speed in the usual notation: c/4 for one cell in four generations, 2c/5 for two cells in five,
+then orthogonal, diagonal or, for anything else, oblique with the move as in (2,1)c/6
+*/
+fn speed(period: u32, dx: i64, dy: i64) -> Option<String> {
+    if dx == 0 && dy == 0 {
+        return None;
+    }
+    let (columns, rows) = (dx.unsigned_abs(), dy.unsigned_abs());
+    let distance = columns.max(rows);
+    let fraction = |cells: u64| {
+        let divisor = gcd(cells, period as u64);
+        let (cells, period) = (cells / divisor, period as u64 / divisor);
+        let cells = if cells == 1 {
+            String::new()
+        } else {
+            cells.to_string()
+        };
+        if period == 1 {
+            format!("{}c", cells)
+        } else {
+            format!("{}c/{}", cells, period)
+        }
+    };
+    Some(if columns == 0 || rows == 0 {
+        format!("{} orthogonal", fraction(distance))
+    } else if columns == rows {
+        format!("{} diagonal", fraction(distance))
+    } else {
+        format!("({},{})c/{} oblique", distance, columns.min(rows), period)
+    })
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn smallest_box(phases: &[Plane]) -> (u32, u32) {
+    phases
+        .iter()
+        .filter_map(|phase| phase.bounding_box())
+        .map(|(top, left, bottom, right)| ((right - left + 1) as u32, (bottom - top + 1) as u32))
+        .min_by_key(|&(width, height)| (width as u64 * height as u64, width))
+        .unwrap_or((0, 0))
+}
+
+// Cells changing between two generations, compared in place, averaged over one period
+fn heat(phases: &[Plane]) -> f64 {
+    let changes: usize = phases
+        .windows(2)
+        .map(|pair| {
+            pair[0]
+                .cells()
+                .symmetric_difference(pair[1].cells())
+                .count()
+        })
+        .sum();
+    changes as f64 / (phases.len() - 1) as f64
+}
+
+// Rotor cells, alive in some phases only, as a share of the cells alive in any phase
+fn volatility(phases: &[Plane]) -> f64 {
+    let phases = &phases[..phases.len() - 1];
+    let all = phases
+        .iter()
+        .fold(Plane::default(), |union, phase| union.union(phase));
+    let stator = all
+        .cells()
+        .iter()
+        .filter(|&&(row, column)| phases.iter().all(|phase| phase.contains(row, column)))
+        .count();
+    (all.population() - stator) as f64 / all.population() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    // A 12x12 board with the given cells alive
+    fn board(cells: &[(u32, u32)]) -> Universe {
+        let mut universe = Universe::new(12, 12);
+        for &(row, column) in cells {
+            universe.set_alive(row, column, true);
+        }
+        universe
+    }
+
+    #[test]
+    fn a_block_is_a_still_life() {
+        let analysis = board(&[(4, 4), (4, 5), (5, 4), (5, 5)])
+            .analyze(10)
+            .unwrap();
+        assert_eq!(analysis.kind, PatternKind::StillLife);
+        assert_eq!(analysis.period, 1);
+        assert_eq!(analysis.speed, None);
+        assert_eq!(analysis.bounding_box, (2, 2));
+        assert_eq!((analysis.min_population, analysis.max_population), (4, 4));
+        assert_eq!(analysis.heat, 0.0);
+        assert_eq!(analysis.volatility, Some(0.0));
+    }
+
+    #[test]
+    fn a_blinker_turns_four_of_its_five_cells() {
+        let analysis = board(&[(5, 4), (5, 5), (5, 6)]).analyze(10).unwrap();
+        assert_eq!(analysis.kind, PatternKind::Oscillator);
+        assert_eq!(analysis.period, 2);
+        assert_eq!((analysis.dx, analysis.dy), (0, 0));
+        // Two cells die and two are born every generation, only the middle one stays
+        assert_eq!(analysis.heat, 4.0);
+        assert_eq!(analysis.volatility, Some(0.8));
+        assert_eq!(analysis.bounding_box, (1, 3));
+    }
+
+    #[test]
+    fn a_glider_flies_at_c_4_diagonally() {
+        let glider = board(&[(1, 2), (2, 3), (3, 1), (3, 2), (3, 3)]);
+        let analysis = glider.analyze(10).unwrap();
+        assert_eq!(analysis.kind, PatternKind::Spaceship);
+        assert_eq!(analysis.period, 4);
+        assert_eq!((analysis.dx, analysis.dy), (1, 1));
+        assert_eq!(analysis.speed.as_deref(), Some("c/4 diagonal"));
+        assert_eq!((analysis.min_population, analysis.max_population), (5, 5));
+        assert_eq!(analysis.bounding_box, (3, 3));
+        assert_eq!(analysis.volatility, None);
+        assert!(glider.analyze(3).is_err());
+    }
+
+    #[test]
+    fn a_lightweight_spaceship_flies_at_c_2() {
+        let lwss = board(&[
+            (4, 2),
+            (4, 5),
+            (5, 6),
+            (6, 2),
+            (6, 6),
+            (7, 3),
+            (7, 4),
+            (7, 5),
+            (7, 6),
+        ]);
+        let analysis = lwss.analyze(10).unwrap();
+        assert_eq!(analysis.period, 4);
+        assert_eq!((analysis.dx, analysis.dy), (2, 0));
+        assert_eq!(analysis.speed.as_deref(), Some("c/2 orthogonal"));
+        assert_eq!((analysis.min_population, analysis.max_population), (9, 12));
+    }
+
+    #[test]
+    fn speeds_are_written_as_usual() {
+        assert_eq!(speed(1, 0, 1).as_deref(), Some("c orthogonal"));
+        assert_eq!(speed(5, 0, -2).as_deref(), Some("2c/5 orthogonal"));
+        assert_eq!(speed(8, 2, 2).as_deref(), Some("c/4 diagonal"));
+        assert_eq!(speed(6, -1, 2).as_deref(), Some("(2,1)c/6 oblique"));
+        assert_eq!(speed(3, 0, 0), None);
+    }
+
+    #[test]
+    fn empty_boards_and_b0_rules_are_errors() {
+        assert!(Universe::new(5, 5).analyze(10).is_err());
+        let mut blinker = board(&[(5, 4), (5, 5), (5, 6)]);
+        blinker.set_rule(Rule::new(&[0, 3], &[2, 3]));
+        assert!(blinker.analyze(10).is_err());
+    }
+}
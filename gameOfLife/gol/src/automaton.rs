@@ -0,0 +1,302 @@
+use std::fmt::Debug;
+
+use crate::Universe;
+
+/*
+A board of cells evolving in steps. Game runs any implementation of it, so both servers
+can use other engines without changes.
+Cell states are numbers with 0 as the dead state, two state engines only use 0 and 1.
+*/
+pub trait CellularAutomaton: Debug + Send {
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    fn generations(&self) -> u32;
+
+    // Number of states a cell can be in, editing a cell cycles through them
+    fn states(&self) -> u8 {
+        2
+    }
+
+    // Number of z-layers for engines with a third dimension, get_cell and set_cell work on the selected one
+    fn layers(&self) -> u32 {
+        1
+    }
+
+    fn select_layer(&mut self, _layer: u32) {}
+
+    fn get_cell(&self, row: u32, column: u32) -> u8;
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8);
+
+    fn step(&mut self);
+
+    fn population(&self) -> u32 {
+        let mut count = 0;
+        for row in 0..self.height() {
+            for column in 0..self.width() {
+                count += (self.get_cell(row, column) != 0) as u32;
+            }
+        }
+        count
+    }
+
+    // Replaces the board with an empty one of the given size
+    fn resize(&mut self, width: u32, height: u32);
+
+    // Size of the board resize gives, engines that only run on some sizes round up to them
+    fn fitted_size(&self, width: u32, height: u32) -> (u32, u32) {
+        (width, height)
+    }
+
+    fn clear(&mut self);
+
+    fn randomize(&mut self);
+
+    // JSON sent to the frontends, with at least width, height, generations, live_cells and array
+    fn serialize(&self) -> String;
+
+    // The board as a Universe for engines running Life-like rules, None for every other engine
+    fn as_universe(&self) -> Option<&Universe> {
+        None
+    }
+}
+
+impl CellularAutomaton for Universe {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.is_alive(row, column) as u8
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        self.set_alive(row, column, state != 0);
+    }
+
+    fn step(&mut self) {
+        self.tick();
+    }
+
+    fn population(&self) -> u32 {
+        Universe::population(self)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let (rule, topology) = (self.rule, self.topology);
+        *self = Universe::new(width, height);
+        self.rule = rule;
+        self.topology = topology;
+    }
+
+    fn clear(&mut self) {
+        Universe::clear(self);
+    }
+
+    fn randomize(&mut self) {
+        Universe::randomize(self);
+    }
+
+    fn serialize(&self) -> String {
+        // It is safe to do so, according to serde documentation
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    fn as_universe(&self) -> Option<&Universe> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colours::{ColourLife, Variant};
+    use crate::elementary::ElementaryAutomaton;
+    use crate::life3d::{Rule3D, Universe3D};
+    use crate::margolus::{BlockRule, MargolusAutomaton};
+    use crate::ruletable::{RuleTable, RuleTableAutomaton};
+    use crate::stochastic::{Noise, StochasticLife, UpdateMode};
+    use crate::turmite::{TurmiteRule, TurmiteWorld};
+    use crate::Game;
+
+    // Three states, every step moves each cell on to the next one
+    #[derive(Debug)]
+    struct Cycles {
+        width: u32,
+        height: u32,
+        generations: u32,
+        cells: Vec<u8>,
+    }
+
+    impl CellularAutomaton for Cycles {
+        fn width(&self) -> u32 {
+            self.width
+        }
+
+        fn height(&self) -> u32 {
+            self.height
+        }
+
+        fn generations(&self) -> u32 {
+            self.generations
+        }
+
+        fn states(&self) -> u8 {
+            3
+        }
+
+        fn get_cell(&self, row: u32, column: u32) -> u8 {
+            self.cells[(row * self.width + column) as usize]
+        }
+
+        fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+            self.cells[(row * self.width + column) as usize] = state;
+        }
+
+        fn step(&mut self) {
+            self.generations += 1;
+            for cell in self.cells.iter_mut() {
+                *cell = (*cell + 1) % 3;
+            }
+        }
+
+        fn resize(&mut self, width: u32, height: u32) {
+            *self = Cycles::new(width, height);
+        }
+
+        fn clear(&mut self) {
+            self.cells.fill(0);
+        }
+
+        fn randomize(&mut self) {
+            self.cells.fill(1);
+        }
+
+        fn serialize(&self) -> String {
+            serde_json::json!({
+                "width": self.width,
+                "height": self.height,
+                "generations": self.generations,
+                "live_cells": self.population(),
+                "array": self.cells,
+            })
+            .to_string()
+        }
+    }
+
+    impl Cycles {
+        fn new(width: u32, height: u32) -> Self {
+            Self {
+                width,
+                height,
+                generations: 0,
+                cells: vec![0; (width * height) as usize],
+            }
+        }
+    }
+
+    #[test]
+    fn a_game_runs_an_engine_it_does_not_know() {
+        let mut game = Game::with_automaton(Box::new(Cycles::new(3, 2)));
+        // Editing a cell cycles through all three states
+        for state in [1, 2, 0] {
+            game.change_value(4).unwrap();
+            assert_eq!(game.automaton().get_cell(1, 1), state);
+        }
+        assert!(game.change_value(6).is_err());
+        game.change_value(0).unwrap();
+        game.tick();
+        assert_eq!(game.automaton().get_cell(0, 0), 2);
+        assert_eq!(game.automaton().get_cell(0, 1), 1);
+        // Every state but 0 counts as alive
+        assert_eq!(game.automaton().population(), 6);
+        assert_eq!(game.universe().population(), 6);
+        assert!(game.life().is_err());
+        game.change_size(4, 4);
+        assert_eq!(game.automaton().fitted_size(5, 5), (5, 5));
+        assert_eq!(game.automaton().population(), 16);
+        game.negate_everything();
+        assert_eq!(game.automaton().population(), 0);
+    }
+
+    fn engines() -> Vec<Box<dyn CellularAutomaton>> {
+        let (width, height) = (8, 6);
+        vec![
+            Box::new(Universe::new(width, height)),
+            Box::new(ColourLife::new(Variant::QuadLife, width, height)),
+            Box::new(
+                StochasticLife::new(
+                    width,
+                    height,
+                    UpdateMode::RandomSequential,
+                    Noise::default(),
+                    1,
+                )
+                .unwrap(),
+            ),
+            Box::new(ElementaryAutomaton::new(30, width, height)),
+            Box::new(RuleTableAutomaton::new(
+                RuleTable::wireworld(),
+                width,
+                height,
+            )),
+            Box::new(MargolusAutomaton::new(BlockRule::critters(), width, height)),
+            Box::new(Universe3D::new(width, height, 3, Rule3D::default()).unwrap()),
+            Box::new(TurmiteWorld::new(
+                TurmiteRule::langtons_ant(),
+                width,
+                height,
+            )),
+        ]
+    }
+
+    #[test]
+    fn every_engine_serializes_what_the_frontends_draw() {
+        for automaton in engines() {
+            let mut game = Game::with_automaton(automaton);
+            game.randomize();
+            game.tick();
+            game.tick();
+            let automaton = game.automaton();
+            let value: serde_json::Value = serde_json::from_str(&automaton.serialize()).unwrap();
+            assert_eq!(value["width"], automaton.width(), "{:?}", automaton);
+            assert_eq!(value["height"], automaton.height(), "{:?}", automaton);
+            assert_eq!(value["generations"], automaton.generations());
+            assert!(value["live_cells"].is_u64(), "{:?}", automaton);
+            let cells = (automaton.width() * automaton.height()) as usize;
+            assert_eq!(value["array"].as_array().unwrap().len(), cells);
+        }
+    }
+
+    #[test]
+    fn population_counts_the_cells_in_a_state_other_than_0() {
+        for mut automaton in engines() {
+            automaton.randomize();
+            automaton.step();
+            let mut count = 0;
+            for row in 0..automaton.height() {
+                for column in 0..automaton.width() {
+                    count += (automaton.get_cell(row, column) != 0) as u32;
+                }
+            }
+            // A 3D engine counts the whole volume, the cells shown are one layer of it
+            if automaton.layers() == 1 {
+                assert_eq!(automaton.population(), count, "{:?}", automaton);
+            } else {
+                assert!(automaton.population() >= count);
+            }
+            automaton.clear();
+            assert_eq!(automaton.population(), 0, "{:?}", automaton);
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::plane::{Periodicity, Plane};
+use crate::{Rule, Topology, Universe};
+
+// Longest period looked for before an object is counted as unknown
+pub const MAX_PERIOD: u32 = 120;
+
+// apgcode used for everything that does not repeat within MAX_PERIOD generations
+pub const UNKNOWN: &str = "zz_UNKNOWN";
+
+// Cells closer than this belong to the same object, so oscillators like the beacon stay in one piece
+const OBJECT_DISTANCE: i64 = 2;
+
+const WECHSLER_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+const ZERO_RUN_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// The eight rotations and reflections of the square
+type Orientation = fn(i64, i64) -> (i64, i64);
+const ORIENTATIONS: [Orientation; 8] = [
+    |r, c| (r, c),
+    |r, c| (r, -c),
+    |r, c| (-r, c),
+    |r, c| (-r, -c),
+    |r, c| (c, r),
+    |r, c| (c, -r),
+    |r, c| (-c, r),
+    |r, c| (-c, -r),
+];
+
+// apgcodes of the common objects and their names
+const KNOWN_OBJECTS: &[(&str, &str)] = &[
+    ("xs4_33", "block"),
+    ("xs4_252", "tub"),
+    ("xs5_253", "boat"),
+    ("xs6_356", "ship"),
+    ("xs6_696", "beehive"),
+    ("xs6_25a4", "barge"),
+    ("xs6_39c", "aircraft carrier"),
+    ("xs6_bd", "snake"),
+    ("xs7_25ac", "long boat"),
+    ("xs7_2596", "loaf"),
+    ("xs7_178c", "eater 1"),
+    ("xs8_6996", "pond"),
+    ("xs8_35ac", "long ship"),
+    ("xs8_69ic", "mango"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xp3_co9nas0san9oczgoldlo0oldlogz1047210127401", "pulsar"),
+    ("xp15_4r4z4r4", "pentadecathlon"),
+    ("xq4_153", "glider"),
+    ("xq4_6frc", "lightweight spaceship"),
+    ("xq4_27dee6", "middleweight spaceship"),
+    ("xq4_27deee6", "heavyweight spaceship"),
+];
+
+/*
+Objects found on a board, counted by their apgcode:
+xs<population>_ for still lifes, xp<period>_ for oscillators and xq<period>_ for spaceships,
+followed by the extended Wechsler code of the object
+*/
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Census {
+    pub objects: BTreeMap<String, u32>,
+}
+
+impl Census {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, apgcode: &str, count: u32) {
+        *self.objects.entry(apgcode.to_owned()).or_insert(0) += count;
+    }
+
+    pub fn merge(&mut self, other: &Census) {
+        for (apgcode, count) in &other.objects {
+            self.add(apgcode, *count);
+        }
+    }
+
+    pub fn count(&self, apgcode: &str) -> u32 {
+        self.objects.get(apgcode).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u32 {
+        self.objects.values().sum()
+    }
+
+    // Most common objects first, ties in apgcode order
+    pub fn sorted(&self) -> Vec<(&str, u32)> {
+        let mut entries: Vec<(&str, u32)> = self
+            .objects
+            .iter()
+            .map(|(apgcode, count)| (apgcode.as_str(), *count))
+            .collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        entries
+    }
+}
+
+// Common name of an apgcode, e.g. "glider" for xq4_153
+pub fn name(apgcode: &str) -> Option<&'static str> {
+    KNOWN_OBJECTS
+        .iter()
+        .find(|(code, _)| *code == apgcode)
+        .map(|(_, name)| *name)
+}
+
+/*
+apgcode of a single object given by its live cells as (row, column) under Conway's rule,
+UNKNOWN if it does not repeat within MAX_PERIOD generations
+*/
+pub fn classify(cells: &[(i64, i64)]) -> String {
+    classify_with(cells, Rule::default())
+}
+
+// Like classify under any rule without B0
+pub fn classify_with(cells: &[(i64, i64)], rule: Rule) -> String {
+    let object = Plane::from_cells(cells.iter().copied());
+    match object.periodicity(MAX_PERIOD, rule) {
+        Some(periodicity) => apgcode(&object, periodicity, rule),
+        None => UNKNOWN.to_owned(),
+    }
+}
+
+impl Universe {
+    /*
+    Splits the board into objects and classifies each of them on its own under the board's rule.
+    Meant for settled boards, anything still evolving ends up as UNKNOWN.
+    Rules with B0 have no objects of their own, they are an error.
+    */
+    pub fn census(&self) -> Result<Census, String> {
+        if self.rule.next_state(false, 0) {
+            return Err(format!(
+                "rule {} with B0 fills the whole plane, its objects cannot be counted",
+                self.rule
+            ));
+        }
+        let mut census = Census::new();
+        for object in self.objects() {
+            census_object(&object, self.rule, &mut census);
+        }
+        Ok(census)
+    }
+
+    /*
+    Groups of live cells near each other. On a torus their coordinates are unwrapped
+    so an object crossing the edge stays in one piece, a bounded board has no cells beyond its edges.
+    */
+    pub(crate) fn objects(&self) -> Vec<Plane> {
+        let width = self.width as i64;
+        let height = self.height as i64;
+        let mut seen = vec![false; self.array.len()];
+        let mut objects = Vec::new();
+        for idx in 0..self.array.len() {
+            if !self.array[idx] || seen[idx] {
+                continue;
+            }
+            seen[idx] = true;
+            let mut stack = vec![(idx as i64 / width, idx as i64 % width)];
+            let mut cells = Vec::new();
+            while let Some((row, column)) = stack.pop() {
+                cells.push((row, column));
+                for delta_row in -OBJECT_DISTANCE..=OBJECT_DISTANCE {
+                    for delta_col in -OBJECT_DISTANCE..=OBJECT_DISTANCE {
+                        let neighbor_row = row + delta_row;
+                        let neighbor_col = column + delta_col;
+                        if self.topology == Topology::Bounded
+                            && !((0..height).contains(&neighbor_row)
+                                && (0..width).contains(&neighbor_col))
+                        {
+                            continue;
+                        }
+                        let neighbor = (neighbor_row.rem_euclid(height) * width
+                            + neighbor_col.rem_euclid(width))
+                            as usize;
+                        if self.array[neighbor] && !seen[neighbor] {
+                            seen[neighbor] = true;
+                            stack.push((neighbor_row, neighbor_col));
+                        }
+                    }
+                }
+            }
+            objects.push(Plane::from_cells(cells));
+        }
+        objects
+    }
+}
+
+/*
+Counts a group of nearby cells. Groups made of touching pieces that do not
+interact, like a block next to a blinker, are counted piece by piece.
+*/
+fn census_object(object: &Plane, rule: Rule, census: &mut Census) {
+    let periodicity = match object.periodicity(MAX_PERIOD, rule) {
+        Some(periodicity) => periodicity,
+        None => {
+            census.add(UNKNOWN, 1);
+            return;
+        }
+    };
+    let pieces = object.clusters(1);
+    if pieces.len() > 1 && independent(object, &pieces, periodicity.period, rule) {
+        for piece in &pieces {
+            census_object(piece, rule, census);
+        }
+        return;
+    }
+    census.add(&apgcode(object, periodicity, rule), 1);
+}
+
+// Whether the pieces evolve on their own exactly as they do together
+fn independent(object: &Plane, pieces: &[Plane], period: u32, rule: Rule) -> bool {
+    if pieces
+        .iter()
+        .any(|piece| piece.periodicity(period, rule).is_none())
+    {
+        return false;
+    }
+    let mut whole = object.clone();
+    let mut pieces = pieces.to_vec();
+    for _ in 0..period {
+        whole = whole.step(rule);
+        pieces = pieces.iter().map(|piece| piece.step(rule)).collect();
+        let together = pieces
+            .iter()
+            .fold(Plane::default(), |union, piece| union.union(piece));
+        if together != whole {
+            return false;
+        }
+    }
+    true
+}
+
+// apgcode of a periodic object, the shortest and then smallest code over all phases and orientations
+fn apgcode(object: &Plane, periodicity: Periodicity, rule: Rule) -> String {
+    let prefix = if periodicity.rows != 0 || periodicity.columns != 0 {
+        format!("xq{}", periodicity.period)
+    } else if periodicity.period == 1 {
+        format!("xs{}", object.population())
+    } else {
+        format!("xp{}", periodicity.period)
+    };
+    let mut phase = object.clone();
+    let mut best = canonical_code(&phase);
+    for _ in 1..periodicity.period {
+        phase = phase.step(rule);
+        let code = canonical_code(&phase);
+        if (code.len(), &code) < (best.len(), &best) {
+            best = code;
+        }
+    }
+    format!("{}_{}", prefix, best)
+}
+
+// Smallest extended Wechsler code over the eight rotations and reflections
+fn canonical_code(phase: &Plane) -> String {
+    ORIENTATIONS
+        .iter()
+        .map(|orient| {
+            let turned = Plane::from_cells(phase.cells().iter().map(|&(r, c)| orient(r, c)));
+            wechsler(&turned.normalized().0)
+        })
+        .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+        .unwrap_or_default()
+}
+
+/*
+Extended Wechsler format: the pattern is cut into strips of five rows, each column
+of a strip is one digit with the top row as lowest bit, strips are separated by z.
+Trailing zeros of a strip are left out and runs of zeros are shortened to
+w (two), x (three) and y followed by a digit (four to 39).
+*/
+fn wechsler(normalized: &Plane) -> String {
+    let (_, _, bottom, right) = match normalized.bounding_box() {
+        Some(bounds) => bounds,
+        None => return String::new(),
+    };
+    let mut strips = Vec::new();
+    for strip_top in (0..=bottom).step_by(5) {
+        let mut digits: Vec<u8> = (0..=right)
+            .map(|column| {
+                let value = (0..5)
+                    .filter(|bit| normalized.contains(strip_top + bit, column))
+                    .fold(0, |value, bit| value | 1 << bit);
+                WECHSLER_DIGITS[value]
+            })
+            .collect();
+        while digits.last() == Some(&b'0') {
+            digits.pop();
+        }
+        strips.push(compress_zeros(&digits));
+    }
+    strips.join("z")
+}
+
+fn compress_zeros(digits: &[u8]) -> String {
+    let mut out = String::new();
+    let mut zeros = 0;
+    for &digit in digits.iter().chain(std::iter::once(&b'.')) {
+        if digit == b'0' {
+            zeros += 1;
+            continue;
+        }
+        while zeros >= 4 {
+            let run = zeros.min(39);
+            out.push('y');
+            out.push(ZERO_RUN_DIGITS[run - 4] as char);
+            zeros -= run;
+        }
+        match zeros {
+            3 => out.push('x'),
+            2 => out.push('w'),
+            1 => out.push('0'),
+            _ => {}
+        }
+        zeros = 0;
+        if digit != b'.' {
+            out.push(digit as char);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Topology;
+
+    // Live cells of a picture, one string per row with # for a live cell
+    fn cells(rows: &[&str]) -> Vec<(i64, i64)> {
+        let mut cells = Vec::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (column, cell) in line.chars().enumerate() {
+                if cell == '#' {
+                    cells.push((row as i64, column as i64));
+                }
+            }
+        }
+        cells
+    }
+
+    fn board(width: u32, height: u32, cells: &[(i64, i64)]) -> Universe {
+        let mut universe = Universe::new(width, height);
+        for &(row, column) in cells {
+            universe.set_alive(row as u32, column as u32, true);
+        }
+        universe
+    }
+
+    #[test]
+    fn still_lifes() {
+        assert_eq!(classify(&cells(&["##", "##"])), "xs4_33");
+        assert_eq!(classify(&cells(&[".##.", "#..#", ".##."])), "xs6_696");
+        assert_eq!(classify(&cells(&["##.", "#.#", ".#."])), "xs5_253");
+        assert_eq!(
+            classify(&cells(&[".##.", "#..#", "#..#", ".##."])),
+            "xs8_6996"
+        );
+    }
+
+    #[test]
+    fn oscillators_in_every_phase() {
+        assert_eq!(classify(&cells(&["###"])), "xp2_7");
+        assert_eq!(classify(&cells(&["#", "#", "#"])), "xp2_7");
+        assert_eq!(classify(&cells(&[".###", "###."])), "xp2_7e");
+        assert_eq!(
+            classify(&cells(&["##..", "##..", "..##", "..##"])),
+            "xp2_318c"
+        );
+    }
+
+    #[test]
+    fn gliders_in_every_direction() {
+        let gliders = [
+            [".#.", "..#", "###"],
+            [".#.", "#..", "###"],
+            ["###", "..#", ".#."],
+            ["###", "#..", ".#."],
+        ];
+        for glider in gliders {
+            assert_eq!(classify(&cells(&glider)), "xq4_153");
+        }
+    }
+
+    #[test]
+    fn spaceships() {
+        let lwss = cells(&[".#..#", "#....", "#...#", "####."]);
+        assert_eq!(classify(&lwss), "xq4_6frc");
+        assert_eq!(name("xq4_6frc"), Some("lightweight spaceship"));
+    }
+
+    #[test]
+    fn a_methuselah_is_unknown() {
+        assert_eq!(classify(&cells(&[".##", "##.", ".#."])), UNKNOWN);
+    }
+
+    #[test]
+    fn the_rule_decides() {
+        // Dies under Conway's rule, a still life under B3/S012345678
+        let rule: Rule = "B3/S012345678".parse().unwrap();
+        assert_eq!(classify_with(&cells(&["#"]), rule), "xs1_1");
+    }
+
+    #[test]
+    fn census_of_a_board() {
+        let mut cells = cells(&["##......", "##......", "........", "....###."]);
+        // A second block crossing the edge of the torus
+        cells.extend([(6, 0), (6, 9), (7, 0), (7, 9)]);
+        let universe = board(10, 10, &cells);
+        let census = universe.census().unwrap();
+        assert_eq!(census.count("xs4_33"), 2);
+        assert_eq!(census.count("xp2_7"), 1);
+        assert_eq!(census.total(), 3);
+    }
+
+    #[test]
+    fn a_bounded_board_does_not_wrap() {
+        let mut universe = board(10, 8, &[(6, 0), (6, 9), (7, 0), (7, 9)]);
+        universe.set_topology(Topology::Bounded);
+        assert_eq!(universe.census().unwrap().count("xs4_33"), 0);
+    }
+
+    #[test]
+    fn rules_with_b0_have_no_census() {
+        let mut universe = board(5, 5, &[]);
+        universe.set_rule("B0/S8".parse().unwrap());
+        assert!(universe.census().is_err());
+    }
+}
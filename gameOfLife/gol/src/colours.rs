@@ -0,0 +1,241 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::automaton::CellularAutomaton;
+use crate::Rule;
+
+/*
+Life variants with coloured cells. Cells are born and die as in Life, a newborn takes the
+colour most of its live neighbours have and a surviving cell keeps its own.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    // Two colours
+    Immigration,
+    // Four colours, a cell born from three neighbours of different colours takes the fourth one
+    QuadLife,
+}
+
+impl Variant {
+    pub fn colours(&self) -> u8 {
+        match self {
+            Variant::Immigration => 2,
+            Variant::QuadLife => 4,
+        }
+    }
+
+    pub fn from_colours(colours: u8) -> Result<Self, String> {
+        match colours {
+            2 => Ok(Variant::Immigration),
+            4 => Ok(Variant::QuadLife),
+            _ => Err(format!(
+                "there is no variant with {} colours, only 2 (Immigration) and 4 (QuadLife)",
+                colours
+            )),
+        }
+    }
+}
+
+/*
+Board of a colour variant, state 0 is dead and states 1 up to the number of colours are
+live cells of that colour. The edges wrap around like the ones of Universe.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColourLife {
+    variant: Variant,
+    width: u32,
+    height: u32,
+    generations: u32,
+    live_cells: u32,
+    array: Vec<u8>,
+    // Live cells of each colour, the first entry counts colour 1
+    populations: Vec<u32>,
+    #[serde(default)]
+    rule: Rule,
+}
+
+impl ColourLife {
+    pub fn new(variant: Variant, width: u32, height: u32) -> Self {
+        Self {
+            variant,
+            width,
+            height,
+            generations: 0,
+            live_cells: 0,
+            array: vec![0; width as usize * height as usize],
+            populations: vec![0; variant.colours() as usize],
+            rule: Rule::conway(),
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn colours(&self) -> u8 {
+        self.variant.colours()
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    // Live cells of the given colour, 0 for colours the variant does not have
+    pub fn population_of(&self, colour: u8) -> u32 {
+        match colour {
+            0 => 0,
+            colour => self
+                .populations
+                .get(colour as usize - 1)
+                .cloned()
+                .unwrap_or(0),
+        }
+    }
+
+    fn get_index(&self, row: u32, column: u32) -> usize {
+        (row * self.width + column) as usize
+    }
+
+    fn count_populations(&mut self) {
+        self.populations = vec![0; self.colours() as usize];
+        for &state in &self.array {
+            if state != 0 {
+                self.populations[state as usize - 1] += 1;
+            }
+        }
+        self.live_cells = self.populations.iter().sum();
+    }
+
+    // Live neighbours of each colour, index 0 stays unused
+    fn neighbor_colours(&self, row: u32, column: u32) -> [u8; 5] {
+        let mut counts = [0; 5];
+        for delta_row in [self.height - 1, 0, 1] {
+            for delta_col in [self.width - 1, 0, 1] {
+                if delta_row == 0 && delta_col == 0 {
+                    continue;
+                }
+                let neighbor_row = (row + delta_row) % self.height;
+                let neighbor_col = (column + delta_col) % self.width;
+                let state = self.array[self.get_index(neighbor_row, neighbor_col)];
+                counts[state as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    /*
+    Colour of a newborn: the one most neighbours have. Three neighbours of three different
+    colours give the one none of them has, other ties the lowest of the tied colours.
+    */
+    fn birth_colour(&self, counts: &[u8; 5]) -> u8 {
+        let colours = self.colours() as usize;
+        let most = counts[1..=colours].iter().cloned().max().unwrap_or(0);
+        let tied: Vec<usize> = (1..=colours).filter(|&c| counts[c] == most).collect();
+        if tied.len() == 1 {
+            return tied[0] as u8;
+        }
+        let present = (1..=colours).filter(|&c| counts[c] > 0).count();
+        if present == 3 && colours == 4 && most == 1 {
+            if let Some(missing) = (1..=colours).find(|&c| counts[c] == 0) {
+                return missing as u8;
+            }
+        }
+        tied[0] as u8
+    }
+}
+
+impl CellularAutomaton for ColourLife {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    fn states(&self) -> u8 {
+        self.colours() + 1
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.array[self.get_index(row, column)]
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        let idx = self.get_index(row, column);
+        let state = state.min(self.colours());
+        // Only the two colours involved change, a recount would make filling a board quadratic
+        if self.array[idx] != 0 {
+            self.populations[self.array[idx] as usize - 1] -= 1;
+            self.live_cells -= 1;
+        }
+        if state != 0 {
+            self.populations[state as usize - 1] += 1;
+            self.live_cells += 1;
+        }
+        self.array[idx] = state;
+    }
+
+    fn step(&mut self) {
+        let mut next = self.array.clone();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.get_index(row, col);
+                let counts = self.neighbor_colours(row, col);
+                let live_neighbors = 8 - counts[0];
+                let cell = self.array[idx];
+                next[idx] = match (cell, self.rule.next_state(cell != 0, live_neighbors)) {
+                    (_, false) => 0,
+                    (0, true) => self.birth_colour(&counts),
+                    (colour, true) => colour,
+                };
+            }
+        }
+        self.array = next;
+        self.generations += 1;
+        self.count_populations();
+    }
+
+    fn population(&self) -> u32 {
+        self.live_cells
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let rule = self.rule;
+        *self = ColourLife::new(self.variant, width, height);
+        self.rule = rule;
+    }
+
+    fn clear(&mut self) {
+        self.generations = 0;
+        self.array = vec![0; self.array.len()];
+        self.count_populations();
+    }
+
+    // Live cells as dense as Universe::randomize makes them, every colour equally likely
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        let colours = self.colours();
+        self.generations = 0;
+        for cell in self.array.iter_mut() {
+            *cell = if rng.gen_bool(0.3) {
+                rng.gen_range(1..=colours)
+            } else {
+                0
+            };
+        }
+        self.count_populations();
+    }
+
+    fn serialize(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
@@ -0,0 +1,321 @@
+use rand::Rng;
+
+use crate::automaton::CellularAutomaton;
+use crate::Universe;
+
+/*
+One-dimensional elementary cellular automaton following a Wolfram rule number (0-255).
+Every generation is one row, the rows stack up from the top of a Universe as a
+space-time diagram. Once the board is full it scrolls, the newest row stays at the bottom.
+*/
+#[derive(Debug, Clone)]
+pub struct ElementaryAutomaton {
+    rule: u8,
+    row: Vec<bool>,
+    // Rows of the diagram filled so far
+    rows_used: u32,
+    universe: Universe,
+}
+
+impl ElementaryAutomaton {
+    /*
+    Starts from a single live cell in the middle of the top row, the usual picture for Rule 30
+    */
+    pub fn new(rule: u8, width: u32, height: u32) -> Self {
+        let mut row = vec![false; width as usize];
+        if let Some(middle) = row.get_mut(width as usize / 2) {
+            *middle = true;
+        }
+        let mut automaton = Self {
+            rule,
+            row: Vec::new(),
+            rows_used: 0,
+            universe: Universe::new(width, height),
+        };
+        automaton.set_row(row);
+        automaton
+    }
+
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    // Changes the rule, the diagram so far is kept and continues under the new rule
+    pub fn set_rule(&mut self, rule: u8) {
+        self.rule = rule;
+    }
+
+    // The current generation
+    pub fn row(&self) -> &[bool] {
+        &self.row
+    }
+
+    /*
+    Starts the diagram over with the given row as generation 0,
+    it is cut or padded with dead cells to the width of the board
+    */
+    pub fn set_row(&mut self, mut row: Vec<bool>) {
+        row.resize(self.universe.width as usize, false);
+        self.universe.clear();
+        self.rows_used = 0;
+        self.row = row;
+        self.push_row();
+        self.universe.generations = 0;
+    }
+
+    // The space-time diagram as it is shown by the frontends
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
+
+    /*
+    Each cell looks at its left neighbour, itself and its right neighbour, the row wraps around.
+    Read as a three bit number they pick the bit of the rule number that is the next state.
+    */
+    fn next_row(&self) -> Vec<bool> {
+        let width = self.row.len();
+        (0..width)
+            .map(|i| {
+                let left = self.row[(i + width - 1) % width] as u8;
+                let center = self.row[i] as u8;
+                let right = self.row[(i + 1) % width] as u8;
+                let neighborhood = left << 2 | center << 1 | right;
+                (self.rule >> neighborhood) & 1 == 1
+            })
+            .collect()
+    }
+
+    /*
+    Writes the current row below the diagram, scrolling it up if the board is full.
+    Ages and activity carry on down each column from the row above, so the newest row
+    tells how long each column has been alive.
+    */
+    fn push_row(&mut self) {
+        let width = self.universe.width as usize;
+        if width == 0 || self.universe.height == 0 {
+            return;
+        }
+        let (mut ages, mut activity) = match self.rows_used {
+            0 => (vec![0; width], vec![0; width]),
+            used => {
+                let start = (used as usize - 1) * width;
+                (
+                    self.universe.ages[start..start + width].to_vec(),
+                    self.universe.activity[start..start + width].to_vec(),
+                )
+            }
+        };
+        for (column, &alive) in self.row.iter().enumerate() {
+            ages[column] = if alive {
+                ages[column].saturating_add(1)
+            } else {
+                0
+            };
+            activity[column] = activity[column].saturating_add(alive as u32);
+        }
+        if self.rows_used < self.universe.height {
+            let start = self.rows_used as usize * width;
+            self.universe.array[start..start + width].copy_from_slice(&self.row);
+            self.universe.ages[start..start + width].copy_from_slice(&ages);
+            self.universe.activity[start..start + width].copy_from_slice(&activity);
+            self.rows_used += 1;
+        } else {
+            self.universe.array.drain(..width);
+            self.universe.array.extend_from_slice(&self.row);
+            self.universe.ages.drain(..width);
+            self.universe.ages.extend_from_slice(&ages);
+            self.universe.activity.drain(..width);
+            self.universe.activity.extend_from_slice(&activity);
+        }
+        self.universe.generations += 1;
+        self.universe.live_cells_count();
+    }
+}
+
+impl CellularAutomaton for ElementaryAutomaton {
+    fn width(&self) -> u32 {
+        self.universe.width
+    }
+
+    fn height(&self) -> u32 {
+        self.universe.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.universe.generations
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.universe.is_alive(row, column) as u8
+    }
+
+    /*
+    Editing a cell makes its row the current generation, the rows below it are dropped
+    and the diagram continues from the edited row
+    */
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        self.universe.set_alive(row, column, state != 0);
+        let width = self.universe.width as usize;
+        let start = row as usize * width;
+        self.row = self.universe.array[start..start + width].to_vec();
+        // Rows past rows_used are empty already, only the ones in use are cut off
+        for later in row + 1..self.rows_used {
+            for column in 0..self.universe.width {
+                self.universe.set_alive(later, column, false);
+            }
+        }
+        self.rows_used = row + 1;
+    }
+
+    fn step(&mut self) {
+        self.row = self.next_row();
+        self.push_row();
+    }
+
+    fn population(&self) -> u32 {
+        self.universe.population()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        *self = ElementaryAutomaton::new(self.rule, width, height);
+    }
+
+    fn clear(&mut self) {
+        self.set_row(Vec::new());
+    }
+
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        let row = (0..self.universe.width)
+            .map(|_| rng.gen_bool(0.5))
+            .collect();
+        self.set_row(row);
+    }
+
+    // The rule is a string like for every other engine, W30 as Golly writes Rule 30
+    fn serialize(&self) -> String {
+        let mut value = serde_json::to_value(&self.universe).unwrap();
+        value["rule"] = format!("W{}", self.rule).into();
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows of the diagram drawn with # for live cells
+    fn rows(automaton: &ElementaryAutomaton) -> Vec<String> {
+        (0..automaton.height())
+            .map(|row| {
+                (0..automaton.width())
+                    .map(|column| match automaton.get_cell(row, column) {
+                        0 => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn diagram(rule: u8) -> Vec<String> {
+        let mut automaton = ElementaryAutomaton::new(rule, 11, 5);
+        for _ in 0..4 {
+            automaton.step();
+        }
+        rows(&automaton)
+    }
+
+    #[test]
+    fn rules_30_90_and_110_draw_their_known_diagrams() {
+        assert_eq!(
+            diagram(30),
+            [
+                ".....#.....",
+                "....###....",
+                "...##..#...",
+                "..##.####..",
+                ".##..#...#.",
+            ]
+        );
+        assert_eq!(
+            diagram(90),
+            [
+                ".....#.....",
+                "....#.#....",
+                "...#...#...",
+                "..#.#.#.#..",
+                ".#.......#.",
+            ]
+        );
+        assert_eq!(
+            diagram(110),
+            [
+                ".....#.....",
+                "....##.....",
+                "...###.....",
+                "..##.#.....",
+                ".#####.....",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_rule_number_picks_the_bit_of_the_neighbourhood() {
+        for rule in 0..=255 {
+            for neighborhood in 0..8u8 {
+                let mut automaton = ElementaryAutomaton::new(rule, 3, 2);
+                automaton.set_row(
+                    (0..3)
+                        .map(|bit| neighborhood >> (2 - bit) & 1 == 1)
+                        .collect(),
+                );
+                automaton.step();
+                assert_eq!(automaton.get_cell(1, 1), rule >> neighborhood & 1);
+            }
+        }
+    }
+
+    #[test]
+    fn the_row_wraps_around_and_the_diagram_scrolls() {
+        let mut automaton = ElementaryAutomaton::new(90, 5, 3);
+        automaton.set_row(vec![true, false, false, false, false]);
+        automaton.step();
+        assert_eq!(automaton.row(), [false, true, false, false, true]);
+        for _ in 0..3 {
+            automaton.step();
+        }
+        assert_eq!(automaton.generations(), 4);
+        // Generations 2 to 4, the newest at the bottom
+        assert_eq!(rows(&automaton), ["..##.", ".####", ".#..#"]);
+    }
+
+    #[test]
+    fn ages_and_activity_carry_on_from_step_to_step() {
+        // Rule 204 keeps every cell as it is, the middle column stays alive
+        let mut automaton = ElementaryAutomaton::new(204, 5, 3);
+        for _ in 0..5 {
+            automaton.step();
+        }
+        let universe = automaton.universe();
+        assert_eq!(universe.ages()[2 * 5 + 2], 6);
+        assert_eq!(universe.activity()[2 * 5 + 2], 6);
+        assert_eq!(universe.ages()[2 * 5 + 1], 0);
+        // Rule 51 inverts every cell, a column is alive every other generation
+        let mut automaton = ElementaryAutomaton::new(51, 5, 8);
+        for _ in 0..4 {
+            automaton.step();
+        }
+        let universe = automaton.universe();
+        assert_eq!(universe.activity()[4 * 5 + 2], 3);
+        assert_eq!(universe.activity()[4 * 5 + 1], 2);
+        assert_eq!(universe.ages()[4 * 5 + 2], 1);
+    }
+
+    #[test]
+    fn the_rule_is_serialized_as_a_string() {
+        let automaton = ElementaryAutomaton::new(30, 5, 5);
+        let value: serde_json::Value = serde_json::from_str(&automaton.serialize()).unwrap();
+        assert_eq!(value["rule"], "W30");
+    }
+}
@@ -1,18 +1,56 @@
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+pub mod analysis;
+pub mod automaton;
+pub mod census;
+pub mod colours;
+pub mod elementary;
+pub mod life3d;
+pub mod limits;
+pub mod margolus;
+mod plane;
+pub mod predecessor;
+pub mod rule;
+pub mod ruletable;
+pub mod sat;
+pub mod search;
+pub mod soup;
+pub mod stochastic;
+pub mod symmetry;
+pub mod topology;
+pub mod turmite;
+
+use analysis::Analysis;
+pub use automaton::CellularAutomaton;
+use census::Census;
+use predecessor::Predecessor;
+pub use rule::Rule;
+pub use topology::Topology;
+use search::Pattern;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Universe {
     width: u32,
     height: u32,
     generations: u32,
     live_cells: u32,
     array: Vec<bool>,
+    // Consecutive generations each cell has been alive, 0 for dead cells
+    #[serde(default)]
+    ages: Vec<u32>,
+    // Total generations each cell has been alive since the board was set up
+    #[serde(default)]
+    activity: Vec<u32>,
+    #[serde(default)]
+    rule: Rule,
+    #[serde(default)]
+    topology: Topology,
 }
 
 #[derive(Debug)]
 pub struct Game {
-    universe: Universe,
+    automaton: Box<dyn CellularAutomaton>,
 }
 
 impl Game {
@@ -20,59 +58,135 @@ impl Game {
     Creates new Game Object, call can be Game::new(None, None) to create 12x12 size
     */
     pub fn new(width: Option<u32>, height: Option<u32>) -> Self {
-        Self {
-            universe: Universe::new(width.unwrap_or(12), height.unwrap_or(12)),
-        }
+        Self::with_automaton(Box::new(Universe::new(
+            width.unwrap_or(12),
+            height.unwrap_or(12),
+        )))
+    }
+
+    /*
+    Creates a Game running any engine, e.g. Game::with_automaton(Box::new(Universe::new(50, 50)))
+    */
+    pub fn with_automaton(automaton: Box<dyn CellularAutomaton>) -> Self {
+        Self { automaton }
     }
+
+    pub fn automaton(&self) -> &dyn CellularAutomaton {
+        self.automaton.as_ref()
+    }
+
+    pub fn automaton_mut(&mut self) -> &mut dyn CellularAutomaton {
+        self.automaton.as_mut()
+    }
+
     pub fn randomize(&mut self) {
-        self.universe.randomize();
+        self.automaton.randomize();
     }
 
     pub fn change_size(&mut self, width: u32, height: u32) -> String {
-        self.universe.generations = 0;
-        self.universe.live_cells = 0;
-        self.universe = Universe::new(width, height);
-        self.universe.randomize();
-        self.universe.live_cells_count();
+        self.automaton.resize(width, height);
+        self.automaton.randomize();
         self.serialize()
     }
 
-    pub fn change_value(&mut self, index: usize) {
-        // the index could be out of bounds and kill the server if a mallicious user gets into play
-        if *self.universe.array.get_mut(index).unwrap() {
-            self.universe.array[index] = false;
-        } else {
-            self.universe.array[index] = true;
+    /*
+    Moves the cell to its next state, for two states that toggles it between dead and alive.
+    Cells are numbered row by row, an index beyond the board is an error.
+    */
+    pub fn change_value(&mut self, index: usize) -> Result<(), String> {
+        let width = self.automaton.width() as usize;
+        let cells = width * self.automaton.height() as usize;
+        if index >= cells {
+            return Err(format!("cell {} is outside the board of {} cells", index, cells));
         }
-        self.universe.live_cells_count();
+        let (row, column) = ((index / width) as u32, (index % width) as u32);
+        let state = (self.automaton.get_cell(row, column) + 1) % self.automaton.states();
+        self.automaton.set_cell(row, column, state);
+        Ok(())
+    }
+
+    // Shows another z-layer of a three-dimensional engine, other engines ignore it
+    pub fn select_layer(&mut self, layer: u32) -> String {
+        self.automaton.select_layer(layer);
+        self.serialize()
     }
 
     pub fn negate_everything(&mut self) -> String {
-        self.universe.generations = 0;
-        self.universe.live_cells = 0;
-        self.universe.array = vec![false; self.universe.array.len()];
+        self.automaton.clear();
         self.serialize()
     }
 
     pub fn serialize(&mut self) -> String {
-        // It is safe to do so, according to serde documentation
-        let x: String = serde_json::to_string_pretty(&self.universe).unwrap();
-        x
+        self.automaton.serialize()
     }
     pub fn tick(&mut self) {
-        self.universe.tick();
+        self.automaton.step();
         //println!("Hi from tick");
     }
+
+    // Two state copy of the board, every cell in a state other than 0 counts as alive
+    pub fn universe(&self) -> Universe {
+        Universe::from_automaton(self.automaton())
+    }
+
+    // The board if the game runs a Life-like rule, an error for every other engine
+    pub fn life(&self) -> Result<&Universe, String> {
+        self.automaton
+            .as_universe()
+            .ok_or_else(|| String::from("the game does not run a Life-like rule"))
+    }
+
+    // Objects on the board under its rule and topology, see Universe::census
+    pub fn census(&self) -> Result<Census, String> {
+        self.life()?.census()
+    }
+
+    /*
+    Period, speed, heat and volatility of the pattern on the board under its rule, see Universe::analyze.
+    Only games running a Life-like rule can be analysed.
+    */
+    pub fn analyze(&self, max_generations: u32) -> Result<Analysis, String> {
+        self.life()?.analyze(max_generations)
+    }
+
+    /*
+    Replaces the board with a found pattern in its middle, keeping the size of the board
+    */
+    pub fn load_pattern(&mut self, pattern: &Pattern) -> String {
+        let (width, height) = (self.automaton.width(), self.automaton.height());
+        self.automaton = Box::new(pattern.to_universe(width, height));
+        self.serialize()
+    }
+
+    /*
+    Whether the board has a parent generation under its rule and topology, see Universe::predecessor.
+    Only games running a Life-like rule can be searched.
+    */
+    pub fn predecessor(&self, max_nodes: Option<u64>) -> Result<Predecessor, String> {
+        Ok(self.life()?.predecessor(max_nodes))
+    }
+
+    /*
+    Replaces the board with a parent if there is one, so the game can be run backwards.
+    The parent keeps the rule and topology of the board.
+    The board stays as it is for Gardens of Eden or when the search gives up.
+    */
+    pub fn step_back(&mut self, max_nodes: Option<u64>) -> Result<Predecessor, String> {
+        let predecessor = self.predecessor(max_nodes)?;
+        if let Predecessor::Found(parent) = &predecessor {
+            self.automaton = Box::new(parent.clone());
+        }
+        Ok(predecessor)
+    }
 }
 
 impl Universe {
     fn get_index(&self, row: u32, column: u32) -> usize {
-        (row * self.width + column) as usize
+        row as usize * self.width as usize + column as usize
     }
 
     pub fn randomize(&mut self) {
         self.generations = 0;
-        self.live_cells_count();
         let mut new = Vec::new();
         let mut rand = rand::thread_rng();
         while new.len() != self.array.len() {
@@ -84,9 +198,177 @@ impl Universe {
             }
         }
         self.array = new;
+        self.live_cells_count();
+        self.reset_history();
+    }
+
+    /*
+    Clears the board and fills the width x height region starting at (top, left) at random,
+    each cell is alive with probability density. The region is cut off at the edges of the board.
+    With a seeded generator the same seed always gives the same soup.
+    A density outside 0 to 1 is an error and leaves the board alone.
+    */
+    pub fn randomize_region<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        top: u32,
+        left: u32,
+        width: u32,
+        height: u32,
+        density: f64,
+    ) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&density) {
+            return Err(format!(
+                "the density has to be between 0 and 1, not {}",
+                density
+            ));
+        }
+        self.generations = 0;
+        self.array = vec![false; self.array.len()];
+        for row in top..top.saturating_add(height).min(self.height) {
+            for col in left..left.saturating_add(width).min(self.width) {
+                let idx = self.get_index(row, col);
+                self.array[idx] = rng.gen_bool(density);
+            }
+        }
+        self.live_cells_count();
+        self.reset_history();
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    pub fn is_alive(&self, row: u32, column: u32) -> bool {
+        self.array[self.get_index(row, column)]
+    }
+
+    pub fn set_alive(&mut self, row: u32, column: u32, alive: bool) {
+        let idx = self.get_index(row, column);
+        // Counted as the cell changes, a recount would make filling a board quadratic
+        match (self.array[idx], alive) {
+            (false, true) => self.live_cells += 1,
+            (true, false) => self.live_cells -= 1,
+            _ => {}
+        }
+        self.array[idx] = alive;
+        self.touch_history(idx);
+    }
+
+    pub fn clear(&mut self) {
+        self.generations = 0;
+        self.live_cells = 0;
+        self.array = vec![false; self.array.len()];
+        self.reset_history();
+    }
+
+    /*
+    Two state copy of any engine's board, every state other than 0 counts as alive.
+    A Universe keeps its rule and topology, boards of other engines get Conway's rule on a torus.
+    */
+    pub fn from_automaton(automaton: &dyn CellularAutomaton) -> Self {
+        if let Some(universe) = automaton.as_universe() {
+            return universe.clone();
+        }
+        let mut universe = Universe::new(automaton.width(), automaton.height());
+        for row in 0..universe.height {
+            for col in 0..universe.width {
+                let idx = universe.get_index(row, col);
+                universe.array[idx] = automaton.get_cell(row, col) != 0;
+            }
+        }
+        universe.generations = automaton.generations();
+        universe.live_cells_count();
+        universe.reset_history();
+        universe
+    }
+
+    // Live cells on the board right now
+    pub fn population(&self) -> u32 {
+        self.array.iter().filter(|&&alive| alive).count() as u32
+    }
+
+    /*
+    Starts the age and activity tracking over from the current board,
+    every live cell counts as freshly born
+    */
+    fn reset_history(&mut self) {
+        self.ages = self.array.iter().map(|&alive| alive as u32).collect();
+        self.activity = self.ages.clone();
+    }
+
+    // Keeps the history of a single cell in line after it was edited by hand
+    fn touch_history(&mut self, idx: usize) {
+        self.ages.resize(self.array.len(), 0);
+        self.activity.resize(self.array.len(), 0);
+        if self.array[idx] {
+            self.ages[idx] = 1;
+            self.activity[idx] = self.activity[idx].saturating_add(1);
+        } else {
+            self.ages[idx] = 0;
+        }
+    }
+
+    /*
+    Records the board after a generation: live cells grow one generation older
+    and add to the heatmap, dead cells lose their age
+    */
+    fn record_history(&mut self) {
+        self.ages.resize(self.array.len(), 0);
+        self.activity.resize(self.array.len(), 0);
+        for idx in 0..self.array.len() {
+            if self.array[idx] {
+                self.ages[idx] = self.ages[idx].saturating_add(1);
+                self.activity[idx] = self.activity[idx].saturating_add(1);
+            } else {
+                self.ages[idx] = 0;
+            }
+        }
+    }
+
+    // Generations each cell has been alive without interruption, row by row
+    pub fn ages(&self) -> &[u32] {
+        &self.ages
+    }
+
+    // Heatmap of how many generations each cell has been alive in total, row by row
+    pub fn activity(&self) -> &[u32] {
+        &self.activity
     }
 
-    fn live_neighbor_count(&self, row: u32, column: u32) -> u8 {
+    // Live cells among the eight around (row, column), wrapping around the edges of a torus
+    pub fn live_neighbor_count(&self, row: u32, column: u32) -> u8 {
+        if self.topology == Topology::Bounded {
+            return self
+                .neighbor_indices(row, column)
+                .map(|idx| self.array[idx] as u8)
+                .sum();
+        }
         let mut count = 0;
         for delta_row in [self.height - 1, 0, 1].iter().cloned() {
             for delta_col in [self.width - 1, 0, 1].iter().cloned() {
@@ -103,56 +385,142 @@ impl Universe {
         count
     }
 
+    // Cells around (row, column) under the topology, on a torus narrower than three cells some come twice
+    pub(crate) fn neighbor_indices(&self, row: u32, column: u32) -> impl Iterator<Item = usize> + '_ {
+        let (height, width) = (self.height as i64, self.width as i64);
+        let bounded = self.topology == Topology::Bounded;
+        let deltas = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        deltas.into_iter().filter_map(move |(delta_row, delta_col): (i64, i64)| {
+            let neighbor_row = row as i64 + delta_row;
+            let neighbor_col = column as i64 + delta_col;
+            let outside = neighbor_row < 0 || neighbor_row >= height || neighbor_col < 0 || neighbor_col >= width;
+            if bounded && outside {
+                return None;
+            }
+            Some((neighbor_row.rem_euclid(height) * width + neighbor_col.rem_euclid(width)) as usize)
+        })
+    }
+
     pub fn live_cells_count(&mut self){
         self.live_cells = 0;
         for idx in 0..self.array.len() {
-            if self.array[idx] == true {
+            if self.array[idx] {
                 self.live_cells += 1;
             }
         }
     }
 
     pub fn new(width: u32, height: u32) -> Self {
-        let x = width * height;
-        let size = x as usize;
+        // In usize, width * height overflows u32 for boards beyond 65536x65536
+        let size = width as usize * height as usize;
         Self {
             width,
             height,
             generations: 0,
             live_cells: 0,
             array: vec![false; size],
+            ages: vec![0; size],
+            activity: vec![0; size],
+            rule: Rule::conway(),
+            topology: Topology::Torus,
         }
     }
 
     pub fn tick(&mut self) {
         let mut next = self.array.clone();
         self.generations += 1;
-        self.live_cells_count();
         for row in 0..self.height {
             for col in 0..self.width {
                 let idx = self.get_index(row, col);
                 let cell = self.array[idx];
                 let live_neighbors = self.live_neighbor_count(row, col);
 
-                let next_cell = match (cell, live_neighbors) {
-                    // Rule 1: Any live cell with fewer than two live neighbours
-                    // dies, as if caused by underpopulation.
-                    (true, x) if x < 2 => false,
-                    // Rule 2: Any live cell with two or three live neighbours
-                    // lives on to the next generation.
-                    (true, 2) | (true, 3) => true,
-                    // Rule 3: Any live cell with more than three live
-                    // neighbours dies, as if by overpopulation.
-                    (true, x) if x > 3 => false,
-                    // Rule 4: Any dead cell with exactly three live neighbours
-                    // becomes a live cell, as if by reproduction.
-                    (false, 3) => true,
-                    // All other cells remain in the same state.
-                    (otherwise, _) => otherwise,
-                };
-                next[idx] = next_cell;
+                next[idx] = self.rule.next_state(cell, live_neighbors);
             }
         }
         self.array = next;
+        self.live_cells_count();
+        self.record_history();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinker() -> Universe {
+        let mut universe = Universe::new(5, 5);
+        for column in 1..4 {
+            universe.set_alive(2, column, true);
+        }
+        universe
+    }
+
+    #[test]
+    fn ages_grow_while_a_cell_lives_and_activity_adds_up() {
+        let mut universe = blinker();
+        let (center, end, top) = (
+            universe.get_index(2, 2),
+            universe.get_index(2, 1),
+            universe.get_index(1, 2),
+        );
+        assert_eq!(
+            (universe.ages()[center], universe.activity()[center]),
+            (1, 1)
+        );
+        for _ in 0..4 {
+            universe.tick();
+        }
+        // The center never dies, the ends and tips of the blinker take turns
+        assert_eq!(universe.ages()[center], 5);
+        assert_eq!(universe.activity()[center], 5);
+        assert_eq!((universe.ages()[end], universe.activity()[end]), (1, 3));
+        assert_eq!((universe.ages()[top], universe.activity()[top]), (0, 2));
+        assert_eq!(universe.activity()[universe.get_index(0, 0)], 0);
+    }
+
+    #[test]
+    fn editing_a_cell_starts_its_age_over_and_keeps_its_activity() {
+        let mut universe = blinker();
+        universe.tick();
+        universe.tick();
+        let center = universe.get_index(2, 2);
+        universe.set_alive(2, 2, false);
+        assert_eq!(
+            (universe.ages()[center], universe.activity()[center]),
+            (0, 3)
+        );
+        universe.set_alive(2, 2, true);
+        assert_eq!(
+            (universe.ages()[center], universe.activity()[center]),
+            (1, 4)
+        );
+    }
+
+    #[test]
+    fn a_new_board_starts_the_history_over() {
+        let mut universe = blinker();
+        universe.tick();
+        universe.clear();
+        assert!(universe.ages().iter().all(|&age| age == 0));
+        assert!(universe.activity().iter().all(|&heat| heat == 0));
+        universe.randomize();
+        for idx in 0..universe.array.len() {
+            let alive = universe.array[idx] as u32;
+            assert_eq!(
+                (universe.ages()[idx], universe.activity()[idx]),
+                (alive, alive)
+            );
+        }
+    }
+
+    #[test]
+    fn ages_and_activity_are_serialized() {
+        let mut universe = blinker();
+        universe.tick();
+        let value: serde_json::Value =
+            serde_json::from_str(&CellularAutomaton::serialize(&universe)).unwrap();
+        assert_eq!(value["ages"][2 * 5 + 2], 2);
+        assert_eq!(value["activity"][5 + 2], 1);
     }
 }
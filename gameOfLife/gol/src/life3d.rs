@@ -0,0 +1,421 @@
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::automaton::CellularAutomaton;
+
+/*
+Rule of a 3D Life, as bit masks over the 0 to 26 live neighbours of a cell:
+a live cell survives if its count is set in survival, a dead cell is born if it is set in birth
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule3D {
+    pub survival: u32,
+    pub birth: u32,
+}
+
+impl Rule3D {
+    /*
+    Bays' notation: live cells with survive_min to survive_max neighbours survive,
+    dead cells with birth_min to birth_max neighbours are born. Life 4555 is bays(4, 5, 5, 5).
+    */
+    pub fn bays(survive_min: u8, survive_max: u8, birth_min: u8, birth_max: u8) -> Self {
+        Self {
+            survival: range_mask(survive_min, survive_max),
+            birth: range_mask(birth_min, birth_max),
+        }
+    }
+
+    pub fn survives(&self, live_neighbors: u8) -> bool {
+        self.survival >> live_neighbors & 1 == 1
+    }
+
+    pub fn is_born(&self, live_neighbors: u8) -> bool {
+        self.birth >> live_neighbors & 1 == 1
+    }
+}
+
+impl Default for Rule3D {
+    fn default() -> Self {
+        Rule3D::bays(4, 5, 5, 5)
+    }
+}
+
+/*
+Reads "4555" or "4/5/5/5" in Bays' notation, or "B5/S4,5" with comma separated counts
+(single digit counts may also be written together as in "B5/S45")
+*/
+impl FromStr for Rule3D {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let upper = rule.to_ascii_uppercase();
+        if upper.starts_with('B') || upper.starts_with('S') {
+            let mut parsed = Rule3D {
+                survival: 0,
+                birth: 0,
+            };
+            for part in upper.split('/') {
+                // The first character may take more than one byte, e.g. in "B3/é"
+                let (kind, counts) = part.split_at(part.chars().next().map_or(0, char::len_utf8));
+                let mask = counts_mask(counts).ok_or(format!("invalid counts in rule {}", rule))?;
+                match kind {
+                    "B" => parsed.birth = mask,
+                    "S" => parsed.survival = mask,
+                    _ => return Err(format!("invalid rule {}", rule)),
+                }
+            }
+            return Ok(parsed);
+        }
+        let numbers: Vec<u8> = if rule.contains(['/', ',']) {
+            rule.split(['/', ','])
+                .map(|n| n.trim().parse().map_err(|_| format!("invalid rule {}", rule)))
+                .collect::<Result<_, _>>()?
+        } else {
+            rule.chars()
+                .map(|c| c.to_digit(10).map(|d| d as u8).ok_or(format!("invalid rule {}", rule)))
+                .collect::<Result<_, _>>()?
+        };
+        match numbers[..] {
+            [survive_min, survive_max, birth_min, birth_max]
+                if numbers.iter().all(|&n| n <= 26) =>
+            {
+                Ok(Rule3D::bays(survive_min, survive_max, birth_min, birth_max))
+            }
+            _ => Err(format!("rule {} needs four counts from 0 to 26", rule)),
+        }
+    }
+}
+
+// The distinct steps -1, 0 and +1 along a side of the torus, added modulo its length
+fn offsets(size: u32) -> Vec<u32> {
+    let mut offsets = vec![0];
+    for offset in [size.saturating_sub(1), 1 % size.max(1)] {
+        if !offsets.contains(&offset) {
+            offsets.push(offset);
+        }
+    }
+    offsets
+}
+
+fn range_mask(min: u8, max: u8) -> u32 {
+    (min..=max.min(26)).fold(0, |mask, n| mask | 1 << n)
+}
+
+fn counts_mask(counts: &str) -> Option<u32> {
+    let numbers: Vec<&str> = if counts.contains(',') {
+        counts.split(',').collect()
+    } else {
+        counts.split("").filter(|c| !c.is_empty()).collect()
+    };
+    numbers.iter().try_fold(0, |mask, n| match n.trim().parse::<u8>() {
+        Ok(n) if n <= 26 => Some(mask | 1 << n),
+        _ => None,
+    })
+}
+
+/*
+Three-dimensional Life on a width x height x depth torus, every cell has 26 neighbours.
+Through CellularAutomaton it shows one z-layer at a time, the one picked with select_layer.
+Neither server runs 3D games yet, so no route or frontend control picks a layer so far;
+serialize_layer already gives a layer in the shape the frontends draw.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Universe3D {
+    width: u32,
+    height: u32,
+    depth: u32,
+    generations: u32,
+    live_cells: u32,
+    rule: Rule3D,
+    layer: u32,
+    array: Vec<bool>,
+}
+
+// One z-layer in the same shape as a serialized Universe, so the frontends can draw it
+#[derive(Serialize)]
+struct Layer<'a> {
+    width: u32,
+    height: u32,
+    depth: u32,
+    layer: u32,
+    generations: u32,
+    live_cells: u32,
+    array: &'a [bool],
+}
+
+// All live cells of the volume as (layer, row, column)
+#[derive(Serialize)]
+struct Voxels {
+    width: u32,
+    height: u32,
+    depth: u32,
+    generations: u32,
+    live_cells: u32,
+    rule: Rule3D,
+    voxels: Vec<(u32, u32, u32)>,
+}
+
+impl Universe3D {
+    // A universe needs at least one layer to show, a depth of 0 is an error
+    pub fn new(width: u32, height: u32, depth: u32, rule: Rule3D) -> Result<Self, String> {
+        if depth == 0 {
+            return Err("a 3D universe needs a depth of at least 1".to_owned());
+        }
+        let size = width as usize * height as usize * depth as usize;
+        Ok(Self {
+            width,
+            height,
+            depth,
+            generations: 0,
+            live_cells: 0,
+            rule,
+            layer: 0,
+            array: vec![false; size],
+        })
+    }
+
+    fn get_index(&self, layer: u32, row: u32, column: u32) -> usize {
+        ((layer as usize * self.height as usize) + row as usize) * self.width as usize
+            + column as usize
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn rule(&self) -> Rule3D {
+        self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: Rule3D) {
+        self.rule = rule;
+    }
+
+    pub fn is_alive(&self, layer: u32, row: u32, column: u32) -> bool {
+        self.array[self.get_index(layer, row, column)]
+    }
+
+    pub fn set_alive(&mut self, layer: u32, row: u32, column: u32, alive: bool) {
+        let idx = self.get_index(layer, row, column);
+        match (self.array[idx], alive) {
+            (false, true) => self.live_cells += 1,
+            (true, false) => self.live_cells -= 1,
+            _ => {}
+        }
+        self.array[idx] = alive;
+    }
+
+    pub fn live_cells_count(&mut self) {
+        self.live_cells = self.array.iter().filter(|&&alive| alive).count() as u32;
+    }
+
+    /*
+    Counts each neighbour once: along a side shorter than 3 the offsets -1, 0 and +1
+    wrap onto the same cells, the cell itself included
+    */
+    fn live_neighbor_count(
+        &self,
+        layer: u32,
+        row: u32,
+        column: u32,
+        offsets: &[Vec<u32>; 3],
+    ) -> u8 {
+        let mut count = 0;
+        for &delta_layer in &offsets[0] {
+            for &delta_row in &offsets[1] {
+                for &delta_col in &offsets[2] {
+                    if delta_layer == 0 && delta_row == 0 && delta_col == 0 {
+                        continue;
+                    }
+                    let idx = self.get_index(
+                        (layer + delta_layer) % self.depth,
+                        (row + delta_row) % self.height,
+                        (column + delta_col) % self.width,
+                    );
+                    count += self.array[idx] as u8;
+                }
+            }
+        }
+        count
+    }
+
+    pub fn tick(&mut self) {
+        let mut next = self.array.clone();
+        self.generations += 1;
+        let offsets = [
+            offsets(self.depth),
+            offsets(self.height),
+            offsets(self.width),
+        ];
+        for layer in 0..self.depth {
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    let idx = self.get_index(layer, row, col);
+                    let live_neighbors = self.live_neighbor_count(layer, row, col, &offsets);
+                    next[idx] = if self.array[idx] {
+                        self.rule.survives(live_neighbors)
+                    } else {
+                        self.rule.is_born(live_neighbors)
+                    };
+                }
+            }
+        }
+        self.array = next;
+        self.live_cells_count();
+    }
+
+    // The given z-layer in the format of a serialized Universe, plus depth and layer
+    pub fn serialize_layer(&self, layer: u32) -> String {
+        let layer = layer.min(self.depth.saturating_sub(1));
+        let size = self.width as usize * self.height as usize;
+        let start = layer as usize * size;
+        let array = &self.array[start..(start + size).min(self.array.len())];
+        let view = Layer {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            layer,
+            generations: self.generations,
+            live_cells: array.iter().filter(|&&alive| alive).count() as u32,
+            array,
+        };
+        serde_json::to_string_pretty(&view).unwrap()
+    }
+
+    // The whole volume, listing every live cell as [layer, row, column]
+    pub fn serialize_voxels(&self) -> String {
+        let mut voxels = Vec::new();
+        for layer in 0..self.depth {
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    if self.is_alive(layer, row, col) {
+                        voxels.push((layer, row, col));
+                    }
+                }
+            }
+        }
+        let view = Voxels {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            generations: self.generations,
+            live_cells: self.live_cells,
+            rule: self.rule,
+            voxels,
+        };
+        serde_json::to_string_pretty(&view).unwrap()
+    }
+}
+
+impl CellularAutomaton for Universe3D {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    fn layers(&self) -> u32 {
+        self.depth
+    }
+
+    fn select_layer(&mut self, layer: u32) {
+        self.layer = layer.min(self.depth.saturating_sub(1));
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.is_alive(self.layer, row, column) as u8
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        self.set_alive(self.layer, row, column, state != 0);
+    }
+
+    fn step(&mut self) {
+        self.tick();
+    }
+
+    fn population(&self) -> u32 {
+        self.live_cells
+    }
+
+    // Keeps the depth, only width and height are part of the common interface
+    fn resize(&mut self, width: u32, height: u32) {
+        // The depth was checked when the universe was made, so this cannot fail
+        *self = Universe3D::new(width, height, self.depth, self.rule).unwrap();
+    }
+
+    fn clear(&mut self) {
+        self.generations = 0;
+        self.array = vec![false; self.array.len()];
+        self.live_cells_count();
+    }
+
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        self.generations = 0;
+        for cell in self.array.iter_mut() {
+            *cell = rng.gen_bool(0.3);
+        }
+        self.live_cells_count();
+    }
+
+    fn serialize(&self) -> String {
+        self.serialize_layer(self.layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_notation() {
+        let bays = Rule3D::bays(4, 5, 5, 5);
+        for rule in ["4555", "4/5/5/5", "4,5,5,5", "B5/S4,5", "b5/s45", " S45/B5 "] {
+            assert_eq!(rule.parse::<Rule3D>(), Ok(bays), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in ["", "455", "4/5/5/27", "B3/é", "é3/S4", "B3/S2é", "B5/S4,27", "X3/S4"] {
+            assert!(rule.parse::<Rule3D>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn a_depth_of_0_is_an_error() {
+        assert!(Universe3D::new(4, 4, 0, Rule3D::default()).is_err());
+        assert!(Universe3D::new(4, 4, 1, Rule3D::default()).is_ok());
+    }
+
+    #[test]
+    fn small_sides_count_each_neighbour_once() {
+        let steps = [offsets(1), offsets(1), offsets(1)];
+        let mut universe = Universe3D::new(1, 1, 1, Rule3D::default()).unwrap();
+        universe.set_alive(0, 0, 0, true);
+        assert_eq!(universe.live_neighbor_count(0, 0, 0, &steps), 0);
+        // Every other cell of a full 2x2x2 torus is a neighbour, each counted once
+        let steps = [offsets(2), offsets(2), offsets(2)];
+        let mut universe = Universe3D::new(2, 2, 2, Rule3D::default()).unwrap();
+        for layer in 0..2 {
+            for row in 0..2 {
+                for col in 0..2 {
+                    universe.set_alive(layer, row, col, true);
+                }
+            }
+        }
+        assert_eq!(universe.live_neighbor_count(1, 0, 1, &steps), 7);
+        // On 3 or more cells every side keeps all three steps
+        assert_eq!(offsets(3).len(), 3);
+    }
+}
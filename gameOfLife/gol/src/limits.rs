@@ -0,0 +1,88 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/*
+Bounds the servers put on a single request, so nobody can make them allocate huge boards,
+run thousands of generations at once or flood them with requests
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Limits {
+    pub max_width: u32,
+    pub max_height: u32,
+    // Cells of one board, keeps boards of the maximum width and height together in check
+    pub max_cells: u64,
+    // Generations a single request may run
+    pub max_steps: u32,
+    // Requests a client may make per second on average, 0 turns rate limiting off, see gol_ratelimit
+    pub requests_per_second: f64,
+    // Requests a client may make at once after being quiet for a while
+    pub burst: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_width: 1000,
+            max_height: 1000,
+            max_cells: 250_000,
+            max_steps: 10_000,
+            requests_per_second: 20.0,
+            burst: 40,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    // A request that makes no sense, like a board without cells, the servers answer with 400
+    Invalid(String),
+    // A request for more than the limits allow, the servers answer with 413
+    TooLarge(String),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::Invalid(message) | LimitError::TooLarge(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_size(&self, width: u32, height: u32) -> Result<(), LimitError> {
+        if width == 0 || height == 0 {
+            return Err(LimitError::Invalid(String::from(
+                "width and height must be at least 1",
+            )));
+        }
+        if width > self.max_width || height > self.max_height {
+            return Err(LimitError::TooLarge(format!(
+                "a board of {}x{} is larger than the maximum of {}x{}",
+                width, height, self.max_width, self.max_height
+            )));
+        }
+        // Both factors fit in u32, so their product cannot overflow u64
+        let cells = width as u64 * height as u64;
+        if cells > self.max_cells {
+            return Err(LimitError::TooLarge(format!(
+                "a board of {}x{} has {} cells, more than the maximum of {}",
+                width, height, cells, self.max_cells
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn check_steps(&self, steps: u32) -> Result<(), LimitError> {
+        if steps == 0 || steps > self.max_steps {
+            return Err(LimitError::Invalid(format!(
+                "steps must be between 1 and {}",
+                self.max_steps
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::automaton::CellularAutomaton;
+use crate::Universe;
+
+/*
+Rule of a block cellular automaton: the board is cut into 2x2 blocks which are replaced as a whole.
+A block is numbered by its live cells, top left 1, top right 2, bottom left 4 and bottom right 8,
+table[block] is the block it turns into. This is the numbering Golly uses for its MS,D rules.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRule {
+    table: [u8; 16],
+}
+
+const TOP_LEFT: u8 = 1;
+const TOP_RIGHT: u8 = 2;
+const BOTTOM_LEFT: u8 = 4;
+const BOTTOM_RIGHT: u8 = 8;
+
+impl BlockRule {
+    pub fn new(table: [u8; 16]) -> Result<Self, String> {
+        if table.iter().any(|&block| block > 15) {
+            return Err("blocks are numbered from 0 to 15".to_owned());
+        }
+        Ok(Self { table })
+    }
+
+    pub fn table(&self) -> [u8; 16] {
+        self.table
+    }
+
+    pub fn apply(&self, block: u8) -> u8 {
+        self.table[block as usize]
+    }
+
+    /*
+    Reversible rules map every block to a different one,
+    running them backwards only needs the inverse table
+    */
+    pub fn is_reversible(&self) -> bool {
+        let mut seen = [false; 16];
+        for &block in &self.table {
+            seen[block as usize] = true;
+        }
+        seen.iter().all(|&seen| seen)
+    }
+
+    pub fn inverse(&self) -> Option<BlockRule> {
+        if !self.is_reversible() {
+            return None;
+        }
+        let mut table = [0; 16];
+        for (block, &next) in self.table.iter().enumerate() {
+            table[next as usize] = block as u8;
+        }
+        Some(Self { table })
+    }
+
+    /*
+    Critters: blocks with exactly two live cells stay, all others are inverted,
+    blocks that had three live cells are also turned by 180 degrees
+    */
+    pub fn critters() -> Self {
+        Self::from_fn(|block| match block.count_ones() {
+            2 => block,
+            3 => rotate_half(!block & 15),
+            _ => !block & 15,
+        })
+    }
+
+    // Tron: completely empty or full blocks are inverted, all others stay
+    pub fn tron() -> Self {
+        Self::from_fn(|block| match block {
+            0 | 15 => !block & 15,
+            _ => block,
+        })
+    }
+
+    /*
+    Billiard ball model: a single ball moves on diagonally, two balls meeting head on
+    leave on the other diagonal, everything else stays
+    */
+    pub fn billiard_ball() -> Self {
+        Self::from_fn(|block| match block {
+            TOP_LEFT | TOP_RIGHT | BOTTOM_LEFT | BOTTOM_RIGHT => rotate_half(block),
+            0b1001 => TOP_RIGHT | BOTTOM_LEFT,
+            0b0110 => TOP_LEFT | BOTTOM_RIGHT,
+            _ => block,
+        })
+    }
+
+    fn from_fn(rule: impl Fn(u8) -> u8) -> Self {
+        let mut table = [0; 16];
+        for (block, next) in table.iter_mut().enumerate() {
+            *next = rule(block as u8);
+        }
+        Self { table }
+    }
+}
+
+// Both sides rounded up to even numbers, so the blocks tile the wrapping board
+fn even_size(width: u32, height: u32) -> (u32, u32) {
+    (width + width % 2, height + height % 2)
+}
+
+fn rotate_half(block: u8) -> u8 {
+    let mut rotated = 0;
+    for (from, to) in [
+        (TOP_LEFT, BOTTOM_RIGHT),
+        (TOP_RIGHT, BOTTOM_LEFT),
+        (BOTTOM_LEFT, TOP_RIGHT),
+        (BOTTOM_RIGHT, TOP_LEFT),
+    ] {
+        if block & from != 0 {
+            rotated |= to;
+        }
+    }
+    rotated
+}
+
+/*
+Reads the 16 entries of a table separated by commas or semicolons,
+optionally prefixed with Golly's MS,D as in MS,D0;8;4;3;2;5;9;7;1;6;10;11;12;13;14;15
+*/
+impl FromStr for BlockRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let entries = rule
+            .strip_prefix("MS,D")
+            .unwrap_or(rule)
+            .split([',', ';'])
+            .map(|entry| {
+                entry
+                    .trim()
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid block {}", entry))
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        let table: [u8; 16] = entries
+            .try_into()
+            .map_err(|_| "a block rule has 16 entries".to_owned())?;
+        BlockRule::new(table)
+    }
+}
+
+/*
+Runs a block rule on a Universe with Margolus partitions: even generations use blocks starting
+at even rows and columns, odd generations the blocks shifted by one cell in both directions.
+The board wraps around, so width and height are rounded up to even numbers.
+*/
+#[derive(Debug, Clone)]
+pub struct MargolusAutomaton {
+    rule: BlockRule,
+    universe: Universe,
+}
+
+impl MargolusAutomaton {
+    pub fn new(rule: BlockRule, width: u32, height: u32) -> Self {
+        let (width, height) = even_size(width, height);
+        Self {
+            rule,
+            universe: Universe::new(width, height),
+        }
+    }
+
+    pub fn rule(&self) -> BlockRule {
+        self.rule
+    }
+
+    pub fn set_rule(&mut self, rule: BlockRule) {
+        self.rule = rule;
+    }
+
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
+
+    /*
+    Undoes the last step of a reversible rule, returns false for rules that cannot be reversed
+    */
+    pub fn step_back(&mut self) -> bool {
+        match self.rule.inverse() {
+            Some(inverse) if self.universe.generations > 0 => {
+                let offset = (self.universe.generations - 1) % 2;
+                self.apply(inverse, offset);
+                self.universe.generations -= 1;
+                self.universe.live_cells_count();
+                self.universe.reset_history();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(&mut self, rule: BlockRule, offset: u32) {
+        let width = self.universe.width;
+        let height = self.universe.height;
+        let mut next = self.universe.array.clone();
+        for top in (offset..height + offset).step_by(2) {
+            for left in (offset..width + offset).step_by(2) {
+                let cells = [
+                    (top % height, left % width, TOP_LEFT),
+                    (top % height, (left + 1) % width, TOP_RIGHT),
+                    ((top + 1) % height, left % width, BOTTOM_LEFT),
+                    ((top + 1) % height, (left + 1) % width, BOTTOM_RIGHT),
+                ];
+                let block = cells
+                    .iter()
+                    .filter(|&&(row, col, _)| self.universe.is_alive(row, col))
+                    .fold(0, |block, &(_, _, bit)| block | bit);
+                let new_block = rule.apply(block);
+                for (row, col, bit) in cells {
+                    next[self.universe.get_index(row, col)] = new_block & bit != 0;
+                }
+            }
+        }
+        self.universe.array = next;
+    }
+}
+
+impl CellularAutomaton for MargolusAutomaton {
+    fn width(&self) -> u32 {
+        self.universe.width
+    }
+
+    fn height(&self) -> u32 {
+        self.universe.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.universe.generations
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.universe.is_alive(row, column) as u8
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        self.universe.set_alive(row, column, state != 0);
+    }
+
+    fn step(&mut self) {
+        let offset = self.universe.generations % 2;
+        self.apply(self.rule, offset);
+        self.universe.generations += 1;
+        self.universe.live_cells_count();
+        self.universe.record_history();
+    }
+
+    fn population(&self) -> u32 {
+        self.universe.population()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        *self = MargolusAutomaton::new(self.rule, width, height);
+    }
+
+    fn fitted_size(&self, width: u32, height: u32) -> (u32, u32) {
+        even_size(width, height)
+    }
+
+    fn clear(&mut self) {
+        self.universe.clear();
+    }
+
+    fn randomize(&mut self) {
+        self.universe.randomize();
+    }
+
+    fn serialize(&self) -> String {
+        let mut value = serde_json::to_value(&self.universe).unwrap();
+        value["rule"] = serde_json::to_value(self.rule.table).unwrap();
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn reversible_rules_run_back_to_where_they_started() {
+        for rule in [
+            BlockRule::critters(),
+            BlockRule::tron(),
+            BlockRule::billiard_ball(),
+        ] {
+            let mut automaton = MargolusAutomaton::new(rule, 16, 12);
+            let mut rng = StdRng::seed_from_u64(4);
+            automaton
+                .universe
+                .randomize_region(&mut rng, 0, 0, 16, 12, 0.4)
+                .unwrap();
+            let start = automaton.universe().array.clone();
+            for _ in 0..25 {
+                automaton.step();
+            }
+            assert_ne!(automaton.universe().array, start);
+            for _ in 0..25 {
+                assert!(automaton.step_back());
+            }
+            assert_eq!(automaton.universe().array, start);
+            assert_eq!(automaton.generations(), 0);
+            assert!(!automaton.step_back());
+        }
+        let mut stays = [0; 16];
+        stays[15] = 15;
+        let rule = BlockRule::new(stays).unwrap();
+        assert!(!rule.is_reversible());
+        let mut automaton = MargolusAutomaton::new(rule, 4, 4);
+        automaton.step();
+        assert!(!automaton.step_back());
+    }
+
+    #[test]
+    fn the_partition_alternates() {
+        // A lone ball moves on diagonally, in a fixed partition it would bounce back and forth
+        let mut automaton = MargolusAutomaton::new(BlockRule::billiard_ball(), 8, 8);
+        automaton.set_cell(0, 0, 1);
+        automaton.step();
+        assert_eq!(automaton.get_cell(1, 1), 1);
+        automaton.step();
+        assert_eq!(automaton.get_cell(2, 2), 1);
+        automaton.step();
+        assert_eq!(automaton.get_cell(3, 3), 1);
+        assert_eq!(automaton.population(), 1);
+    }
+
+    #[test]
+    fn odd_sizes_are_rounded_up_as_fitted_size_tells() {
+        let mut automaton = MargolusAutomaton::new(BlockRule::critters(), 4, 4);
+        assert_eq!(automaton.fitted_size(5, 7), (6, 8));
+        automaton.resize(5, 7);
+        assert_eq!((automaton.width(), automaton.height()), (6, 8));
+        assert_eq!(automaton.fitted_size(6, 8), (6, 8));
+    }
+}
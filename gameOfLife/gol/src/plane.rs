@@ -0,0 +1,158 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::Rule;
+
+/*
+A pattern on the unbounded plane, stored as the set of its live cells as (row, column).
+Used to run single objects in isolation, away from the wrapping edges of a Universe.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Plane {
+    cells: BTreeSet<(i64, i64)>,
+}
+
+// How a periodic pattern repeats: after `period` generations it is back, moved by (rows, columns)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Periodicity {
+    pub period: u32,
+    pub rows: i64,
+    pub columns: i64,
+}
+
+impl Plane {
+    pub fn from_cells<I: IntoIterator<Item = (i64, i64)>>(cells: I) -> Self {
+        Self {
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    pub fn cells(&self) -> &BTreeSet<(i64, i64)> {
+        &self.cells
+    }
+
+    pub fn population(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, row: i64, column: i64) -> bool {
+        self.cells.contains(&(row, column))
+    }
+
+    // (min row, min column, max row, max column) of the live cells
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let first = self.cells.iter().next()?;
+        let mut bounds = (first.0, first.1, first.0, first.1);
+        for &(row, column) in &self.cells {
+            bounds.0 = bounds.0.min(row);
+            bounds.1 = bounds.1.min(column);
+            bounds.2 = bounds.2.max(row);
+            bounds.3 = bounds.3.max(column);
+        }
+        Some(bounds)
+    }
+
+    /*
+    Moves the pattern so its bounding box starts at (0, 0),
+    also returns the (row, column) it was moved away from
+    */
+    pub fn normalized(&self) -> (Plane, (i64, i64)) {
+        match self.bounding_box() {
+            Some((top, left, _, _)) => (self.shifted(-top, -left), (top, left)),
+            None => (self.clone(), (0, 0)),
+        }
+    }
+
+    pub fn shifted(&self, rows: i64, columns: i64) -> Plane {
+        Plane::from_cells(self.cells.iter().map(|&(r, c)| (r + rows, c + columns)))
+    }
+
+    pub fn union(&self, other: &Plane) -> Plane {
+        Plane::from_cells(self.cells.union(&other.cells).copied())
+    }
+
+    // A generation under any life-like rule, rules with B0 would fill the whole plane
+    pub fn step(&self, rule: Rule) -> Plane {
+        let mut counts: HashMap<(i64, i64), u8> = HashMap::new();
+        for &(row, column) in &self.cells {
+            // Live cells without neighbours survive under rules with S0
+            counts.entry((row, column)).or_insert(0);
+            for delta_row in -1..=1 {
+                for delta_col in -1..=1 {
+                    if delta_row == 0 && delta_col == 0 {
+                        continue;
+                    }
+                    *counts.entry((row + delta_row, column + delta_col)).or_insert(0) += 1;
+                }
+            }
+        }
+        let mut next = BTreeSet::new();
+        for (cell, live_neighbors) in counts {
+            if rule.next_state(self.cells.contains(&cell), live_neighbors) {
+                next.insert(cell);
+            }
+        }
+        Plane { cells: next }
+    }
+
+    /*
+    Runs the pattern until its first generation shows up again, possibly moved.
+    Gives up after max_generations or once the pattern died out.
+    */
+    pub fn periodicity(&self, max_generations: u32, rule: Rule) -> Option<Periodicity> {
+        if self.is_empty() {
+            return None;
+        }
+        let (start, (top, left)) = self.normalized();
+        let mut current = self.clone();
+        for generation in 1..=max_generations {
+            current = current.step(rule);
+            if current.population() == start.population() {
+                let (shape, (row, column)) = current.normalized();
+                if shape == start {
+                    return Some(Periodicity {
+                        period: generation,
+                        rows: row - top,
+                        columns: column - left,
+                    });
+                }
+            }
+            if current.is_empty() {
+                return None;
+            }
+        }
+        None
+    }
+
+    /*
+    Splits the pattern into groups of cells, two cells share a group when they are
+    at most `distance` cells apart in both directions
+    */
+    pub fn clusters(&self, distance: i64) -> Vec<Plane> {
+        let mut seen: BTreeSet<(i64, i64)> = BTreeSet::new();
+        let mut clusters = Vec::new();
+        for &start in &self.cells {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut stack = vec![start];
+            let mut cluster = BTreeSet::new();
+            while let Some((row, column)) = stack.pop() {
+                cluster.insert((row, column));
+                for delta_row in -distance..=distance {
+                    for delta_col in -distance..=distance {
+                        let neighbor = (row + delta_row, column + delta_col);
+                        if self.cells.contains(&neighbor) && seen.insert(neighbor) {
+                            stack.push(neighbor);
+                        }
+                    }
+                }
+            }
+            clusters.push(Plane { cells: cluster });
+        }
+        clusters
+    }
+}
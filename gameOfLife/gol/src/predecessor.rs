@@ -0,0 +1,258 @@
+use crate::Universe;
+
+/*
+Cell choices a search tries when the caller sets no limit, under a second in a release build.
+The search is exponential in the number of cells: boards of up to about 8x8 are usually
+decided within it, larger ones often end up Unknown.
+*/
+pub const DEFAULT_MAX_NODES: u64 = 10_000_000;
+
+#[derive(Debug, Clone)]
+pub enum Predecessor {
+    // A board that turns into the given one in a single tick
+    Found(Universe),
+    // No board turns into the given one, it can only be set up by hand
+    GardenOfEden,
+    // The search visited max_nodes cells without an answer
+    Unknown,
+}
+
+/*
+Backtracking search for a parent of a board of the same size and topology under its rule. The parent is
+filled in row by row, cell by cell. After every choice each cell of the board whose
+neighbourhood it touches is checked: if no way of filling in the rest of its neighbourhood
+gives the cell its state on the board, the choice is taken back.
+*/
+struct Search<'a> {
+    target: &'a Universe,
+    // Cells around each cell under the topology, counted twice on very narrow tori
+    neighbors: Vec<Vec<usize>>,
+    // For a cell that is dead and a cell that is alive, bit n is set if n live neighbours
+    // give the cell its state on the board
+    counts: Vec<[u16; 2]>,
+    parent: Vec<Option<bool>>,
+    live: Vec<u8>,
+    unknown: Vec<u8>,
+}
+
+impl<'a> Search<'a> {
+    fn new(target: &'a Universe) -> Self {
+        let size = target.array.len();
+        let mut neighbors = Vec::with_capacity(size);
+        for row in 0..target.height {
+            for column in 0..target.width {
+                neighbors.push(target.neighbor_indices(row, column).collect::<Vec<_>>());
+            }
+        }
+        let counts = target
+            .array
+            .iter()
+            .map(|&alive| {
+                let mask = |cell: bool| {
+                    (0..=8u8)
+                        .filter(|&n| target.rule.next_state(cell, n) == alive)
+                        .fold(0, |mask, n| mask | 1 << n)
+                };
+                [mask(false), mask(true)]
+            })
+            .collect();
+        Self {
+            target,
+            counts,
+            parent: vec![None; size],
+            live: vec![0; size],
+            unknown: neighbors.iter().map(|cells| cells.len() as u8).collect(),
+            neighbors,
+        }
+    }
+
+    fn possible(&self, idx: usize) -> bool {
+        let (low, high) = (self.live[idx], self.live[idx] + self.unknown[idx]);
+        // Live neighbour counts low..=high can still happen
+        let range = (1u16 << (high + 1)) - (1u16 << low);
+        let states: &[usize] = match self.parent[idx] {
+            Some(false) => &[0],
+            Some(true) => &[1],
+            None => &[0, 1],
+        };
+        states
+            .iter()
+            .any(|&state| self.counts[idx][state] & range != 0)
+    }
+
+    // Sets a cell of the parent and tells whether the board around it can still come out
+    fn assign(&mut self, idx: usize, alive: bool) -> bool {
+        self.parent[idx] = Some(alive);
+        for k in 0..self.neighbors[idx].len() {
+            let cell = self.neighbors[idx][k];
+            self.unknown[cell] -= 1;
+            self.live[cell] += alive as u8;
+        }
+        self.possible(idx) && self.neighbors[idx].iter().all(|&cell| self.possible(cell))
+    }
+
+    fn unassign(&mut self, idx: usize) {
+        let alive = self.parent[idx].take().unwrap();
+        for k in 0..self.neighbors[idx].len() {
+            let cell = self.neighbors[idx][k];
+            self.unknown[cell] += 1;
+            self.live[cell] -= alive as u8;
+        }
+    }
+
+    /*
+    Depth first over the cells in row order, trying dead before alive so sparse parents come
+    first. choices holds the value tried last for every assigned cell.
+    */
+    fn run(&mut self, max_nodes: u64) -> Predecessor {
+        let size = self.parent.len();
+        let mut choices: Vec<bool> = Vec::with_capacity(size);
+        let mut nodes = 0;
+        let mut next = Some(false);
+        loop {
+            match next {
+                Some(alive) => {
+                    if nodes >= max_nodes {
+                        return Predecessor::Unknown;
+                    }
+                    nodes += 1;
+                    let idx = choices.len();
+                    choices.push(alive);
+                    if !self.assign(idx, alive) {
+                        next = None;
+                    } else if choices.len() == size {
+                        return Predecessor::Found(self.parent_universe());
+                    } else {
+                        next = Some(false);
+                    }
+                }
+                // Take back the last choice and try the other value, or go further back
+                None => match choices.pop() {
+                    Some(alive) => {
+                        self.unassign(choices.len());
+                        if !alive {
+                            next = Some(true);
+                        }
+                    }
+                    None => return Predecessor::GardenOfEden,
+                },
+            }
+        }
+    }
+
+    fn parent_universe(&self) -> Universe {
+        let mut parent = Universe::new(self.target.width, self.target.height);
+        parent.rule = self.target.rule;
+        parent.topology = self.target.topology;
+        parent.generations = self.target.generations.saturating_sub(1);
+        parent.array = self.parent.iter().map(|&cell| cell == Some(true)).collect();
+        parent.live_cells_count();
+        parent
+    }
+}
+
+impl Universe {
+    /*
+    Looks for a board that turns into this one in one tick, of the same size and topology under the same rule.
+    max_nodes limits how many cell choices the search may try, None stands for DEFAULT_MAX_NODES.
+    Some(u64::MAX) searches until it knows, which can take longer than anyone waits.
+    */
+    pub fn predecessor(&self, max_nodes: Option<u64>) -> Predecessor {
+        if self.array.is_empty() {
+            return Predecessor::Found(self.clone());
+        }
+        Search::new(self).run(max_nodes.unwrap_or(DEFAULT_MAX_NODES))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colours::{ColourLife, Variant};
+    use crate::{Game, Rule, Topology};
+
+    fn board(width: u32, height: u32, cells: &[(u32, u32)]) -> Universe {
+        let mut universe = Universe::new(width, height);
+        for &(row, column) in cells {
+            universe.set_alive(row, column, true);
+        }
+        universe
+    }
+
+    fn same_cells(first: &Universe, second: &Universe) -> bool {
+        (0..first.height()).all(|row| {
+            (0..first.width())
+                .all(|column| first.is_alive(row, column) == second.is_alive(row, column))
+        })
+    }
+
+    // Checks the parent found for a board really turns into it
+    fn parent_of(target: &Universe) -> Universe {
+        match target.predecessor(None) {
+            Predecessor::Found(parent) => {
+                let mut next = parent.clone();
+                next.tick();
+                assert!(same_cells(&next, target));
+                parent
+            }
+            other => panic!("expected a parent, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_blinker_has_a_parent() {
+        let horizontal = board(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        parent_of(&horizontal);
+        let vertical = board(5, 5, &[(1, 2), (2, 2), (3, 2)]);
+        parent_of(&vertical);
+    }
+
+    #[test]
+    fn the_parent_keeps_rule_and_topology() {
+        let mut target = board(6, 6, &[(2, 1), (2, 2), (2, 3)]);
+        target.set_rule(Rule::new(&[3, 6], &[2, 3]));
+        target.set_topology(Topology::Bounded);
+        let parent = parent_of(&target);
+        assert_eq!(parent.rule(), target.rule());
+        assert_eq!(parent.topology(), Topology::Bounded);
+    }
+
+    #[test]
+    fn a_full_bounded_board_is_a_garden_of_eden() {
+        let cells: Vec<(u32, u32)> = (0..3)
+            .flat_map(|row| (0..3).map(move |column| (row, column)))
+            .collect();
+        let mut target = board(3, 3, &cells);
+        target.set_topology(Topology::Bounded);
+        assert!(matches!(
+            target.predecessor(None),
+            Predecessor::GardenOfEden
+        ));
+    }
+
+    #[test]
+    fn step_back_runs_the_game_backwards() {
+        let mut game = Game::with_automaton(Box::new(board(5, 5, &[(2, 1), (2, 2), (2, 3)])));
+        assert!(matches!(game.step_back(None), Ok(Predecessor::Found(_))));
+        game.tick();
+        let life = game.life().unwrap();
+        assert!(same_cells(life, &board(5, 5, &[(2, 1), (2, 2), (2, 3)])));
+    }
+
+    #[test]
+    fn the_search_gives_up_after_max_nodes() {
+        let blinker = board(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        assert!(matches!(blinker.predecessor(Some(1)), Predecessor::Unknown));
+        assert!(matches!(
+            blinker.predecessor(Some(u64::MAX)),
+            Predecessor::Found(_)
+        ));
+    }
+
+    #[test]
+    fn other_engines_have_no_predecessor() {
+        let colours = ColourLife::new(Variant::QuadLife, 5, 5);
+        let mut game = Game::with_automaton(Box::new(colours));
+        assert!(game.step_back(None).is_err());
+    }
+}
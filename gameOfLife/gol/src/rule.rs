@@ -0,0 +1,103 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/*
+Life-like rule in B/S notation: a dead cell with a neighbour count listed after B is born,
+a live cell with a count listed after S survives. Conway's Life is B3/S23.
+Serialized as its B/S string.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rule {
+    // Bit n is set when n neighbours give a birth or let a cell survive
+    birth: u16,
+    survival: u16,
+}
+
+impl Rule {
+    pub fn new(birth: &[u8], survival: &[u8]) -> Self {
+        let mask = |counts: &[u8]| {
+            counts
+                .iter()
+                .filter(|&&n| n <= 8)
+                .fold(0, |mask, &n| mask | 1 << n)
+        };
+        Self {
+            birth: mask(birth),
+            survival: mask(survival),
+        }
+    }
+
+    pub fn conway() -> Self {
+        Rule::new(&[3], &[2, 3])
+    }
+
+    pub fn next_state(&self, cell: bool, live_neighbors: u8) -> bool {
+        let mask = if cell { self.survival } else { self.birth };
+        mask >> live_neighbors & 1 == 1
+    }
+
+    pub fn birth(&self) -> Vec<u8> {
+        (0..=8).filter(|&n| self.birth >> n & 1 == 1).collect()
+    }
+
+    pub fn survival(&self) -> Vec<u8> {
+        (0..=8).filter(|&n| self.survival >> n & 1 == 1).collect()
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::conway()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = |counts: Vec<u8>| counts.iter().map(|n| n.to_string()).collect::<String>();
+        write!(f, "B{}/S{}", digits(self.birth()), digits(self.survival()))
+    }
+}
+
+/*
+Reads B3/S23, b3s23 and the older survival/birth form 23/3
+*/
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rule {}, expected something like B3/S23", rule);
+        let digits = |part: &str| -> Result<Vec<u8>, String> {
+            part.chars()
+                .map(|c| match c.to_digit(10) {
+                    Some(n) if n <= 8 => Ok(n as u8),
+                    _ => Err(invalid()),
+                })
+                .collect()
+        };
+        let lower = rule.trim().to_ascii_lowercase();
+        if let Some(rest) = lower.strip_prefix('b') {
+            let (birth, survival) = rest.split_once('s').ok_or_else(invalid)?;
+            let birth = birth.trim_end_matches('/');
+            return Ok(Rule::new(&digits(birth)?, &digits(survival)?));
+        }
+        let (survival, birth) = lower.split_once('/').ok_or_else(invalid)?;
+        Ok(Rule::new(&digits(birth)?, &digits(survival)?))
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        rule.parse()
+    }
+}
+
+impl From<Rule> for String {
+    fn from(rule: Rule) -> Self {
+        rule.to_string()
+    }
+}
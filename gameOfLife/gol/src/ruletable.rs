@@ -0,0 +1,714 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::automaton::CellularAutomaton;
+
+// Neighbourhoods the cache of a RuleTableAutomaton holds before it starts over
+const MAX_CACHED: usize = 1 << 16;
+
+// Wireworld with 0 empty, 1 electron head, 2 electron tail and 3 conductor
+pub const WIREWORLD: &str = "@RULE WireWorld
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var o={0,2,3}
+var p={0,2,3}
+var q={0,2,3}
+var r={0,2,3}
+var s={0,2,3}
+var t={0,2,3}
+var u={0,2,3}
+# electron head becomes a tail, a tail becomes conductor again
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+# conductor becomes a head next to exactly one or two heads
+3,1,o,p,q,r,s,t,u,1
+3,1,1,p,q,r,s,t,u,1
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Neighborhood {
+    // N, NE, E, SE, S, SW, W, NW
+    Moore,
+    // N, E, S, W
+    VonNeumann,
+}
+
+// Which rearrangements of the neighbours a transition also applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Symmetries {
+    None,
+    Rotate4,
+    Rotate4Reflect,
+    Rotate8,
+    Rotate8Reflect,
+    Reflect,
+    Permute,
+}
+
+// States allowed at one position of a transition, variables bind every position using them to one state
+#[derive(Debug, Clone)]
+struct Term {
+    states: Vec<u8>,
+    variable: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    center: Term,
+    neighbors: Vec<Term>,
+    output: Term,
+}
+
+/*
+A multi-state rule given as a list of transitions, read from the @TABLE section of a Golly .rule file.
+Each line lists the cell, its neighbours in clockwise order starting north and the new state of the cell.
+The first transition matching a cell wins, cells no transition matches stay as they are.
+*/
+#[derive(Debug, Clone)]
+pub struct RuleTable {
+    name: String,
+    highest_state: u8,
+    neighborhood: Neighborhood,
+    symmetries: Symmetries,
+    transitions: Vec<Transition>,
+    // Neighbour orders to try for each transition, unused for Permute
+    orders: Vec<Vec<usize>>,
+}
+
+impl RuleTable {
+    pub fn wireworld() -> Self {
+        RuleTable::parse(WIREWORLD).unwrap()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut name = String::new();
+        let mut in_table = false;
+        let mut highest_state: Option<u8> = None;
+        let mut neighborhood = Neighborhood::Moore;
+        let mut symmetries = Symmetries::None;
+        let mut variables: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut transitions = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('@') {
+                let mut words = section.split_whitespace();
+                let section = words.next().unwrap_or("");
+                in_table = section == "TABLE";
+                if section == "RULE" {
+                    name = words.next().unwrap_or("").to_owned();
+                }
+                continue;
+            }
+            if !in_table {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            if let Some(value) = line.strip_prefix("n_states:") {
+                let value: u16 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| error("invalid n_states"))?;
+                // States are kept in a u8, so the last one has to leave room to count past it
+                if !(2..=255).contains(&value) {
+                    return Err(error("n_states has to be between 2 and 255"));
+                }
+                highest_state = Some((value - 1) as u8);
+            } else if let Some(value) = line.strip_prefix("neighborhood:") {
+                neighborhood = match value.trim() {
+                    "Moore" => Neighborhood::Moore,
+                    "vonNeumann" => Neighborhood::VonNeumann,
+                    other => return Err(error(&format!("unsupported neighborhood {}", other))),
+                };
+            } else if let Some(value) = line.strip_prefix("symmetries:") {
+                symmetries = match value.trim() {
+                    "none" => Symmetries::None,
+                    "rotate4" => Symmetries::Rotate4,
+                    "rotate4reflect" => Symmetries::Rotate4Reflect,
+                    "rotate8" => Symmetries::Rotate8,
+                    "rotate8reflect" => Symmetries::Rotate8Reflect,
+                    "reflect" | "reflect_horizontal" => Symmetries::Reflect,
+                    "permute" => Symmetries::Permute,
+                    other => return Err(error(&format!("unsupported symmetries {}", other))),
+                };
+            } else if let Some(declaration) = line.strip_prefix("var ") {
+                let (variable, values) = declaration
+                    .split_once('=')
+                    .ok_or_else(|| error("variable without values"))?;
+                let values = parse_set(values.trim(), &variables).map_err(|e| error(&e))?;
+                variables.insert(variable.trim().to_owned(), values);
+            } else {
+                let tokens = split_transition(line);
+                let transition =
+                    parse_transition(&tokens, &variables, neighborhood).map_err(|e| error(&e))?;
+                transitions.push(transition);
+            }
+        }
+
+        // n_states counts the dead state as well
+        let highest = highest_state.ok_or("rule table without n_states")?;
+        let too_high = transitions.iter().any(|t: &Transition| {
+            std::iter::once(&t.center)
+                .chain(&t.neighbors)
+                .chain(std::iter::once(&t.output))
+                .any(|term| term.states.iter().any(|&s| s > highest))
+        });
+        if too_high {
+            return Err("transition uses a state above n_states".to_owned());
+        }
+        let orders = neighbor_orders(neighborhood, symmetries);
+        Ok(Self {
+            name,
+            highest_state: highest,
+            neighborhood,
+            symmetries,
+            transitions,
+            orders,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Number of states including the dead state 0
+    pub fn states(&self) -> u16 {
+        self.highest_state as u16 + 1
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    pub fn symmetries(&self) -> Symmetries {
+        self.symmetries
+    }
+
+    /*
+    Next state of a cell, neighbours are given in the order of the neighbourhood:
+    clockwise starting north
+    */
+    pub fn next_state(&self, center: u8, neighbors: &[u8]) -> u8 {
+        for transition in &self.transitions {
+            let mut bound = HashMap::new();
+            if !transition.center.matches(center, &mut bound) {
+                continue;
+            }
+            let matched = if self.symmetries == Symmetries::Permute {
+                match_permuted(
+                    &transition.neighbors,
+                    neighbors,
+                    &mut vec![false; neighbors.len()],
+                    &mut bound,
+                )
+            } else {
+                self.orders.iter().any(|order| {
+                    let mut attempt = bound.clone();
+                    let matched = order
+                        .iter()
+                        .zip(&transition.neighbors)
+                        .all(|(&position, term)| term.matches(neighbors[position], &mut attempt));
+                    if matched {
+                        bound = attempt;
+                    }
+                    matched
+                })
+            };
+            if matched {
+                return match transition.output.variable {
+                    Some(variable) => bound[&variable],
+                    None => transition.output.states[0],
+                };
+            }
+        }
+        center
+    }
+}
+
+impl Term {
+    fn matches(&self, state: u8, bound: &mut HashMap<usize, u8>) -> bool {
+        if !self.states.contains(&state) {
+            return false;
+        }
+        match self.variable {
+            Some(variable) => *bound.entry(variable).or_insert(state) == state,
+            None => true,
+        }
+    }
+}
+
+// Matches the neighbours in any order, trying each free neighbour for each term in turn
+fn match_permuted(
+    terms: &[Term],
+    neighbors: &[u8],
+    used: &mut [bool],
+    bound: &mut HashMap<usize, u8>,
+) -> bool {
+    let Some((term, rest)) = terms.split_first() else {
+        return true;
+    };
+    for position in 0..neighbors.len() {
+        if used[position] {
+            continue;
+        }
+        let mut attempt = bound.clone();
+        if term.matches(neighbors[position], &mut attempt) {
+            used[position] = true;
+            if match_permuted(rest, neighbors, used, &mut attempt) {
+                *bound = attempt;
+                return true;
+            }
+            used[position] = false;
+        }
+    }
+    false
+}
+
+/*
+All rotations and reflections a symmetry allows, as the neighbour each position of a transition is compared with
+*/
+fn neighbor_orders(neighborhood: Neighborhood, symmetries: Symmetries) -> Vec<Vec<usize>> {
+    let count = match neighborhood {
+        Neighborhood::Moore => 8,
+        Neighborhood::VonNeumann => 4,
+    };
+    // A quarter turn moves a Moore neighbour two places clockwise, a von Neumann neighbour one
+    let quarter = count / 4;
+    let rotations: Vec<usize> = match symmetries {
+        Symmetries::None | Symmetries::Reflect | Symmetries::Permute => vec![0],
+        Symmetries::Rotate4 | Symmetries::Rotate4Reflect => (0..4).map(|i| i * quarter).collect(),
+        Symmetries::Rotate8 | Symmetries::Rotate8Reflect => (0..count).collect(),
+    };
+    let reflect = matches!(
+        symmetries,
+        Symmetries::Reflect | Symmetries::Rotate4Reflect | Symmetries::Rotate8Reflect
+    );
+    let mut orders = Vec::new();
+    for rotation in rotations {
+        orders.push((0..count).map(|i| (i + rotation) % count).collect());
+        if reflect {
+            orders.push((0..count).map(|i| (count - i + rotation) % count).collect());
+        }
+    }
+    orders
+}
+
+// Splits a transition at commas outside of braces, lines without commas have one state per character
+fn split_transition(line: &str) -> Vec<String> {
+    if !line.contains(',') {
+        return line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from)
+            .collect();
+    }
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                tokens.push(current.trim().to_owned());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    tokens.push(current.trim().to_owned());
+    tokens
+}
+
+// A state, a variable or a set of those written as {0,1,a}
+fn parse_set(value: &str, variables: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>, String> {
+    if let Some(inner) = value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        let mut states = Vec::new();
+        for item in inner.split(',') {
+            for state in parse_set(item.trim(), variables)? {
+                if !states.contains(&state) {
+                    states.push(state);
+                }
+            }
+        }
+        return Ok(states);
+    }
+    if let Some(states) = variables.get(value) {
+        return Ok(states.clone());
+    }
+    value
+        .parse::<u8>()
+        .map(|state| vec![state])
+        .map_err(|_| format!("unknown state or variable {}", value))
+}
+
+fn parse_transition(
+    tokens: &[String],
+    variables: &HashMap<String, Vec<u8>>,
+    neighborhood: Neighborhood,
+) -> Result<Transition, String> {
+    let count = match neighborhood {
+        Neighborhood::Moore => 8,
+        Neighborhood::VonNeumann => 4,
+    };
+    if tokens.len() != count + 2 {
+        return Err(format!(
+            "transition needs {} entries, found {}",
+            count + 2,
+            tokens.len()
+        ));
+    }
+    // Every variable name gets a number, so repeated names are bound to the same state
+    let mut names: Vec<&str> = Vec::new();
+    let mut terms = Vec::new();
+    for token in tokens {
+        let variable = if variables.contains_key(token.as_str()) {
+            let position = names
+                .iter()
+                .position(|name| name == token)
+                .unwrap_or_else(|| {
+                    names.push(token);
+                    names.len() - 1
+                });
+            Some(position)
+        } else {
+            None
+        };
+        terms.push(Term {
+            states: parse_set(token, variables)?,
+            variable,
+        });
+    }
+    let output = terms.pop().unwrap();
+    if output.variable.is_none() && output.states.len() != 1 {
+        return Err("the new state has to be a single state or a variable".to_owned());
+    }
+    if let Some(variable) = output.variable {
+        if !terms.iter().any(|term| term.variable == Some(variable)) {
+            return Err("the new state uses a variable not bound by the transition".to_owned());
+        }
+    }
+    let center = terms.remove(0);
+    Ok(Transition {
+        center,
+        neighbors: terms,
+        output,
+    })
+}
+
+/*
+Runs a rule table on a width x height torus laid out like a Universe,
+cells hold states from 0 to states - 1 instead of alive or dead
+*/
+#[derive(Debug, Clone)]
+pub struct RuleTableAutomaton {
+    table: RuleTable,
+    width: u32,
+    height: u32,
+    generations: u32,
+    live_cells: u32,
+    array: Vec<u8>,
+    // Next states of neighbourhoods seen so far, the table is only searched once for each
+    // until MAX_CACHED of them are known and the cache is cleared
+    cache: HashMap<[u8; 9], u8>,
+}
+
+// Same fields as a serialized Universe, with states in the array
+#[derive(Serialize)]
+struct RuleTableView<'a> {
+    width: u32,
+    height: u32,
+    generations: u32,
+    live_cells: u32,
+    array: &'a [u8],
+    rule: &'a str,
+    states: u16,
+}
+
+impl RuleTableAutomaton {
+    pub fn new(table: RuleTable, width: u32, height: u32) -> Self {
+        Self {
+            table,
+            width,
+            height,
+            generations: 0,
+            live_cells: 0,
+            array: vec![0; width as usize * height as usize],
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn table(&self) -> &RuleTable {
+        &self.table
+    }
+
+    fn get_index(&self, row: u32, column: u32) -> usize {
+        (row * self.width + column) as usize
+    }
+
+    fn live_cells_count(&mut self) {
+        self.live_cells = self.array.iter().filter(|&&state| state != 0).count() as u32;
+    }
+
+    // Neighbours clockwise from north, wrapping around the edges
+    fn neighbors(&self, row: u32, column: u32) -> [u8; 8] {
+        let north = (row + self.height - 1) % self.height;
+        let south = (row + 1) % self.height;
+        let west = (column + self.width - 1) % self.width;
+        let east = (column + 1) % self.width;
+        let positions = match self.table.neighborhood {
+            Neighborhood::Moore => [
+                (north, column),
+                (north, east),
+                (row, east),
+                (south, east),
+                (south, column),
+                (south, west),
+                (row, west),
+                (north, west),
+            ],
+            // Only the first four are used
+            Neighborhood::VonNeumann => [
+                (north, column),
+                (row, east),
+                (south, column),
+                (row, west),
+                (row, column),
+                (row, column),
+                (row, column),
+                (row, column),
+            ],
+        };
+        positions.map(|(r, c)| self.array[self.get_index(r, c)])
+    }
+}
+
+impl CellularAutomaton for RuleTableAutomaton {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    fn states(&self) -> u8 {
+        self.table.highest_state.saturating_add(1).max(2)
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.array[self.get_index(row, column)]
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        let idx = self.get_index(row, column);
+        let state = state.min(self.table.highest_state);
+        self.live_cells = self.live_cells + (state != 0) as u32 - (self.array[idx] != 0) as u32;
+        self.array[idx] = state;
+    }
+
+    fn step(&mut self) {
+        let count = match self.table.neighborhood {
+            Neighborhood::Moore => 8,
+            Neighborhood::VonNeumann => 4,
+        };
+        let mut next = self.array.clone();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.get_index(row, col);
+                let mut key = [0; 9];
+                key[0] = self.array[idx];
+                key[1..].copy_from_slice(&self.neighbors(row, col));
+                if self.cache.len() >= MAX_CACHED && !self.cache.contains_key(&key) {
+                    self.cache.clear();
+                }
+                let table = &self.table;
+                next[idx] = *self
+                    .cache
+                    .entry(key)
+                    .or_insert_with(|| table.next_state(key[0], &key[1..=count]));
+            }
+        }
+        self.array = next;
+        self.generations += 1;
+        self.live_cells_count();
+    }
+
+    fn population(&self) -> u32 {
+        self.live_cells
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        *self = RuleTableAutomaton::new(self.table.clone(), width, height);
+    }
+
+    fn clear(&mut self) {
+        self.generations = 0;
+        self.array = vec![0; self.array.len()];
+        self.live_cells_count();
+    }
+
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        self.generations = 0;
+        for cell in self.array.iter_mut() {
+            *cell = rng.gen_range(0..=self.table.highest_state);
+        }
+        self.live_cells_count();
+    }
+
+    fn serialize(&self) -> String {
+        let view = RuleTableView {
+            width: self.width,
+            height: self.height,
+            generations: self.generations,
+            live_cells: self.live_cells,
+            array: &self.array,
+            rule: &self.table.name,
+            states: self.table.states(),
+        };
+        serde_json::to_string_pretty(&view).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Game;
+
+    // States of a Wireworld board from a picture: . empty, H head, t tail, # conductor
+    fn wireworld(rows: &[&str]) -> RuleTableAutomaton {
+        let mut automaton = RuleTableAutomaton::new(
+            RuleTable::wireworld(),
+            rows[0].len() as u32,
+            rows.len() as u32,
+        );
+        for (row, line) in rows.iter().enumerate() {
+            for (column, cell) in line.chars().enumerate() {
+                let state = match cell {
+                    'H' => 1,
+                    't' => 2,
+                    '#' => 3,
+                    _ => 0,
+                };
+                automaton.set_cell(row as u32, column as u32, state);
+            }
+        }
+        automaton
+    }
+
+    fn row(automaton: &RuleTableAutomaton, row: u32) -> Vec<u8> {
+        (0..automaton.width())
+            .map(|column| automaton.get_cell(row, column))
+            .collect()
+    }
+
+    fn table(n_states: &str, transitions: &str) -> Result<RuleTable, String> {
+        RuleTable::parse(&format!(
+            "@RULE Test\n@TABLE\nn_states:{}\nneighborhood:Moore\nsymmetries:none\n{}",
+            n_states, transitions
+        ))
+    }
+
+    #[test]
+    fn parses_wireworld() {
+        let table = RuleTable::wireworld();
+        assert_eq!(table.name(), "WireWorld");
+        assert_eq!(table.states(), 4);
+        assert_eq!(table.neighborhood(), Neighborhood::Moore);
+        assert_eq!(table.symmetries(), Symmetries::Permute);
+    }
+
+    #[test]
+    fn an_electron_runs_along_a_wire() {
+        let mut automaton = wireworld(&[".......", ".tH####", "......."]);
+        automaton.step();
+        assert_eq!(row(&automaton, 1), vec![0, 3, 2, 1, 3, 3, 3]);
+        automaton.step();
+        assert_eq!(row(&automaton, 1), vec![0, 3, 3, 2, 1, 3, 3]);
+        assert_eq!(automaton.population(), 6);
+    }
+
+    #[test]
+    fn three_heads_do_not_light_a_conductor() {
+        let table = RuleTable::wireworld();
+        assert_eq!(table.next_state(3, &[1, 0, 0, 0, 0, 0, 0, 0]), 1);
+        assert_eq!(table.next_state(3, &[0, 0, 1, 0, 0, 0, 1, 0]), 1);
+        assert_eq!(table.next_state(3, &[1, 0, 1, 0, 1, 0, 0, 0]), 3);
+        assert_eq!(table.next_state(0, &[1, 1, 1, 0, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn symmetries_apply_a_transition_to_rotated_neighbours() {
+        let rotated = RuleTable::parse(
+            "@TABLE\nn_states:2\nneighborhood:vonNeumann\nsymmetries:rotate4\n0,1,0,0,0,1\n",
+        )
+        .unwrap();
+        for neighbors in [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]] {
+            assert_eq!(rotated.next_state(0, &neighbors), 1);
+        }
+        let fixed = table("2", "0,1,0,0,0,0,0,0,0,1\n").unwrap();
+        assert_eq!(fixed.next_state(0, &[1, 0, 0, 0, 0, 0, 0, 0]), 1);
+        assert_eq!(fixed.next_state(0, &[0, 0, 1, 0, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn variables_bind_every_position_to_one_state() {
+        let transitions = "var a={1,2}\n0,a,a,0,0,0,0,0,0,a\n";
+        let table = table("3", transitions).unwrap();
+        assert_eq!(table.next_state(0, &[2, 2, 0, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(table.next_state(0, &[1, 2, 0, 0, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_tables() {
+        assert!(table("1", "").is_err());
+        assert!(table("256", "").is_err());
+        assert!(table("2", "0,0,0,0,0,0,0,0,0,2\n").is_err());
+        assert!(table("2", "0,0,0\n").is_err());
+        assert!(RuleTable::parse("@TABLE\n0,0,0,0,0,0,0,0,0,1\n").is_err());
+    }
+
+    #[test]
+    fn changing_a_cell_cycles_through_every_state() {
+        let table = table("255", "").unwrap();
+        let mut game = Game::with_automaton(Box::new(RuleTableAutomaton::new(table, 2, 2)));
+        for state in 1..=255u16 {
+            game.change_value(0).unwrap();
+            assert_eq!(game.automaton().get_cell(0, 0) as u16, state % 255);
+        }
+    }
+
+    #[test]
+    fn the_cache_stays_bounded() {
+        let table = table("255", "var a={0,1}\n0,a,0,0,0,0,0,0,0,a\n").unwrap();
+        // Random boards of 255 states hardly repeat a neighbourhood
+        let mut automaton = RuleTableAutomaton::new(table, 300, 300);
+        for _ in 0..3 {
+            automaton.randomize();
+            automaton.step();
+            assert!(automaton.cache.len() <= MAX_CACHED);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+/*
+A small CDCL SAT solver: two watched literals, first-UIP clause learning, VSIDS branching
+with phase saving and Luby restarts. Variables and literals follow DIMACS, variables are
+numbered from 1 and a negative number is the negated variable.
+*/
+
+// Conflicts of the first restart, later restarts follow the Luby sequence in multiples of it
+const RESTART_BASE: u64 = 100;
+const ACTIVITY_DECAY: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SatResult {
+    Satisfiable,
+    Unsatisfiable,
+    // The conflict budget ran out first
+    Unknown,
+}
+
+#[derive(Debug, Default)]
+pub struct Solver {
+    // Per variable
+    assigns: Vec<Option<bool>>,
+    level: Vec<u32>,
+    reason: Vec<Option<usize>>,
+    phase: Vec<bool>,
+    activity: Vec<f64>,
+    seen: Vec<bool>,
+    order: VarOrder,
+    // Internal literals are 2 * variable + 1 if negated, with variables from 0
+    clauses: Vec<Vec<u32>>,
+    // Clauses watching each literal, visited when the literal becomes false
+    watches: Vec<Vec<usize>>,
+    trail: Vec<u32>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    var_inc: f64,
+    // False once the clauses are known to be unsatisfiable
+    ok: bool,
+    model: Vec<bool>,
+    conflicts: u64,
+}
+
+fn var(lit: u32) -> usize {
+    (lit >> 1) as usize
+}
+
+fn value(assigns: &[Option<bool>], lit: u32) -> Option<bool> {
+    assigns[var(lit)].map(|assigned| assigned != (lit & 1 == 1))
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self {
+            var_inc: 1.0,
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn new_var(&mut self) -> i32 {
+        let v = self.assigns.len();
+        self.assigns.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.phase.push(false);
+        self.activity.push(0.0);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        self.order.insert(v, &self.activity);
+        v as i32 + 1
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.assigns.len()
+    }
+
+    pub fn num_clauses(&self) -> usize {
+        self.clauses.len()
+    }
+
+    pub fn conflicts(&self) -> u64 {
+        self.conflicts
+    }
+
+    fn internal(&self, lit: i32) -> u32 {
+        let v = lit.unsigned_abs() - 1;
+        assert!(
+            (v as usize) < self.assigns.len(),
+            "unknown variable {}",
+            lit
+        );
+        v << 1 | (lit < 0) as u32
+    }
+
+    /*
+    Adds a clause, the disjunction of the given literals. Can be called between solves,
+    e.g. to block a solution found before. Returns false once the clauses are unsatisfiable.
+    */
+    pub fn add_clause(&mut self, lits: &[i32]) -> bool {
+        if !self.ok {
+            return false;
+        }
+        self.cancel_until(0);
+        let mut clause: Vec<u32> = lits.iter().map(|&lit| self.internal(lit)).collect();
+        clause.sort_unstable();
+        clause.dedup();
+        // A literal next to its negation satisfies the clause, they are neighbours after sorting
+        if clause.windows(2).any(|pair| pair[0] ^ 1 == pair[1]) {
+            return true;
+        }
+        if clause
+            .iter()
+            .any(|&lit| value(&self.assigns, lit) == Some(true))
+        {
+            return true;
+        }
+        clause.retain(|&lit| value(&self.assigns, lit).is_none());
+        match clause.len() {
+            0 => self.ok = false,
+            1 => {
+                self.enqueue(clause[0], None);
+                if self.propagate().is_some() {
+                    self.ok = false;
+                }
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+        self.ok
+    }
+
+    fn attach(&mut self, clause: Vec<u32>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0] as usize].push(index);
+        self.watches[clause[1] as usize].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    // Value of a variable in the last satisfying assignment
+    pub fn value(&self, variable: i32) -> bool {
+        self.model[(variable.unsigned_abs() - 1) as usize] != (variable < 0)
+    }
+
+    pub fn solve(&mut self) -> SatResult {
+        self.solve_limited(None)
+    }
+
+    // Gives up with Unknown after max_conflicts conflicts, counted over this call
+    pub fn solve_limited(&mut self, max_conflicts: Option<u64>) -> SatResult {
+        if !self.ok {
+            return SatResult::Unsatisfiable;
+        }
+        self.cancel_until(0);
+        if self.propagate().is_some() {
+            self.ok = false;
+            return SatResult::Unsatisfiable;
+        }
+        let limit = max_conflicts.map(|max| self.conflicts + max);
+        let mut restart = 0;
+        loop {
+            let mut budget = luby(restart) * RESTART_BASE;
+            if let Some(limit) = limit {
+                if self.conflicts >= limit {
+                    self.cancel_until(0);
+                    return SatResult::Unknown;
+                }
+                budget = budget.min(limit - self.conflicts);
+            }
+            if let Some(result) = self.search(budget) {
+                return result;
+            }
+            restart += 1;
+        }
+    }
+
+    // Runs until a result or until budget conflicts happened, then restarts by returning None
+    fn search(&mut self, budget: u64) -> Option<SatResult> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+                conflicts += 1;
+                if self.trail_lim.is_empty() {
+                    self.ok = false;
+                    return Some(SatResult::Unsatisfiable);
+                }
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.cancel_until(backtrack_level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let index = self.attach(learnt);
+                    self.enqueue(asserting, Some(index));
+                }
+                self.var_inc /= ACTIVITY_DECAY;
+            } else {
+                if conflicts >= budget {
+                    self.cancel_until(0);
+                    return None;
+                }
+                match self.pick_branch() {
+                    Some(v) => {
+                        self.trail_lim.push(self.trail.len());
+                        let lit = (v as u32) << 1 | (!self.phase[v]) as u32;
+                        self.enqueue(lit, None);
+                    }
+                    None => {
+                        self.model = self.assigns.iter().map(|a| a.unwrap_or(false)).collect();
+                        self.cancel_until(0);
+                        return Some(SatResult::Satisfiable);
+                    }
+                }
+            }
+        }
+    }
+
+    fn enqueue(&mut self, lit: u32, reason: Option<usize>) {
+        let v = var(lit);
+        self.assigns[v] = Some(lit & 1 == 0);
+        self.level[v] = self.trail_lim.len() as u32;
+        self.reason[v] = reason;
+        self.trail.push(lit);
+    }
+
+    // Unit propagation, returns the clause that became false if there is a conflict
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let false_lit = self.trail[self.qhead] ^ 1;
+            self.qhead += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit as usize]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                i += 1;
+                let clause = &mut self.clauses[index];
+                // Keep the false literal in the second watch
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if value(&self.assigns, first) == Some(true) {
+                    watchers[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                let replacement =
+                    (2..clause.len()).find(|&k| value(&self.assigns, clause[k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    let watch = clause[1] as usize;
+                    self.watches[watch].push(index);
+                    continue;
+                }
+                watchers[kept] = index;
+                kept += 1;
+                if value(&self.assigns, first) == Some(false) {
+                    conflict = Some(index);
+                    while i < watchers.len() {
+                        watchers[kept] = watchers[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(index));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[false_lit as usize] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /*
+    First-UIP conflict analysis: resolves the conflict clause with the reasons of the literals
+    of the current level until one is left. Returns the learnt clause with the asserting
+    literal first and the level to go back to.
+    */
+    fn analyze(&mut self, conflict: usize) -> (Vec<u32>, usize) {
+        let current_level = self.trail_lim.len() as u32;
+        let mut learnt = vec![0];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause_index = conflict;
+        let mut implied: Option<u32> = None;
+        loop {
+            let skip = implied.is_some() as usize;
+            for k in skip..self.clauses[clause_index].len() {
+                let lit = self.clauses[clause_index][k];
+                let v = var(lit);
+                if self.seen[v] || self.level[v] == 0 {
+                    continue;
+                }
+                self.seen[v] = true;
+                self.bump(v);
+                if self.level[v] >= current_level {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[var(self.trail[index])] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[var(lit)] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            clause_index = self.reason[var(lit)].expect("implied literal without a reason");
+        }
+        learnt[0] = implied.unwrap() ^ 1;
+        for &lit in &learnt[1..] {
+            self.seen[var(lit)] = false;
+        }
+        let mut backtrack_level = 0;
+        if learnt.len() > 1 {
+            let (highest, _) = learnt
+                .iter()
+                .enumerate()
+                .skip(1)
+                .max_by_key(|&(_, &lit)| self.level[var(lit)])
+                .unwrap();
+            learnt.swap(1, highest);
+            backtrack_level = self.level[var(learnt[1])] as usize;
+        }
+        (learnt, backtrack_level)
+    }
+
+    fn bump(&mut self, v: usize) {
+        self.activity[v] += self.var_inc;
+        if self.activity[v] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+        self.order.increased(v, &self.activity);
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.trail_lim.len() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for k in (start..self.trail.len()).rev() {
+            let v = var(self.trail[k]);
+            self.phase[v] = self.assigns[v].unwrap_or(false);
+            self.assigns[v] = None;
+            self.reason[v] = None;
+            self.order.insert(v, &self.activity);
+        }
+        self.trail.truncate(start);
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+
+    fn pick_branch(&mut self) -> Option<usize> {
+        while let Some(v) = self.order.pop(&self.activity) {
+            if self.assigns[v].is_none() {
+                return Some(v);
+            }
+        }
+        None
+    }
+}
+
+// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+fn luby(mut i: u64) -> u64 {
+    let mut size = 1;
+    let mut power = 1;
+    while size < i + 1 {
+        size = 2 * size + 1;
+        power *= 2;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        power /= 2;
+        if i >= size {
+            i -= size;
+        }
+    }
+    power
+}
+
+// Max-heap of variables by activity, remembering where each variable sits
+#[derive(Debug, Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    position: Vec<Option<usize>>,
+}
+
+impl VarOrder {
+    fn insert(&mut self, v: usize, activity: &[f64]) {
+        if self.position.len() <= v {
+            self.position.resize(v + 1, None);
+        }
+        if self.position[v].is_some() {
+            return;
+        }
+        self.position[v] = Some(self.heap.len());
+        self.heap.push(v);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, v: usize, activity: &[f64]) {
+        if let Some(at) = self.position[v] {
+            self.sift_up(at, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.position[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.position[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn sift_up(&mut self, mut at: usize, activity: &[f64]) {
+        while at > 0 {
+            let parent = (at - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[at]] {
+                break;
+            }
+            self.swap(at, parent);
+            at = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut at: usize, activity: &[f64]) {
+        loop {
+            let left = 2 * at + 1;
+            let right = left + 1;
+            let mut largest = at;
+            if left < self.heap.len() && activity[self.heap[left]] > activity[self.heap[largest]] {
+                largest = left;
+            }
+            if right < self.heap.len() && activity[self.heap[right]] > activity[self.heap[largest]]
+            {
+                largest = right;
+            }
+            if largest == at {
+                break;
+            }
+            self.swap(at, largest);
+            at = largest;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.position[self.heap[a]] = Some(a);
+        self.position[self.heap[b]] = Some(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solver(variables: usize, clauses: &[&[i32]]) -> Solver {
+        let mut solver = Solver::new();
+        for _ in 0..variables {
+            solver.new_var();
+        }
+        for clause in clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    fn satisfies(solver: &Solver, clauses: &[&[i32]]) -> bool {
+        clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|&lit| solver.value(lit.abs()) == (lit > 0))
+        })
+    }
+
+    #[test]
+    fn finds_a_model() {
+        let clauses: &[&[i32]] = &[&[1, 2], &[-1, 3], &[-2, -3], &[2, 3], &[-1, -2, 3]];
+        let mut solver = solver(3, clauses);
+        assert_eq!(solver.solve(), SatResult::Satisfiable);
+        assert!(satisfies(&solver, clauses));
+    }
+
+    #[test]
+    fn every_sign_of_two_variables_is_unsatisfiable() {
+        let mut solver = solver(2, &[&[1, 2], &[1, -2], &[-1, 2], &[-1, -2]]);
+        assert_eq!(solver.solve(), SatResult::Unsatisfiable);
+    }
+
+    // Three pigeons do not fit into two holes, pigeon p sits in hole h if 2 * p + h + 1
+    #[test]
+    fn pigeonhole_is_unsatisfiable() {
+        let sits = |pigeon: i32, hole: i32| 2 * pigeon + hole + 1;
+        let mut clauses: Vec<Vec<i32>> = (0..3).map(|p| vec![sits(p, 0), sits(p, 1)]).collect();
+        for hole in 0..2 {
+            for first in 0..3 {
+                for second in first + 1..3 {
+                    clauses.push(vec![-sits(first, hole), -sits(second, hole)]);
+                }
+            }
+        }
+        let clauses: Vec<&[i32]> = clauses.iter().map(Vec::as_slice).collect();
+        let mut solver = solver(6, &clauses);
+        assert_eq!(solver.solve(), SatResult::Unsatisfiable);
+    }
+
+    #[test]
+    fn blocking_a_model_finds_the_next() {
+        let mut solver = solver(2, &[&[1, 2]]);
+        let mut models = 0;
+        while solver.solve() == SatResult::Satisfiable {
+            models += 1;
+            let block: Vec<i32> = (1..=2)
+                .map(|v| if solver.value(v) { -v } else { v })
+                .collect();
+            solver.add_clause(&block);
+        }
+        assert_eq!(models, 3);
+    }
+
+    #[test]
+    fn an_empty_clause_is_unsatisfiable() {
+        let mut solver = solver(1, &[]);
+        assert!(!solver.add_clause(&[]));
+        assert_eq!(solver.solve(), SatResult::Unsatisfiable);
+    }
+}
@@ -0,0 +1,553 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sat::{SatResult, Solver};
+use crate::symmetry::Symmetry;
+use crate::{Rule, Universe};
+
+/*
+Settings of a pattern search. Looks for a pattern that stays inside a width x height box
+on an otherwise empty plane and comes back as itself after period generations, moved by dx
+columns and dy rows. Period 1 without a move finds still lifes, a longer period oscillators
+and a move spaceships. The period is exact, patterns repeating earlier are left out.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatternSearch {
+    pub width: u32,
+    pub height: u32,
+    pub period: u32,
+    pub dx: i32,
+    pub dy: i32,
+    pub symmetry: Symmetry,
+    pub rule: Rule,
+    // Conflicts the solver may run into before giving up, None searches until it is done
+    pub max_conflicts: Option<u64>,
+}
+
+// A found pattern, cells holds the (row, column) of every live cell inside its bounding box
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<(u32, u32)>,
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchResult {
+    Found(Pattern),
+    // There is no such pattern inside the box
+    NotFound,
+    // The solver ran out of conflicts before it knew
+    GaveUp,
+}
+
+impl Pattern {
+    /*
+    Places the pattern in the middle of an empty width x height board running its rule.
+    The board should leave room around the pattern, on a torus it meets itself otherwise.
+    */
+    pub fn to_universe(&self, width: u32, height: u32) -> Universe {
+        let mut universe = Universe::new(width, height);
+        universe.set_rule(self.rule);
+        let top = height.saturating_sub(self.height) / 2;
+        let left = width.saturating_sub(self.width) / 2;
+        for &(row, column) in &self.cells {
+            universe.set_alive((top + row) % height, (left + column) % width, true);
+        }
+        universe.live_cells_count();
+        universe
+    }
+
+    fn from_cells(cells: &[(u32, u32)], rule: Rule) -> Self {
+        let top = cells.iter().map(|&(row, _)| row).min().unwrap_or(0);
+        let left = cells.iter().map(|&(_, column)| column).min().unwrap_or(0);
+        let mut cells: Vec<(u32, u32)> = cells
+            .iter()
+            .map(|&(row, column)| (row - top, column - left))
+            .collect();
+        cells.sort_unstable();
+        Self {
+            width: cells
+                .iter()
+                .map(|&(_, column)| column + 1)
+                .max()
+                .unwrap_or(0),
+            height: cells.iter().map(|&(row, _)| row + 1).max().unwrap_or(0),
+            cells,
+            rule,
+        }
+    }
+}
+
+impl PatternSearch {
+    // Still lifes of Conway's Life inside the box, other searches change the public fields
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            period: 1,
+            dx: 0,
+            dy: 0,
+            symmetry: Symmetry::C1,
+            rule: Rule::conway(),
+            max_conflicts: None,
+        }
+    }
+
+    pub fn search(&self) -> Result<SearchResult, String> {
+        let mut encoding = Encoding::new(self)?;
+        Ok(encoding.next())
+    }
+
+    /*
+    Up to limit different patterns. Other phases and positions of a pattern found before
+    are not counted again. Stops early when there are no more or the solver gives up.
+    */
+    pub fn search_all(&self, limit: usize) -> Result<Vec<Pattern>, String> {
+        let mut encoding = Encoding::new(self)?;
+        let mut patterns = Vec::new();
+        while patterns.len() < limit {
+            match encoding.next() {
+                SearchResult::Found(pattern) => patterns.push(pattern),
+                _ => break,
+            }
+        }
+        Ok(patterns)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err("the search box must not be empty".to_owned());
+        }
+        if self.period == 0 {
+            return Err("the period must be at least 1".to_owned());
+        }
+        if self.dx.unsigned_abs() >= self.width || self.dy.unsigned_abs() >= self.height {
+            return Err("the pattern cannot move further than its box".to_owned());
+        }
+        if !self.symmetry.fits(self.width, self.height) {
+            return Err(format!(
+                "symmetry {} does not fit a {}x{} box",
+                self.symmetry, self.width, self.height
+            ));
+        }
+        // The cells around the box are taken to stay dead, under B0 they are born
+        if self.rule.next_state(false, 0) {
+            return Err(format!(
+                "rule {} with B0 fills the whole plane, its patterns cannot be searched",
+                self.rule
+            ));
+        }
+        Ok(())
+    }
+}
+
+/*
+Clauses for period generations of the box: one variable per cell of the box and generation,
+the generation after the last is generation 0 moved by (dx, dy). Every cell of the box and
+of the ring of cells around it has to follow the rule, the ring itself stays dead.
+*/
+struct Encoding {
+    search: PatternSearch,
+    solver: Solver,
+    // cells[generation][row * width + column]
+    cells: Vec<Vec<i32>>,
+    // Conflicts left over all the solves
+    budget: Option<u64>,
+}
+
+impl Encoding {
+    fn new(search: &PatternSearch) -> Result<Self, String> {
+        search.validate()?;
+        let mut solver = Solver::new();
+        let size = (search.width * search.height) as usize;
+        let cells = (0..search.period)
+            .map(|_| (0..size).map(|_| solver.new_var()).collect())
+            .collect();
+        let mut encoding = Self {
+            search: search.clone(),
+            solver,
+            cells,
+            budget: search.max_conflicts,
+        };
+        encoding.add_transitions();
+        encoding.add_exact_period();
+        encoding.add_symmetry();
+        let generation_zero = encoding.cells[0].clone();
+        encoding.solver.add_clause(&generation_zero);
+        Ok(encoding)
+    }
+
+    /*
+    Variable of a cell, None for cells outside the box which are always dead.
+    Generation period is generation 0 moved by (dx, dy).
+    */
+    fn cell(&self, generation: u32, row: i64, column: i64) -> Option<i32> {
+        let (width, height) = (self.search.width as i64, self.search.height as i64);
+        let (generation, row, column) = if generation == self.search.period {
+            (
+                0,
+                row - self.search.dy as i64,
+                column - self.search.dx as i64,
+            )
+        } else {
+            (generation, row, column)
+        };
+        if row < 0 || column < 0 || row >= height || column >= width {
+            return None;
+        }
+        Some(self.cells[generation as usize][(row * width + column) as usize])
+    }
+
+    fn add_transitions(&mut self) {
+        let (width, height) = (self.search.width as i64, self.search.height as i64);
+        // Beyond the ring only the moved generation 0 can have cells, which must stay dead
+        let (reach_x, reach_y) = (
+            1 + self.search.dx.abs() as i64,
+            1 + self.search.dy.abs() as i64,
+        );
+        for generation in 0..self.search.period {
+            for row in -reach_y..height + reach_y {
+                for column in -reach_x..width + reach_x {
+                    let cell = self.cell(generation, row, column);
+                    let next = self.cell(generation + 1, row, column);
+                    let mut neighbors = Vec::new();
+                    for delta_row in -1..=1 {
+                        for delta_col in -1..=1 {
+                            if delta_row == 0 && delta_col == 0 {
+                                continue;
+                            }
+                            if let Some(neighbor) =
+                                self.cell(generation, row + delta_row, column + delta_col)
+                            {
+                                neighbors.push(neighbor);
+                            }
+                        }
+                    }
+                    self.add_rule(cell, &neighbors, next);
+                }
+            }
+        }
+    }
+
+    /*
+    Counts the neighbours with a totalizer, at_least[k - 1] is true exactly when k or more are
+    alive, then forbids every combination of cell and count that does not lead to next
+    */
+    fn add_rule(&mut self, cell: Option<i32>, neighbors: &[i32], next: Option<i32>) {
+        let at_least = self.totalizer(neighbors);
+        for count in 0..=neighbors.len() {
+            for alive in [false, true] {
+                if cell.is_none() && alive {
+                    continue;
+                }
+                let next_alive = self.search.rule.next_state(alive, count as u8);
+                if next.is_none() && !next_alive {
+                    continue;
+                }
+                let mut clause = Vec::new();
+                if count > 0 {
+                    clause.push(-at_least[count - 1]);
+                }
+                if count < neighbors.len() {
+                    clause.push(at_least[count]);
+                }
+                if let Some(cell) = cell {
+                    clause.push(if alive { -cell } else { cell });
+                }
+                if let Some(next) = next {
+                    clause.push(if next_alive { next } else { -next });
+                }
+                self.solver.add_clause(&clause);
+            }
+        }
+    }
+
+    fn totalizer(&mut self, inputs: &[i32]) -> Vec<i32> {
+        if inputs.len() <= 1 {
+            return inputs.to_vec();
+        }
+        let (left, right) = inputs.split_at(inputs.len() / 2);
+        let left = self.totalizer(left);
+        let right = self.totalizer(right);
+        let sum: Vec<i32> = (0..inputs.len()).map(|_| self.solver.new_var()).collect();
+        // i from the left and j from the right give at least i + j, fewer give at most i + j
+        for i in 0..=left.len() {
+            for j in 0..=right.len() {
+                if i + j > 0 {
+                    let mut clause = vec![sum[i + j - 1]];
+                    if i > 0 {
+                        clause.push(-left[i - 1]);
+                    }
+                    if j > 0 {
+                        clause.push(-right[j - 1]);
+                    }
+                    self.solver.add_clause(&clause);
+                }
+                if i + j < inputs.len() {
+                    let mut clause = vec![-sum[i + j]];
+                    if i < left.len() {
+                        clause.push(left[i]);
+                    }
+                    if j < right.len() {
+                        clause.push(right[j]);
+                    }
+                    self.solver.add_clause(&clause);
+                }
+            }
+        }
+        sum
+    }
+
+    /*
+    A pattern repeating after a divisor of the period also repeats after period / p for some
+    prime p, so generation period / p has to differ from generation 0 moved by (dx, dy) / p.
+    If the move cannot be split p ways there is nothing to rule out. The two are compared on
+    the whole plane: a cell of generation 0 moved out of the box is a difference as well, as
+    generation period / p has no cells outside the box.
+    */
+    fn add_exact_period(&mut self) {
+        let PatternSearch {
+            width,
+            height,
+            period,
+            dx,
+            dy,
+            ..
+        } = self.search;
+        for prime in prime_factors(period) {
+            if dx % prime as i32 != 0 || dy % prime as i32 != 0 {
+                continue;
+            }
+            let generation = period / prime;
+            let (shift_x, shift_y) = ((dx / prime as i32) as i64, (dy / prime as i32) as i64);
+            let mut differences = Vec::new();
+            for row in 0..height as i64 {
+                for column in 0..width as i64 {
+                    let later = self.cell(generation, row, column).unwrap();
+                    let earlier = self.cell(0, row - shift_y, column - shift_x);
+                    let difference = self.solver.new_var();
+                    match earlier {
+                        Some(earlier) => {
+                            self.solver.add_clause(&[-difference, later, earlier]);
+                            self.solver.add_clause(&[-difference, -later, -earlier]);
+                        }
+                        None => {
+                            self.solver.add_clause(&[-difference, later]);
+                        }
+                    }
+                    differences.push(difference);
+                }
+            }
+            for row in 0..height as i64 {
+                for column in 0..width as i64 {
+                    if self.cell(0, row + shift_y, column + shift_x).is_none() {
+                        let earlier = self.cell(0, row, column).unwrap();
+                        let difference = self.solver.new_var();
+                        self.solver.add_clause(&[-difference, earlier]);
+                        differences.push(difference);
+                    }
+                }
+            }
+            self.solver.add_clause(&differences);
+        }
+    }
+
+    // The rules are symmetric, so a symmetric generation 0 keeps its symmetry
+    fn add_symmetry(&mut self) {
+        let (width, height) = (self.search.width, self.search.height);
+        for orbit in self.search.symmetry.orbits(height, width) {
+            let (first_row, first_col) = orbit[0];
+            let first = self.cells[0][(first_row * width + first_col) as usize];
+            for &(row, column) in &orbit[1..] {
+                let other = self.cells[0][(row * width + column) as usize];
+                self.solver.add_clause(&[-first, other]);
+                self.solver.add_clause(&[first, -other]);
+            }
+        }
+    }
+
+    fn next(&mut self) -> SearchResult {
+        let before = self.solver.conflicts();
+        let result = self.solver.solve_limited(self.budget);
+        if let Some(budget) = self.budget.as_mut() {
+            *budget = budget.saturating_sub(self.solver.conflicts() - before);
+        }
+        match result {
+            SatResult::Satisfiable => {
+                let phases = self.phases();
+                self.block(&phases);
+                SearchResult::Found(Pattern::from_cells(&phases[0], self.search.rule))
+            }
+            SatResult::Unsatisfiable => SearchResult::NotFound,
+            SatResult::Unknown => SearchResult::GaveUp,
+        }
+    }
+
+    // Live cells of every generation of the solution
+    fn phases(&self) -> Vec<Vec<(u32, u32)>> {
+        let width = self.search.width;
+        self.cells
+            .iter()
+            .map(|generation| {
+                generation
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &variable)| self.solver.value(variable))
+                    .map(|(idx, _)| (idx as u32 / width, idx as u32 % width))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /*
+    Rules out the solution as generation 0 in every phase and position it fits into the box,
+    so later solutions are really different patterns
+    */
+    fn block(&mut self, phases: &[Vec<(u32, u32)>]) {
+        let (width, height) = (self.search.width, self.search.height);
+        let mut blocked = HashSet::new();
+        for phase in phases {
+            let pattern = Pattern::from_cells(phase, self.search.rule);
+            for top in 0..=height.saturating_sub(pattern.height) {
+                for left in 0..=width.saturating_sub(pattern.width) {
+                    let mut alive = vec![false; (width * height) as usize];
+                    for &(row, column) in &pattern.cells {
+                        alive[((top + row) * width + left + column) as usize] = true;
+                    }
+                    blocked.insert(alive);
+                }
+            }
+        }
+        for alive in blocked {
+            let clause: Vec<i32> = self.cells[0]
+                .iter()
+                .zip(alive)
+                .map(|(&variable, alive)| if alive { -variable } else { variable })
+                .collect();
+            self.solver.add_clause(&clause);
+        }
+    }
+}
+
+fn prime_factors(mut n: u32) -> Vec<u32> {
+    let mut primes = Vec::new();
+    let mut p = 2;
+    while p * p <= n {
+        if n.is_multiple_of(p) {
+            primes.push(p);
+            while n.is_multiple_of(p) {
+                n /= p;
+            }
+        }
+        p += 1;
+    }
+    if n > 1 {
+        primes.push(n);
+    }
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(search: &PatternSearch) -> Pattern {
+        match search.search().unwrap() {
+            SearchResult::Found(pattern) => pattern,
+            other => panic!("expected a pattern, got {:?}", other),
+        }
+    }
+
+    // Live cells of a board as (row, column), the board is read at its top left corner
+    fn cells(universe: &Universe) -> Vec<(u32, u32)> {
+        let mut cells = Vec::new();
+        for row in 0..universe.height() {
+            for column in 0..universe.width() {
+                if universe.is_alive(row, column) {
+                    cells.push((row, column));
+                }
+            }
+        }
+        cells
+    }
+
+    // Runs the pattern on a board with room to spare and checks it comes back moved by (dx, dy)
+    fn repeats(pattern: &Pattern, period: u32, dx: i32, dy: i32) -> bool {
+        let mut universe = pattern.to_universe(20, 20);
+        let start = cells(&universe);
+        for _ in 0..period {
+            universe.tick();
+        }
+        let moved: Vec<(u32, u32)> = start
+            .iter()
+            .map(|&(row, column)| ((row as i32 + dy) as u32, (column as i32 + dx) as u32))
+            .collect();
+        cells(&universe) == moved
+    }
+
+    #[test]
+    fn finds_the_block() {
+        let pattern = found(&PatternSearch::new(2, 2));
+        assert_eq!(pattern.cells, vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn finds_the_blinker() {
+        let mut search = PatternSearch::new(3, 3);
+        search.period = 2;
+        let pattern = found(&search);
+        assert_eq!(pattern.cells.len(), 3);
+        assert!(pattern.width == 1 || pattern.height == 1);
+        assert!(repeats(&pattern, 2, 0, 0));
+        // The period is exact, still lifes are left out
+        assert!(!repeats(&pattern, 1, 0, 0));
+    }
+
+    #[test]
+    fn finds_the_glider() {
+        let mut search = PatternSearch::new(4, 4);
+        search.period = 4;
+        search.dx = 1;
+        search.dy = 1;
+        let pattern = found(&search);
+        assert_eq!(pattern.cells.len(), 5);
+        assert!(repeats(&pattern, 4, 1, 1));
+    }
+
+    #[test]
+    fn finds_the_lightweight_spaceship() {
+        let mut search = PatternSearch::new(7, 5);
+        search.period = 4;
+        search.dx = 2;
+        let pattern = found(&search);
+        assert!(repeats(&pattern, 4, 2, 0));
+        assert!(!repeats(&pattern, 2, 1, 0));
+    }
+
+    #[test]
+    fn nothing_moves_in_a_small_box() {
+        let mut search = PatternSearch::new(3, 3);
+        search.period = 4;
+        search.dx = 1;
+        search.dy = 1;
+        assert_eq!(search.search().unwrap(), SearchResult::NotFound);
+    }
+
+    #[test]
+    fn search_all_counts_every_pattern_once() {
+        let mut search = PatternSearch::new(3, 3);
+        search.period = 2;
+        assert_eq!(search.search_all(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rules_with_b0_are_errors() {
+        let mut search = PatternSearch::new(3, 3);
+        search.rule = "B03/S23".parse().unwrap();
+        assert!(search.search().is_err());
+        assert!(search.search_all(1).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::census::{Census, UNKNOWN};
+use crate::symmetry::Symmetry;
+use crate::{Rule, Universe};
+
+// apgcode recorded for soups that are still evolving after max_generations
+pub const UNSTABLE: &str = "zz_UNSTABLE";
+
+// Objects showing up in almost every soup, everything else gets its seed recorded
+pub const COMMON_OBJECTS: &[&str] = &[
+    "xs4_33",
+    "xp2_7",
+    "xs6_696",
+    "xq4_153",
+    "xs7_2596",
+    "xs5_253",
+    "xs4_252",
+    "xs6_356",
+    "xs8_6996",
+    "xs7_25ac",
+    "xs6_25a4",
+    "xp2_7e",
+    "xp2_318c",
+    "xs6_39c",
+];
+
+// Longest population cycle a settled soup may show
+const MAX_STABLE_PERIOD: usize = 30;
+// Generations the population has to follow that cycle before the soup counts as settled
+const STABLE_WINDOW: usize = 90;
+
+/*
+Settings of a soup search. Every soup is a soup_size x soup_size square of random cells,
+a cell narrower where its symmetry needs it, with the given symmetry in the middle of an
+otherwise empty board_size x board_size torus, seeded by its number.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SoupSearch {
+    pub soup_size: u32,
+    pub board_size: u32,
+    // Probability of a cell of the soup being alive
+    #[serde(deserialize_with = "density")]
+    pub density: f64,
+    #[serde(default)]
+    pub symmetry: Symmetry,
+    pub max_generations: u32,
+    pub threads: usize,
+    // Soups run between two saves of the progress file
+    pub batch_size: u64,
+    pub common: Vec<String>,
+}
+
+// Result of a single soup
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Soup {
+    pub seed: u64,
+    pub generations: u32,
+    pub stabilised: bool,
+    pub census: Census,
+}
+
+// A soup that produced an object outside the common ones
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Find {
+    pub seed: u64,
+    pub apgcode: String,
+}
+
+// What decides the soups of a search and their census, a search only resumes progress made with the same
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SoupSettings {
+    pub rule: Rule,
+    pub soup_size: u32,
+    pub board_size: u32,
+    pub density: f64,
+    pub symmetry: Symmetry,
+    pub max_generations: u32,
+}
+
+// Everything a search has done so far, saved after each batch so it can be resumed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Progress {
+    pub settings: SoupSettings,
+    pub next_seed: u64,
+    pub soups: u64,
+    pub census: Census,
+    pub finds: Vec<Find>,
+}
+
+impl Default for SoupSearch {
+    fn default() -> Self {
+        Self {
+            soup_size: 16,
+            board_size: 96,
+            density: 0.5,
+            symmetry: Symmetry::C1,
+            max_generations: 5000,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            batch_size: 1000,
+            common: COMMON_OBJECTS.iter().map(|code| code.to_string()).collect(),
+        }
+    }
+}
+
+impl Progress {
+    // Nothing done yet by a search with these settings
+    pub fn new(settings: SoupSettings) -> Progress {
+        Progress {
+            settings,
+            next_seed: 0,
+            soups: 0,
+            census: Census::new(),
+            finds: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Progress> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Writes to a temporary file first so an interrupted save never loses the old progress
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(temporary, path)
+    }
+
+    // Adds soups to the totals, they have to be sorted by seed and continue at next_seed
+    pub fn record(&mut self, soups: &[Soup], search: &SoupSearch) {
+        for soup in soups {
+            self.census.merge(&soup.census);
+            self.finds.extend(search.finds(soup));
+            self.next_seed = soup.seed + 1;
+            self.soups += 1;
+        }
+    }
+}
+
+// Reads a density, which has to be a probability
+fn density<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let density = f64::deserialize(deserializer)?;
+    check_density(density).map_err(serde::de::Error::custom)?;
+    Ok(density)
+}
+
+// NaN is no probability either
+fn check_density(density: f64) -> Result<(), String> {
+    if (0.0..=1.0).contains(&density) {
+        Ok(())
+    } else {
+        Err(format!(
+            "the density has to be between 0 and 1, not {}",
+            density
+        ))
+    }
+}
+
+impl SoupSearch {
+    pub fn validate(&self) -> Result<(), String> {
+        check_density(self.density)
+    }
+
+    // Soups run Conway's rule, the one a new Universe has
+    pub fn settings(&self) -> SoupSettings {
+        SoupSettings {
+            rule: Rule::default(),
+            soup_size: self.soup_size,
+            board_size: self.board_size,
+            density: self.density,
+            symmetry: self.symmetry,
+            max_generations: self.max_generations,
+        }
+    }
+
+    /*
+    The starting board of a soup, the same seed always gives the same board.
+    The soup loses a row or column where its symmetry needs another parity, see Symmetry::fit.
+    */
+    pub fn soup(&self, seed: u64) -> Result<Universe, String> {
+        let mut universe = Universe::new(self.board_size, self.board_size);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let size = self.soup_size.min(self.board_size);
+        let (width, height) = self.symmetry.fit(size, size);
+        universe.randomize_symmetric(&mut rng, self.symmetry, width, height, self.density)?;
+        Ok(universe)
+    }
+
+    // Runs a soup until it settles or max_generations is reached and takes its census
+    pub fn run_soup(&self, seed: u64) -> Result<Soup, String> {
+        let mut universe = self.soup(seed)?;
+        let mut populations = vec![universe.population()];
+        let mut stabilised = false;
+        while universe.generations() < self.max_generations {
+            universe.tick();
+            populations.push(universe.population());
+            if settled(&populations) {
+                stabilised = true;
+                break;
+            }
+        }
+        // Soups run Conway's rule, which has objects to count
+        let mut census = universe.census().unwrap();
+        if !stabilised {
+            census.add(UNSTABLE, 1);
+        }
+        Ok(Soup {
+            seed,
+            generations: universe.generations(),
+            stabilised,
+            census,
+        })
+    }
+
+    // Objects of a soup worth keeping its seed for
+    pub fn finds(&self, soup: &Soup) -> Vec<Find> {
+        soup.census
+            .objects
+            .keys()
+            .filter(|apgcode| !self.common.contains(apgcode))
+            .map(|apgcode| Find {
+                seed: soup.seed,
+                apgcode: apgcode.clone(),
+            })
+            .collect()
+    }
+
+    // Runs a range of seeds spread over the worker threads, results are sorted by seed
+    pub fn run_batch(&self, seeds: Range<u64>) -> Result<Vec<Soup>, String> {
+        self.validate()?;
+        let next = AtomicU64::new(seeds.start);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(|| loop {
+                    let seed = next.fetch_add(1, Ordering::Relaxed);
+                    if seed >= seeds.end {
+                        break;
+                    }
+                    let soup = self.run_soup(seed);
+                    results.lock().unwrap().push((seed, soup));
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(seed, _)| *seed);
+        results.into_iter().map(|(_, soup)| soup).collect()
+    }
+
+    /*
+    Runs the next `soups` seeds, picking up where the progress file left off.
+    The file is rewritten after every batch, so a stopped search loses at most one batch.
+    Progress made with other settings is an error, their censuses cannot be added up.
+    */
+    pub fn run(&self, progress_path: &Path, soups: u64) -> io::Result<Progress> {
+        self.validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut progress = if progress_path.exists() {
+            Progress::load(progress_path)?
+        } else {
+            Progress::new(self.settings())
+        };
+        if progress.settings != self.settings() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} was saved by a search with other settings",
+                    progress_path.display()
+                ),
+            ));
+        }
+        let end = progress.next_seed + soups;
+        while progress.next_seed < end {
+            let batch_end = (progress.next_seed + self.batch_size.max(1)).min(end);
+            let results = self
+                .run_batch(progress.next_seed..batch_end)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            progress.record(&results, self);
+            progress.save(progress_path)?;
+        }
+        Ok(progress)
+    }
+}
+
+// Whether the population has been repeating with a short period for long enough
+fn settled(populations: &[u32]) -> bool {
+    (1..=MAX_STABLE_PERIOD).any(|period| {
+        let window = STABLE_WINDOW.max(3 * period);
+        populations.len() > window + period
+            && (populations.len() - window..populations.len())
+                .all(|i| populations[i] == populations[i - period])
+    })
+}
+
+// Whether a find is an object that never repeated, as opposed to a rare but known one
+pub fn is_unknown(find: &Find) -> bool {
+    find.apgcode == UNKNOWN || find.apgcode == UNSTABLE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(density: f64) -> SoupSearch {
+        SoupSearch {
+            board_size: 32,
+            max_generations: 100,
+            threads: 1,
+            density,
+            ..SoupSearch::default()
+        }
+    }
+
+    #[test]
+    fn densities_outside_zero_to_one_are_errors() {
+        for density in [-0.5, 1.5, f64::NAN] {
+            let search = search(density);
+            assert!(search.validate().is_err());
+            assert!(search.soup(1).is_err());
+            assert!(search.run_batch(0..2).is_err());
+        }
+    }
+
+    #[test]
+    fn an_invalid_density_is_not_deserialized() {
+        let mut json = serde_json::to_value(search(0.5)).unwrap();
+        json["density"] = serde_json::json!(2.0);
+        assert!(serde_json::from_value::<SoupSearch>(json.clone()).is_err());
+        json["density"] = serde_json::json!(0.25);
+        assert_eq!(serde_json::from_value::<SoupSearch>(json).unwrap().density, 0.25);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_soup() {
+        let search = search(0.5);
+        let first = search.run_batch(3..5).unwrap();
+        let second = search.run_batch(3..5).unwrap();
+        assert_eq!(first.len(), 2);
+        for (first, second) in first.iter().zip(&second) {
+            assert_eq!(first.seed, second.seed);
+            assert_eq!(first.generations, second.generations);
+            assert_eq!(first.census.sorted(), second.census.sorted());
+        }
+    }
+
+    #[test]
+    fn a_region_is_cut_off_at_the_edges() {
+        let mut universe = Universe::new(8, 8);
+        let mut rng = StdRng::seed_from_u64(1);
+        universe
+            .randomize_region(&mut rng, 6, u32::MAX, 4, u32::MAX, 1.0)
+            .unwrap();
+        assert_eq!(universe.population(), 0);
+        universe
+            .randomize_region(&mut rng, 6, 6, u32::MAX, u32::MAX, 1.0)
+            .unwrap();
+        assert_eq!(universe.population(), 4);
+        assert!(universe
+            .randomize_region(&mut rng, 0, 0, 8, 8, 1.5)
+            .is_err());
+        assert_eq!(universe.population(), 4);
+    }
+
+    #[test]
+    fn progress_is_resumed_only_with_the_same_settings() {
+        let path = std::env::temp_dir().join(format!("gol_soup_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let batches = SoupSearch {
+            batch_size: 2,
+            ..search(0.5)
+        };
+        assert_eq!(batches.run(&path, 3).unwrap().next_seed, 3);
+        let progress = batches.run(&path, 2).unwrap();
+        assert_eq!((progress.next_seed, progress.soups), (5, 5));
+        assert!(search(0.25).run(&path, 1).is_err());
+        let symmetric = SoupSearch {
+            symmetry: Symmetry::C2_1,
+            ..batches.clone()
+        };
+        assert!(symmetric.run(&path, 1).is_err());
+        assert_eq!(Progress::load(&path).unwrap().soups, 5);
+        fs::remove_file(&path).unwrap();
+    }
+}
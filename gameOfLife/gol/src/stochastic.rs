@@ -0,0 +1,393 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::automaton::CellularAutomaton;
+use crate::Universe;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "UpdateModeFields")]
+pub enum UpdateMode {
+    // Every cell at once, like Universe::tick
+    Synchronous,
+    // One cell after the other in a new random order every generation, later cells see the earlier updates
+    RandomSequential,
+    // Every cell updates with the given probability, the others keep their state for this generation
+    Probabilistic(f64),
+}
+
+/*
+Failing rules: a birth the rule asks for does not happen with probability birth_failure,
+a death does not happen with probability death_failure
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "NoiseFields")]
+pub struct Noise {
+    pub birth_failure: f64,
+    pub death_failure: f64,
+}
+
+// What a mode and noise are read from before they are checked
+#[derive(Deserialize)]
+enum UpdateModeFields {
+    Synchronous,
+    RandomSequential,
+    Probabilistic(f64),
+}
+
+#[derive(Deserialize)]
+struct NoiseFields {
+    birth_failure: f64,
+    death_failure: f64,
+}
+
+fn check_probability(name: &str, probability: f64) -> Result<(), String> {
+    if (0.0..=1.0).contains(&probability) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} has to be a probability between 0 and 1, not {}",
+            name, probability
+        ))
+    }
+}
+
+impl UpdateMode {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            UpdateMode::Probabilistic(probability) => {
+                check_probability("the update probability", *probability)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<UpdateModeFields> for UpdateMode {
+    type Error = String;
+
+    fn try_from(fields: UpdateModeFields) -> Result<Self, Self::Error> {
+        let mode = match fields {
+            UpdateModeFields::Synchronous => UpdateMode::Synchronous,
+            UpdateModeFields::RandomSequential => UpdateMode::RandomSequential,
+            UpdateModeFields::Probabilistic(probability) => UpdateMode::Probabilistic(probability),
+        };
+        mode.validate()?;
+        Ok(mode)
+    }
+}
+
+impl Noise {
+    pub fn new(birth_failure: f64, death_failure: f64) -> Result<Self, String> {
+        let noise = Self {
+            birth_failure,
+            death_failure,
+        };
+        noise.validate()?;
+        Ok(noise)
+    }
+
+    // NaN is no probability either
+    pub fn validate(&self) -> Result<(), String> {
+        check_probability("birth_failure", self.birth_failure)?;
+        check_probability("death_failure", self.death_failure)
+    }
+
+    fn apply<R: Rng>(&self, rng: &mut R, cell: bool, next: bool) -> bool {
+        match (cell, next) {
+            (false, true) if rng.gen_bool(self.birth_failure) => false,
+            (true, false) if rng.gen_bool(self.death_failure) => true,
+            _ => next,
+        }
+    }
+}
+
+impl TryFrom<NoiseFields> for Noise {
+    type Error = String;
+
+    fn try_from(fields: NoiseFields) -> Result<Self, Self::Error> {
+        Noise::new(fields.birth_failure, fields.death_failure)
+    }
+}
+
+impl Universe {
+    /*
+    One generation under the given update mode and noise, all randomness comes from rng.
+    Synchronous without noise gives the same board as tick.
+    Fails without changing the board if a probability of the mode or noise is invalid.
+    */
+    pub fn tick_with<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        mode: UpdateMode,
+        noise: Noise,
+    ) -> Result<(), String> {
+        mode.validate()?;
+        noise.validate()?;
+        self.generations += 1;
+        match mode {
+            UpdateMode::Synchronous => self.update_all(rng, 1.0, noise),
+            UpdateMode::Probabilistic(probability) => self.update_all(rng, probability, noise),
+            UpdateMode::RandomSequential => {
+                let mut order: Vec<usize> = (0..self.array.len()).collect();
+                order.shuffle(rng);
+                for idx in order {
+                    let (row, col) = (idx as u32 / self.width, idx as u32 % self.width);
+                    let cell = self.array[idx];
+                    let live_neighbors = self.live_neighbor_count(row, col);
+                    let next = self.rule.next_state(cell, live_neighbors);
+                    self.array[idx] = noise.apply(rng, cell, next);
+                }
+            }
+        }
+        self.live_cells_count();
+        self.record_history();
+        Ok(())
+    }
+
+    // Updates every cell from the previous board, each one only with the given probability
+    fn update_all<R: Rng>(&mut self, rng: &mut R, probability: f64, noise: Noise) {
+        let mut next = self.array.clone();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if !rng.gen_bool(probability) {
+                    continue;
+                }
+                let idx = self.get_index(row, col);
+                let cell = self.array[idx];
+                let live_neighbors = self.live_neighbor_count(row, col);
+                let rule = self.rule.next_state(cell, live_neighbors);
+                next[idx] = noise.apply(rng, cell, rule);
+            }
+        }
+        self.array = next;
+    }
+}
+
+/*
+Life with asynchronous updates and noise, under the rule of its Universe.
+The random number generator is seeded, so the same seed, mode and noise always give the same run.
+*/
+#[derive(Debug, Clone)]
+pub struct StochasticLife {
+    universe: Universe,
+    mode: UpdateMode,
+    noise: Noise,
+    seed: u64,
+    rng: StdRng,
+}
+
+#[derive(Serialize)]
+struct StochasticView<'a> {
+    #[serde(flatten)]
+    universe: &'a Universe,
+    mode: UpdateMode,
+    noise: Noise,
+    seed: u64,
+}
+
+impl StochasticLife {
+    pub fn new(
+        width: u32,
+        height: u32,
+        mode: UpdateMode,
+        noise: Noise,
+        seed: u64,
+    ) -> Result<Self, String> {
+        mode.validate()?;
+        noise.validate()?;
+        Ok(Self {
+            universe: Universe::new(width, height),
+            mode,
+            noise,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    pub fn universe(&self) -> &Universe {
+        &self.universe
+    }
+
+    pub fn mode(&self) -> UpdateMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: UpdateMode) -> Result<(), String> {
+        mode.validate()?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    pub fn noise(&self) -> Noise {
+        self.noise
+    }
+
+    pub fn set_noise(&mut self, noise: Noise) -> Result<(), String> {
+        noise.validate()?;
+        self.noise = noise;
+        Ok(())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Restarts the random number generator, a run from the same board repeats itself
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
+
+impl CellularAutomaton for StochasticLife {
+    fn width(&self) -> u32 {
+        self.universe.width
+    }
+
+    fn height(&self) -> u32 {
+        self.universe.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.universe.generations
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.universe.is_alive(row, column) as u8
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        self.universe.set_alive(row, column, state != 0);
+    }
+
+    fn step(&mut self) {
+        // Mode and noise are checked whenever they are set, so this cannot fail
+        self.universe
+            .tick_with(&mut self.rng, self.mode, self.noise)
+            .unwrap();
+    }
+
+    fn population(&self) -> u32 {
+        self.universe.population()
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.universe.resize(width, height);
+    }
+
+    fn clear(&mut self) {
+        self.universe.clear();
+    }
+
+    // Uses the seeded generator as well, so the soup is part of the reproducible run
+    fn randomize(&mut self) {
+        let (width, height) = (self.universe.width, self.universe.height);
+        // 0.3 is a valid density, so this cannot fail
+        self.universe
+            .randomize_region(&mut self.rng, 0, 0, width, height, 0.3)
+            .unwrap();
+    }
+
+    fn serialize(&self) -> String {
+        let view = StochasticView {
+            universe: &self.universe,
+            mode: self.mode,
+            noise: self.noise,
+            seed: self.seed,
+        };
+        serde_json::to_string_pretty(&view).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probabilities_outside_zero_to_one_are_errors() {
+        assert!(Noise::new(0.1, 1.0).is_ok());
+        assert!(Noise::new(-0.1, 0.0).is_err());
+        assert!(Noise::new(0.0, f64::NAN).is_err());
+        assert!(UpdateMode::Probabilistic(f64::NAN).validate().is_err());
+        assert!(UpdateMode::Probabilistic(1.5).validate().is_err());
+        assert!(Noise::new(2.0, 0.0).is_err());
+        let mode = UpdateMode::Probabilistic(f64::INFINITY);
+        assert!(StochasticLife::new(4, 4, mode, Noise::default(), 1).is_err());
+    }
+
+    #[test]
+    fn invalid_probabilities_are_not_deserialized() {
+        let noise: Result<Noise, _> =
+            serde_json::from_str(r#"{"birth_failure": 0.5, "death_failure": 3}"#);
+        assert!(noise.is_err());
+        let mode: Result<UpdateMode, _> = serde_json::from_str(r#"{"Probabilistic": -1}"#);
+        assert!(mode.is_err());
+        let mode: UpdateMode = serde_json::from_str(r#"{"Probabilistic": 0.25}"#).unwrap();
+        assert_eq!(mode, UpdateMode::Probabilistic(0.25));
+    }
+
+    // A seeded board with a fair share of live cells
+    fn soup(seed: u64) -> Universe {
+        let mut universe = Universe::new(16, 16);
+        let mut rng = StdRng::seed_from_u64(seed);
+        universe
+            .randomize_region(&mut rng, 0, 0, 16, 16, 0.4)
+            .unwrap();
+        universe
+    }
+
+    #[test]
+    fn synchronous_without_noise_is_tick() {
+        let mut ticked = soup(7);
+        let mut updated = soup(7);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..10 {
+            ticked.tick();
+            updated
+                .tick_with(&mut rng, UpdateMode::Synchronous, Noise::default())
+                .unwrap();
+            assert_eq!(updated.array, ticked.array);
+        }
+        assert_eq!(updated.population(), ticked.population());
+        assert_eq!(updated.generations(), 10);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_run() {
+        let noise = Noise::new(0.2, 0.1).unwrap();
+        for mode in [
+            UpdateMode::Synchronous,
+            UpdateMode::RandomSequential,
+            UpdateMode::Probabilistic(0.5),
+        ] {
+            let run = |seed| {
+                let mut life = StochasticLife::new(16, 16, mode, noise, seed).unwrap();
+                life.randomize();
+                let mut boards = Vec::new();
+                for _ in 0..10 {
+                    life.step();
+                    boards.push(life.universe().array.clone());
+                }
+                boards
+            };
+            assert_eq!(run(5), run(5));
+            assert_ne!(run(5), run(6));
+        }
+    }
+
+    #[test]
+    fn an_invalid_step_leaves_the_board_alone() {
+        let mut universe = Universe::new(4, 4);
+        let mut rng = StdRng::seed_from_u64(1);
+        let noise = Noise {
+            birth_failure: f64::NAN,
+            death_failure: 0.0,
+        };
+        assert!(universe
+            .tick_with(&mut rng, UpdateMode::Synchronous, noise)
+            .is_err());
+        assert_eq!(universe.generations(), 0);
+    }
+}
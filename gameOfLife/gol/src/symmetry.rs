@@ -0,0 +1,435 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::Universe;
+
+/*
+Symmetries of a pattern inside a rectangle, named like apgsearch does:
+C for rotations only, D for rotations and reflections, + for reflections across the
+horizontal and vertical axes and x for reflections across the diagonals.
+The number after a group tells where its centre lies: 1 on a cell, 2 on the edge between
+two cells and 4 on the corner between four, which fixes whether the sides of the rectangle
+are odd or even. For D2_+ it tells whether the mirror runs through a column of cells or between two.
+The diagonal and quarter turn groups need a square.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(try_from = "String", into = "String")]
+pub enum Symmetry {
+    #[default]
+    C1,
+    C2_1,
+    C2_2,
+    C2_4,
+    C4_1,
+    C4_4,
+    D2Orthogonal1,
+    D2Orthogonal2,
+    D2Diagonal,
+    D4Orthogonal1,
+    D4Orthogonal2,
+    D4Orthogonal4,
+    D4Diagonal1,
+    D4Diagonal4,
+    D8_1,
+    D8_4,
+}
+
+// What the sides of a rectangle have to be for a symmetry to fit it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sides {
+    Any,
+    BothOdd,
+    // One odd and one even
+    Mixed,
+    BothEven,
+    OddWidth,
+    EvenWidth,
+}
+
+// One element of a symmetry group, mapping (row, column) inside a height x width rectangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transform {
+    Identity,
+    HalfTurn,
+    QuarterTurn,
+    ThreeQuarterTurn,
+    MirrorColumns,
+    MirrorRows,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Transform {
+    fn apply(self, row: u32, column: u32, height: u32, width: u32) -> (u32, u32) {
+        let (last_row, last_col) = (height - 1, width - 1);
+        match self {
+            Transform::Identity => (row, column),
+            Transform::HalfTurn => (last_row - row, last_col - column),
+            Transform::QuarterTurn => (column, last_row - row),
+            Transform::ThreeQuarterTurn => (last_col - column, row),
+            Transform::MirrorColumns => (row, last_col - column),
+            Transform::MirrorRows => (last_row - row, column),
+            Transform::Transpose => (column, row),
+            Transform::AntiTranspose => (last_col - column, last_row - row),
+        }
+    }
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 16] = [
+        Symmetry::C1,
+        Symmetry::C2_1,
+        Symmetry::C2_2,
+        Symmetry::C2_4,
+        Symmetry::C4_1,
+        Symmetry::C4_4,
+        Symmetry::D2Orthogonal1,
+        Symmetry::D2Orthogonal2,
+        Symmetry::D2Diagonal,
+        Symmetry::D4Orthogonal1,
+        Symmetry::D4Orthogonal2,
+        Symmetry::D4Orthogonal4,
+        Symmetry::D4Diagonal1,
+        Symmetry::D4Diagonal4,
+        Symmetry::D8_1,
+        Symmetry::D8_4,
+    ];
+
+    fn transforms(self) -> &'static [Transform] {
+        use Transform::*;
+        match self {
+            Symmetry::C1 => &[Identity],
+            Symmetry::C2_1 | Symmetry::C2_2 | Symmetry::C2_4 => &[Identity, HalfTurn],
+            Symmetry::C4_1 | Symmetry::C4_4 => &[Identity, QuarterTurn, HalfTurn, ThreeQuarterTurn],
+            Symmetry::D2Orthogonal1 | Symmetry::D2Orthogonal2 => &[Identity, MirrorColumns],
+            Symmetry::D2Diagonal => &[Identity, Transpose],
+            Symmetry::D4Orthogonal1 | Symmetry::D4Orthogonal2 | Symmetry::D4Orthogonal4 => {
+                &[Identity, MirrorColumns, MirrorRows, HalfTurn]
+            }
+            Symmetry::D4Diagonal1 | Symmetry::D4Diagonal4 => {
+                &[Identity, Transpose, AntiTranspose, HalfTurn]
+            }
+            Symmetry::D8_1 | Symmetry::D8_4 => &[
+                Identity,
+                QuarterTurn,
+                HalfTurn,
+                ThreeQuarterTurn,
+                MirrorColumns,
+                MirrorRows,
+                Transpose,
+                AntiTranspose,
+            ],
+        }
+    }
+
+    // Whether the group needs the rectangle to be a square
+    pub fn needs_square(self) -> bool {
+        matches!(
+            self,
+            Symmetry::C4_1
+                | Symmetry::C4_4
+                | Symmetry::D2Diagonal
+                | Symmetry::D4Diagonal1
+                | Symmetry::D4Diagonal4
+                | Symmetry::D8_1
+                | Symmetry::D8_4
+        )
+    }
+
+    fn sides(self) -> Sides {
+        match self {
+            Symmetry::C1 | Symmetry::D2Diagonal => Sides::Any,
+            Symmetry::C2_1
+            | Symmetry::C4_1
+            | Symmetry::D4Orthogonal1
+            | Symmetry::D4Diagonal1
+            | Symmetry::D8_1 => Sides::BothOdd,
+            Symmetry::C2_2 | Symmetry::D4Orthogonal2 => Sides::Mixed,
+            Symmetry::C2_4
+            | Symmetry::C4_4
+            | Symmetry::D4Orthogonal4
+            | Symmetry::D4Diagonal4
+            | Symmetry::D8_4 => Sides::BothEven,
+            Symmetry::D2Orthogonal1 => Sides::OddWidth,
+            Symmetry::D2Orthogonal2 => Sides::EvenWidth,
+        }
+    }
+
+    // Whether the symmetry can be laid on a width x height rectangle
+    pub fn fits(self, width: u32, height: u32) -> bool {
+        if self.needs_square() && width != height {
+            return false;
+        }
+        let (odd_width, odd_height) = (width % 2 == 1, height % 2 == 1);
+        match self.sides() {
+            Sides::Any => true,
+            Sides::BothOdd => odd_width && odd_height,
+            Sides::Mixed => odd_width != odd_height,
+            Sides::BothEven => !odd_width && !odd_height,
+            Sides::OddWidth => odd_width,
+            Sides::EvenWidth => !odd_width,
+        }
+    }
+
+    /*
+    The largest rectangle inside width x height the symmetry fits: a side of the wrong parity
+    loses a cell, with one odd and one even side needed the height gives way first.
+    Nothing fits when there is no cell left to take away.
+    */
+    pub fn fit(self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = if self.needs_square() {
+            (width.min(height), width.min(height))
+        } else {
+            (width, height)
+        };
+        let with_parity = |side: u32, odd: bool| {
+            if (side % 2 == 1) == odd {
+                side
+            } else {
+                side.saturating_sub(1)
+            }
+        };
+        match self.sides() {
+            Sides::Any => (width, height),
+            Sides::BothOdd => (with_parity(width, true), with_parity(height, true)),
+            Sides::BothEven => (with_parity(width, false), with_parity(height, false)),
+            Sides::OddWidth => (with_parity(width, true), height),
+            Sides::EvenWidth => (with_parity(width, false), height),
+            Sides::Mixed if self.fits(width, height) || (width, height) == (0, 0) => {
+                (width, height)
+            }
+            Sides::Mixed if height > 0 => (width, height - 1),
+            Sides::Mixed => (width - 1, height),
+        }
+    }
+
+    /*
+    Every cell the symmetry maps (row, column) to inside a height x width rectangle,
+    the cell itself included. Cells of one orbit have to share their state.
+    */
+    pub fn orbit(self, row: u32, column: u32, height: u32, width: u32) -> Vec<(u32, u32)> {
+        let mut cells = Vec::new();
+        for transform in self.transforms() {
+            let cell = transform.apply(row, column, height, width);
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+
+    // All orbits of the rectangle, each listed once starting with its first cell in row order
+    pub fn orbits(self, height: u32, width: u32) -> Vec<Vec<(u32, u32)>> {
+        let mut covered = vec![false; height as usize * width as usize];
+        let mut orbits = Vec::new();
+        for row in 0..height {
+            for column in 0..width {
+                if covered[(row * width + column) as usize] {
+                    continue;
+                }
+                let orbit = self.orbit(row, column, height, width);
+                for &(r, c) in &orbit {
+                    covered[(r * width + c) as usize] = true;
+                }
+                orbits.push(orbit);
+            }
+        }
+        orbits
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::C1 => "C1",
+            Symmetry::C2_1 => "C2_1",
+            Symmetry::C2_2 => "C2_2",
+            Symmetry::C2_4 => "C2_4",
+            Symmetry::C4_1 => "C4_1",
+            Symmetry::C4_4 => "C4_4",
+            Symmetry::D2Orthogonal1 => "D2_+1",
+            Symmetry::D2Orthogonal2 => "D2_+2",
+            Symmetry::D2Diagonal => "D2_x",
+            Symmetry::D4Orthogonal1 => "D4_+1",
+            Symmetry::D4Orthogonal2 => "D4_+2",
+            Symmetry::D4Orthogonal4 => "D4_+4",
+            Symmetry::D4Diagonal1 => "D4_x1",
+            Symmetry::D4Diagonal4 => "D4_x4",
+            Symmetry::D8_1 => "D8_1",
+            Symmetry::D8_4 => "D8_4",
+        }
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Symmetry::ALL
+            .iter()
+            .copied()
+            .find(|symmetry| symmetry.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| format!("unknown symmetry {}", name))
+    }
+}
+
+impl TryFrom<String> for Symmetry {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        name.parse()
+    }
+}
+
+impl From<Symmetry> for String {
+    fn from(symmetry: Symmetry) -> Self {
+        symmetry.name().to_owned()
+    }
+}
+
+impl Universe {
+    /*
+    Clears the board and fills a width x height region in its middle at random with the given
+    symmetry: all cells of an orbit share one random state, alive with probability density.
+    The region may be the whole board, it has to be one the symmetry fits, see Symmetry::fit.
+    */
+    pub fn randomize_symmetric<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        symmetry: Symmetry,
+        width: u32,
+        height: u32,
+        density: f64,
+    ) -> Result<(), String> {
+        if width > self.width || height > self.height {
+            return Err(format!(
+                "a {}x{} region does not fit on a {}x{} board",
+                width, height, self.width, self.height
+            ));
+        }
+        if !symmetry.fits(width, height) {
+            return Err(format!(
+                "symmetry {} does not fit a {}x{} region",
+                symmetry, width, height
+            ));
+        }
+        if !(0.0..=1.0).contains(&density) {
+            return Err(format!(
+                "the density has to be between 0 and 1, not {}",
+                density
+            ));
+        }
+        let top = (self.height - height) / 2;
+        let left = (self.width - width) / 2;
+        self.generations = 0;
+        self.array = vec![false; self.array.len()];
+        for orbit in symmetry.orbits(height, width) {
+            let alive = rng.gen_bool(density);
+            for (row, col) in orbit {
+                let idx = self.get_index(top + row, left + col);
+                self.array[idx] = alive;
+            }
+        }
+        self.live_cells_count();
+        self.reset_history();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    // Whether the region in the middle of the board looks the same under every element of the group
+    fn invariant(universe: &Universe, symmetry: Symmetry, width: u32, height: u32) -> bool {
+        let top = (universe.height() - height) / 2;
+        let left = (universe.width() - width) / 2;
+        (0..height).all(|row| {
+            (0..width).all(|column| {
+                symmetry.transforms().iter().all(|transform| {
+                    let (image_row, image_col) = transform.apply(row, column, height, width);
+                    universe.is_alive(top + row, left + column)
+                        == universe.is_alive(top + image_row, left + image_col)
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn every_variant_gives_soups_invariant_under_its_group() {
+        for symmetry in Symmetry::ALL {
+            for (width, height) in [(9, 9), (10, 10), (10, 7), (7, 10)] {
+                let (width, height) = symmetry.fit(width, height);
+                assert!(
+                    symmetry.fits(width, height),
+                    "{} {}x{}",
+                    symmetry,
+                    width,
+                    height
+                );
+                for seed in 0..5 {
+                    let mut universe = Universe::new(16, 16);
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    universe
+                        .randomize_symmetric(&mut rng, symmetry, width, height, 0.5)
+                        .unwrap();
+                    assert!(invariant(&universe, symmetry, width, height));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_variant_fixes_the_parity_of_the_sides() {
+        assert_eq!(Symmetry::C1.fit(16, 9), (16, 9));
+        assert_eq!(Symmetry::C2_1.fit(16, 16), (15, 15));
+        assert_eq!(Symmetry::C2_2.fit(16, 16), (16, 15));
+        assert_eq!(Symmetry::C2_2.fit(16, 15), (16, 15));
+        assert_eq!(Symmetry::C2_4.fit(16, 15), (16, 14));
+        assert_eq!(Symmetry::D2Orthogonal1.fit(16, 16), (15, 16));
+        assert_eq!(Symmetry::D2Orthogonal2.fit(15, 15), (14, 15));
+        assert_eq!(Symmetry::D4Orthogonal4.fit(15, 15), (14, 14));
+        assert_eq!(Symmetry::D8_1.fit(16, 12), (11, 11));
+        assert_eq!(Symmetry::D8_4.fit(16, 12), (12, 12));
+        assert!(!Symmetry::D4Orthogonal2.fits(0, 0));
+        assert_eq!(Symmetry::D4Orthogonal2.fit(0, 0), (0, 0));
+    }
+
+    #[test]
+    fn a_region_the_symmetry_does_not_fit_is_an_error() {
+        let mut universe = Universe::new(16, 16);
+        let mut rng = StdRng::seed_from_u64(1);
+        for (symmetry, width, height) in [
+            (Symmetry::C2_1, 16, 16),
+            (Symmetry::C2_2, 15, 15),
+            (Symmetry::D4Diagonal4, 16, 14),
+            (Symmetry::C1, 17, 16),
+        ] {
+            assert!(universe
+                .randomize_symmetric(&mut rng, symmetry, width, height, 0.5)
+                .is_err());
+        }
+        assert!(universe
+            .randomize_symmetric(&mut rng, Symmetry::C1, 8, 8, 1.5)
+            .is_err());
+    }
+
+    #[test]
+    fn names_are_the_ones_of_apgsearch() {
+        for symmetry in Symmetry::ALL {
+            assert_eq!(symmetry.name().parse::<Symmetry>(), Ok(symmetry));
+        }
+        assert_eq!("d4_+2".parse::<Symmetry>(), Ok(Symmetry::D4Orthogonal2));
+        assert!("C2".parse::<Symmetry>().is_err());
+    }
+}
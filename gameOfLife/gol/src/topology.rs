@@ -0,0 +1,41 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/*
+What lies beyond the edges of a board. On a torus the edges wrap around, so the cells of one
+edge neighbour those of the opposite one. A bounded board is surrounded by dead cells,
+gliders leaving it turn into blocks at the edge.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    #[default]
+    Torus,
+    Bounded,
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Torus => write!(f, "torus"),
+            Topology::Bounded => write!(f, "bounded"),
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(topology: &str) -> Result<Self, Self::Err> {
+        match topology.trim().to_ascii_lowercase().as_str() {
+            "torus" => Ok(Topology::Torus),
+            "bounded" => Ok(Topology::Bounded),
+            _ => Err(format!(
+                "invalid topology {}, expected torus or bounded",
+                topology
+            )),
+        }
+    }
+}
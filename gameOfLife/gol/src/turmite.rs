@@ -0,0 +1,462 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::automaton::CellularAutomaton;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    None,
+    Right,
+    UTurn,
+    Left,
+}
+
+// What a turmite does on a cell: paint it, turn and change its own state, then it moves one cell forward
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Action {
+    pub write: u8,
+    pub turn: Turn,
+    pub next_state: u8,
+}
+
+/*
+Transition table of a turmite, actions[state][colour] is what a turmite in that state does
+on a cell of that colour. Langton's Ant is the turmite with one state and two colours.
+*/
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TurmiteRule {
+    actions: Vec<Vec<Action>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turmite {
+    pub row: u32,
+    pub column: u32,
+    pub direction: Direction,
+    pub state: u8,
+}
+
+impl Direction {
+    fn turned(self, turn: Turn) -> Direction {
+        let quarters = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::UTurn => 2,
+            Turn::Left => 3,
+        };
+        let directions = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ];
+        directions[(self as usize + quarters) % 4]
+    }
+}
+
+impl TurmiteRule {
+    pub fn new(actions: Vec<Vec<Action>>) -> Result<Self, String> {
+        let colors = actions.first().map_or(0, Vec::len);
+        if colors < 2 || actions.iter().any(|state| state.len() != colors) {
+            return Err("every state needs an action for each of at least two colours".to_owned());
+        }
+        // Colours and states are stored in a u8 and counted by colors() and states()
+        if colors > 255 || actions.len() > 255 {
+            return Err("a turmite has at most 255 states and 255 colours".to_owned());
+        }
+        let valid = actions.iter().flatten().all(|action| {
+            (action.write as usize) < colors && (action.next_state as usize) < actions.len()
+        });
+        if !valid {
+            return Err("an action writes an unknown colour or enters an unknown state".to_owned());
+        }
+        Ok(Self { actions })
+    }
+
+    pub fn langtons_ant() -> Self {
+        TurmiteRule::from_turns("RL").unwrap()
+    }
+
+    /*
+    Multi-colour ant: the n-th letter is the turn (L, R, N or U) on colour n,
+    every visit moves the cell on to the next colour. Langton's Ant is "RL".
+    */
+    pub fn from_turns(turns: &str) -> Result<Self, String> {
+        let turns = turns
+            .chars()
+            .map(|letter| match letter.to_ascii_uppercase() {
+                'L' => Ok(Turn::Left),
+                'R' => Ok(Turn::Right),
+                'N' => Ok(Turn::None),
+                'U' => Ok(Turn::UTurn),
+                other => Err(format!("unknown turn {}", other)),
+            })
+            .collect::<Result<Vec<Turn>, String>>()?;
+        let colors = turns.len();
+        let actions = turns
+            .iter()
+            .enumerate()
+            .map(|(color, &turn)| Action {
+                write: ((color + 1) % colors.max(1)) as u8,
+                turn,
+                next_state: 0,
+            })
+            .collect();
+        TurmiteRule::new(vec![actions])
+    }
+
+    /*
+    Reads the notation used by Golly and Ed Pegg, e.g. {{{1, 2, 0}, {0, 8, 0}}} for Langton's Ant:
+    one list per state, in it one {write, turn, next state} per colour,
+    with the turns 1 (none), 2 (right), 4 (u-turn) and 8 (left)
+    */
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut chars = spec.chars().filter(|c| !c.is_whitespace()).peekable();
+        let states = match parse_list(&mut chars)? {
+            Node::List(states) => states,
+            Node::Number(_) => return Err("a turmite needs a list of states".to_owned()),
+        };
+        if chars.next().is_some() {
+            return Err("unexpected text after the turmite".to_owned());
+        }
+        let mut actions = Vec::new();
+        for state in states {
+            let Node::List(colors) = state else {
+                return Err("every state has to be a list of actions".to_owned());
+            };
+            let mut row = Vec::new();
+            for color in colors {
+                let numbers = match color {
+                    Node::List(numbers) => numbers,
+                    Node::Number(_) => return Err("every action has to be a list".to_owned()),
+                };
+                let numbers = numbers
+                    .iter()
+                    .map(|number| match number {
+                        Node::Number(n) => Ok(*n),
+                        Node::List(_) => Err("actions contain numbers only".to_owned()),
+                    })
+                    .collect::<Result<Vec<u32>, String>>()?;
+                let [write, turn, next_state] = numbers[..] else {
+                    return Err("every action has three numbers".to_owned());
+                };
+                let turn = match turn {
+                    1 => Turn::None,
+                    2 => Turn::Right,
+                    4 => Turn::UTurn,
+                    8 => Turn::Left,
+                    other => return Err(format!("unknown turn {}", other)),
+                };
+                row.push(Action {
+                    write: u8::try_from(write).map_err(|_| "colour too large".to_owned())?,
+                    turn,
+                    next_state: u8::try_from(next_state)
+                        .map_err(|_| "state too large".to_owned())?,
+                });
+            }
+            actions.push(row);
+        }
+        TurmiteRule::new(actions)
+    }
+
+    pub fn colors(&self) -> u8 {
+        self.actions[0].len() as u8
+    }
+
+    pub fn states(&self) -> u8 {
+        self.actions.len() as u8
+    }
+
+    pub fn action(&self, state: u8, color: u8) -> Action {
+        self.actions[state as usize][color as usize]
+    }
+}
+
+enum Node {
+    Number(u32),
+    List(Vec<Node>),
+}
+
+fn parse_list<I: Iterator<Item = char>>(
+    chars: &mut std::iter::Peekable<I>,
+) -> Result<Node, String> {
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                items.push(parse_list(chars)?);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Node::List(items)),
+                    _ => return Err("missing } in turmite".to_owned()),
+                }
+            }
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                number.push(c);
+                chars.next();
+            }
+            number
+                .parse()
+                .map(Node::Number)
+                .map_err(|_| "number too large".to_owned())
+        }
+        _ => Err("expected a number or {".to_owned()),
+    }
+}
+
+/*
+Grid of coloured cells on a torus the size of a Universe with any number of turmites walking over it.
+Every step each turmite acts once, in the order they were added.
+*/
+#[derive(Serialize, Debug, Clone)]
+pub struct TurmiteWorld {
+    width: u32,
+    height: u32,
+    generations: u32,
+    live_cells: u32,
+    rule: TurmiteRule,
+    array: Vec<u8>,
+    ants: Vec<Turmite>,
+}
+
+impl TurmiteWorld {
+    // An empty grid with a single turmite in the middle facing north
+    pub fn new(rule: TurmiteRule, width: u32, height: u32) -> Self {
+        let mut world = Self {
+            width,
+            height,
+            generations: 0,
+            live_cells: 0,
+            rule,
+            array: vec![0; width as usize * height as usize],
+            ants: Vec::new(),
+        };
+        if width > 0 && height > 0 {
+            world.add_ant(Turmite {
+                row: height / 2,
+                column: width / 2,
+                direction: Direction::North,
+                state: 0,
+            });
+        }
+        world
+    }
+
+    pub fn rule(&self) -> &TurmiteRule {
+        &self.rule
+    }
+
+    pub fn ants(&self) -> &[Turmite] {
+        &self.ants
+    }
+
+    // Turmites outside of the grid are moved back onto it, an empty grid takes none
+    pub fn add_ant(&mut self, mut ant: Turmite) {
+        if self.array.is_empty() {
+            return;
+        }
+        ant.row %= self.height;
+        ant.column %= self.width;
+        ant.state = ant.state.min(self.rule.states() - 1);
+        self.ants.push(ant);
+    }
+
+    pub fn remove_ants(&mut self) {
+        self.ants.clear();
+    }
+
+    fn get_index(&self, row: u32, column: u32) -> usize {
+        (row * self.width + column) as usize
+    }
+
+    fn live_cells_count(&mut self) {
+        self.live_cells = self.array.iter().filter(|&&color| color != 0).count() as u32;
+    }
+}
+
+impl CellularAutomaton for TurmiteWorld {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn generations(&self) -> u32 {
+        self.generations
+    }
+
+    fn states(&self) -> u8 {
+        self.rule.colors()
+    }
+
+    fn get_cell(&self, row: u32, column: u32) -> u8 {
+        self.array[self.get_index(row, column)]
+    }
+
+    fn set_cell(&mut self, row: u32, column: u32, state: u8) {
+        let idx = self.get_index(row, column);
+        let color = state.min(self.rule.colors() - 1);
+        self.live_cells = self.live_cells + (color != 0) as u32 - (self.array[idx] != 0) as u32;
+        self.array[idx] = color;
+    }
+
+    fn step(&mut self) {
+        self.generations += 1;
+        // There is nowhere to go on an empty grid
+        if self.array.is_empty() {
+            return;
+        }
+        for i in 0..self.ants.len() {
+            let ant = self.ants[i];
+            let idx = self.get_index(ant.row, ant.column);
+            let action = self.rule.action(ant.state, self.array[idx]);
+            self.array[idx] = action.write;
+            let direction = ant.direction.turned(action.turn);
+            let (row, column) = match direction {
+                Direction::North => ((ant.row + self.height - 1) % self.height, ant.column),
+                Direction::East => (ant.row, (ant.column + 1) % self.width),
+                Direction::South => ((ant.row + 1) % self.height, ant.column),
+                Direction::West => (ant.row, (ant.column + self.width - 1) % self.width),
+            };
+            self.ants[i] = Turmite {
+                row,
+                column,
+                direction,
+                state: action.next_state,
+            };
+        }
+        self.live_cells_count();
+    }
+
+    fn population(&self) -> u32 {
+        self.live_cells
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        *self = TurmiteWorld::new(self.rule.clone(), width, height);
+    }
+
+    // Blanks the grid, the turmites stay where they are
+    fn clear(&mut self) {
+        self.generations = 0;
+        self.array = vec![0; self.array.len()];
+        self.live_cells_count();
+    }
+
+    // Blanks the grid and scatters the turmites, keeping how many there are
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        let count = self.ants.len().max(1);
+        self.clear();
+        self.ants.clear();
+        if self.array.is_empty() {
+            return;
+        }
+        let directions = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ];
+        for _ in 0..count {
+            self.add_ant(Turmite {
+                row: rng.gen_range(0..self.height),
+                column: rng.gen_range(0..self.width),
+                direction: directions[rng.gen_range(0..4)],
+                state: 0,
+            });
+        }
+    }
+
+    // The Universe fields with colours in the array, plus the rule and where the turmites are
+    fn serialize(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn langtons_ant_turns_right_on_white() {
+        let mut world = TurmiteWorld::new(TurmiteRule::langtons_ant(), 5, 5);
+        world.step();
+        assert_eq!(world.get_cell(2, 2), 1);
+        assert_eq!(world.population(), 1);
+        let ant = world.ants()[0];
+        assert_eq!(
+            (ant.row, ant.column, ant.direction),
+            (2, 3, Direction::East)
+        );
+    }
+
+    #[test]
+    fn more_than_255_states_or_colours_are_errors() {
+        assert!(TurmiteRule::from_turns(&"RL".repeat(128)).is_err());
+        assert_eq!(
+            TurmiteRule::from_turns(&"L".repeat(255)).unwrap().colors(),
+            255
+        );
+        let action = Action {
+            write: 0,
+            turn: Turn::Right,
+            next_state: 0,
+        };
+        assert!(TurmiteRule::new(vec![vec![action; 2]; 256]).is_err());
+        assert_eq!(
+            TurmiteRule::new(vec![vec![action; 2]; 255])
+                .unwrap()
+                .states(),
+            255
+        );
+    }
+
+    #[test]
+    fn an_empty_grid_has_no_turmites() {
+        for (width, height) in [(0, 0), (0, 3), (3, 0)] {
+            let mut world = TurmiteWorld::new(TurmiteRule::langtons_ant(), width, height);
+            world.randomize();
+            world.add_ant(Turmite {
+                row: 1,
+                column: 1,
+                direction: Direction::North,
+                state: 0,
+            });
+            world.step();
+            assert!(world.ants().is_empty());
+            assert_eq!(world.generations(), 1);
+        }
+    }
+
+    #[test]
+    fn randomize_keeps_the_number_of_turmites() {
+        let mut world = TurmiteWorld::new(TurmiteRule::langtons_ant(), 8, 8);
+        world.add_ant(Turmite {
+            row: 10,
+            column: 3,
+            direction: Direction::South,
+            state: 0,
+        });
+        assert_eq!(world.ants()[1].row, 2);
+        world.randomize();
+        assert_eq!(world.ants().len(), 2);
+        world.resize(0, 0);
+        world.randomize();
+        assert!(world.ants().is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+/*
+Client of the game API of gol_http, see /api/v1/openapi.json on a running server for the
+document it follows. Every call blocks until the server answered.
+
+    let client = Client::new("http://localhost:8000");
+    let game = client.create_game(&NewGame::default())?;
+    let game = client.tick(&game.id, 10)?;
+    println!("{} cells alive", game.universe.population());
+*/
+use std::fmt;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use gol::colours::ColourLife;
+use gol::Universe;
+
+pub const API_PATH: &str = "/api/v1";
+
+/*
+A game as the server sends it, for games running plain Life. The board of any other engine
+does not fit a Universe and fails to be read with a Transport error, e.g. the array of a
+colour game holds colours instead of booleans, see ColourGameResource.
+*/
+#[derive(Deserialize, Debug, Clone)]
+pub struct GameResource {
+    pub id: String,
+    #[serde(flatten)]
+    pub universe: Universe,
+}
+
+// A game created with colours, see Client::colour_game
+#[derive(Deserialize, Debug, Clone)]
+pub struct ColourGameResource {
+    pub id: String,
+    #[serde(flatten)]
+    pub board: ColourLife,
+}
+
+// What listing the games shows about each of them
+#[derive(Deserialize, Debug, Clone)]
+pub struct GameInfo {
+    pub id: String,
+    pub width: u32,
+    pub height: u32,
+    pub generations: u32,
+    pub playing: bool,
+    #[serde(default)]
+    pub players: usize,
+    pub idle_seconds: u64,
+}
+
+// A player of a colour game, score counts the live cells in their colour
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlayerInfo {
+    pub id: String,
+    pub name: String,
+    pub colour: u8,
+    pub score: u32,
+    pub placed: u32,
+}
+
+// Options of a new game, the server picks its default size for those left out
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct NewGame {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    // New games start randomized unless this is false
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub randomize: Option<bool>,
+    // 2 for Immigration or 4 for QuadLife, the game then takes players, see create_colour_game
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colours: Option<u8>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    pub row: u32,
+    pub column: u32,
+    pub state: u8,
+}
+
+impl CellChange {
+    pub fn alive(row: u32, column: u32) -> Self {
+        Self {
+            row,
+            column,
+            state: 1,
+        }
+    }
+
+    pub fn dead(row: u32, column: u32) -> Self {
+        Self {
+            row,
+            column,
+            state: 0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CellChanges<'a> {
+    cells: &'a [CellChange],
+}
+
+#[derive(Serialize)]
+struct PlacedCell {
+    row: u32,
+    column: u32,
+    alive: bool,
+}
+
+#[derive(Serialize)]
+struct PlacedCells {
+    cells: Vec<PlacedCell>,
+}
+
+#[derive(Serialize)]
+struct NewPlayer<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct Size {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    /*
+    The server answered with an error status, message is the error it sent.
+    retry_after holds the seconds of its Retry-After header, sent along with 429.
+    */
+    Api {
+        status: u16,
+        message: String,
+        retry_after: Option<u64>,
+    },
+    // No answer, e.g. the server is not running, or one that could not be read
+    Transport(String),
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Transport(_) => None,
+        }
+    }
+
+    // How long the server asked to wait before trying again, if it did
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ClientError::Api { retry_after, .. } => retry_after.map(Duration::from_secs),
+            ClientError::Transport(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Api {
+                status, message, ..
+            } => write!(f, "{}: {}", status, message),
+            ClientError::Transport(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<ureq::Error> for ClientError {
+    fn from(error: ureq::Error) -> Self {
+        match error {
+            ureq::Error::Status(status, response) => {
+                let retry_after = response
+                    .header("Retry-After")
+                    .and_then(|seconds| seconds.trim().parse().ok());
+                let message = match response.into_json::<ErrorBody>() {
+                    Ok(body) => body.error,
+                    Err(_) => String::from("no error message"),
+                };
+                ClientError::Api {
+                    status,
+                    message,
+                    retry_after,
+                }
+            }
+            ureq::Error::Transport(transport) => ClientError::Transport(transport.to_string()),
+        }
+    }
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+pub struct Client {
+    // Address of the server without the API path, e.g. http://localhost:8000
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    /*
+    gol_http closes every connection after its answer, so the default agent opens a new one
+    per request instead of reusing one the server already dropped
+    */
+    pub fn new(base_url: &str) -> Self {
+        let agent = ureq::AgentBuilder::new().max_idle_connections(0).build();
+        Self::with_agent(base_url, agent)
+    }
+
+    // For an agent of its own, e.g. with timeouts or a proxy
+    pub fn with_agent(base_url: &str, agent: ureq::Agent) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            agent,
+        }
+    }
+
+    pub fn create_game(&self, options: &NewGame) -> ClientResult<GameResource> {
+        let response = self.request("POST", "/games").send_json(options)?;
+        read(response)
+    }
+
+    // Same as create_game for options with colours set
+    pub fn create_colour_game(&self, options: &NewGame) -> ClientResult<ColourGameResource> {
+        let response = self.request("POST", "/games").send_json(options)?;
+        read(response)
+    }
+
+    // The games this client created, the server lists no others
+    pub fn list_games(&self) -> ClientResult<Vec<GameInfo>> {
+        read(self.request("GET", "/games").call()?)
+    }
+
+    pub fn game(&self, id: &str) -> ClientResult<GameResource> {
+        read(self.request("GET", &game_path(id, "")).call()?)
+    }
+
+    pub fn delete_game(&self, id: &str) -> ClientResult<()> {
+        self.request("DELETE", &game_path(id, "")).call()?;
+        Ok(())
+    }
+
+    // Runs steps generations, the server takes between 1 and 10000 at once
+    pub fn tick(&self, id: &str, steps: u32) -> ClientResult<GameResource> {
+        let response = self
+            .request("POST", &game_path(id, "/tick"))
+            .query("steps", &steps.to_string())
+            .call()?;
+        read(response)
+    }
+
+    // Sets all cells or, if one of them is outside the board, none of them
+    pub fn set_cells(&self, id: &str, cells: &[CellChange]) -> ClientResult<GameResource> {
+        let response = self
+            .request("PATCH", &game_path(id, "/cells"))
+            .send_json(CellChanges { cells })?;
+        read(response)
+    }
+
+    // A new randomized board of the given size
+    pub fn resize(&self, id: &str, width: u32, height: u32) -> ClientResult<GameResource> {
+        let response = self
+            .request("PUT", &game_path(id, "/size"))
+            .send_json(Size { width, height })?;
+        read(response)
+    }
+
+    pub fn randomize(&self, id: &str) -> ClientResult<GameResource> {
+        read(self.request("POST", &game_path(id, "/randomize")).call()?)
+    }
+
+    pub fn clear(&self, id: &str) -> ClientResult<GameResource> {
+        read(self.request("POST", &game_path(id, "/clear")).call()?)
+    }
+
+    /*
+    The methods answering with a GameResource only work for plain Life,
+    a game created with colours is read with this one
+    */
+    pub fn colour_game(&self, id: &str) -> ClientResult<ColourGameResource> {
+        read(self.request("GET", &game_path(id, "")).call()?)
+    }
+
+    // Joins a colour game, the player gets the lowest colour nobody plays in
+    pub fn join_game(&self, id: &str, name: &str) -> ClientResult<PlayerInfo> {
+        let response = self
+            .request("POST", &game_path(id, "/players"))
+            .send_json(NewPlayer { name })?;
+        read(response)
+    }
+
+    pub fn players(&self, id: &str) -> ClientResult<Vec<PlayerInfo>> {
+        read(self.request("GET", &game_path(id, "/players")).call()?)
+    }
+
+    pub fn leave_game(&self, id: &str, player: &str) -> ClientResult<()> {
+        let path = game_path(id, &format!("/players/{}", player));
+        self.request("DELETE", &path).call()?;
+        Ok(())
+    }
+
+    /*
+    Brings the cells given as (row, column, alive) to life in the player's colour or kills
+    them, fails with 409 if one of them belongs to another player
+    */
+    pub fn place_cells(
+        &self,
+        id: &str,
+        player: &str,
+        cells: &[(u32, u32, bool)],
+    ) -> ClientResult<ColourGameResource> {
+        let cells = cells
+            .iter()
+            .map(|&(row, column, alive)| PlacedCell { row, column, alive })
+            .collect();
+        let path = game_path(id, &format!("/players/{}/cells", player));
+        let response = self
+            .request("PATCH", &path)
+            .send_json(PlacedCells { cells })?;
+        read(response)
+    }
+
+    // The OpenAPI document of the server
+    pub fn openapi(&self) -> ClientResult<serde_json::Value> {
+        read(self.request("GET", "/openapi.json").call()?)
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}{}{}", self.base_url, API_PATH, path);
+        self.agent.request(method, &url)
+    }
+}
+
+fn game_path(id: &str, rest: &str) -> String {
+    format!("/games/{}{}", id, rest)
+}
+
+fn read<T: DeserializeOwned>(response: ureq::Response) -> ClientResult<T> {
+    response
+        .into_json()
+        .map_err(|error| ClientError::Transport(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use gol::colours::Variant;
+    use gol::CellularAutomaton;
+
+    use super::*;
+
+    /*
+    A server answering a single request with the given status line, headers and body,
+    gives back the address to reach it and the request line it received
+    */
+    fn serve_once(head: &str, body: String) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let response = format!("{}\r\nContent-Length: {}\r\n\r\n{}", head, body.len(), body);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // The whole request is read, so closing the connection loses no part of the answer
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request_line.trim_end().to_owned()
+        });
+        (address, server)
+    }
+
+    // A board as gol_http sends it, the fields of the engine next to the id
+    fn served(id: &str, board: String) -> String {
+        let mut value: serde_json::Value = serde_json::from_str(&board).unwrap();
+        value["id"] = id.into();
+        value.to_string()
+    }
+
+    #[test]
+    fn a_game_is_read_from_the_answer() {
+        let mut universe = Universe::new(6, 4);
+        universe.set_alive(1, 2, true);
+        let body = served("abc", CellularAutomaton::serialize(&universe));
+        let (address, server) = serve_once("HTTP/1.1 201 Created", body);
+        let game = Client::new(&address)
+            .create_game(&NewGame::default())
+            .unwrap();
+        assert_eq!(server.join().unwrap(), "POST /api/v1/games HTTP/1.1");
+        assert_eq!(game.id, "abc");
+        assert_eq!(game.universe.width(), 6);
+        assert!(game.universe.is_alive(1, 2));
+        assert_eq!(game.universe.population(), 1);
+    }
+
+    #[test]
+    fn steps_go_into_the_query() {
+        let body = served("abc", CellularAutomaton::serialize(&Universe::new(3, 3)));
+        let (address, server) = serve_once("HTTP/1.1 200 OK", body);
+        Client::new(&address).tick("abc", 5).unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            "POST /api/v1/games/abc/tick?steps=5 HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn errors_keep_status_message_and_retry_after() {
+        let body = r#"{"status": 429, "error": "too many requests, slow down"}"#.to_owned();
+        let (address, _) = serve_once("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 3", body);
+        let error = Client::new(&address).list_games().unwrap_err();
+        assert_eq!(error.status(), Some(429));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(error.to_string(), "429: too many requests, slow down");
+
+        let body = r#"{"status": 404, "error": "no game with this id"}"#.to_owned();
+        let (address, _) = serve_once("HTTP/1.1 404 Not Found", body);
+        let error = Client::new(&address).game("gone").unwrap_err();
+        assert_eq!(error.status(), Some(404));
+        assert_eq!(error.retry_after(), None);
+    }
+
+    #[test]
+    fn a_colour_game_is_no_game_resource() {
+        let board = ColourLife::new(Variant::QuadLife, 4, 4);
+        let body = served("abc", CellularAutomaton::serialize(&board));
+        let (address, _) = serve_once("HTTP/1.1 200 OK", body.clone());
+        let error = Client::new(&address).game("abc").unwrap_err();
+        assert!(matches!(error, ClientError::Transport(_)));
+        let (address, _) = serve_once("HTTP/1.1 200 OK", body);
+        let game = Client::new(&address).colour_game("abc").unwrap();
+        assert_eq!(game.board.width(), 4);
+    }
+
+    #[test]
+    fn no_server_is_a_transport_error() {
+        // Nobody listens on a port that was just let go
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let error = Client::new(&format!("http://{}", address))
+            .list_games()
+            .unwrap_err();
+        assert!(matches!(error, ClientError::Transport(_)));
+        assert_eq!(error.status(), None);
+    }
+}
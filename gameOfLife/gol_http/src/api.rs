@@ -0,0 +1,837 @@
+use rocket::http::{Header, Status};
+use rocket::request::Request;
+use rocket::response::status::Created;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::tokio::task;
+use rocket::{catch, catchers, delete, get, patch, post, put, routes, Catcher, Route, State};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::info;
+use utoipa::{OpenApi, ToSchema};
+
+use gol::colours::{ColourLife, Variant};
+use gol::limits::{LimitError, Limits};
+use gol::Game;
+
+use crate::metrics;
+use crate::players::{PlayerError, PlayerInfo, Roster};
+use crate::sessions::{SessionError, SessionGame, SessionInfo, Sessions};
+use crate::throttle::{self, Client, Throttle};
+
+pub const BASE: &str = "/api/v1";
+
+/*
+A game as the API shows it: its id next to everything its engine serializes,
+for Life the fields of gol::Universe
+*/
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct GameResource {
+    pub id: String,
+    #[serde(flatten)]
+    #[schema(value_type = Board)]
+    pub board: Map<String, Value>,
+}
+
+/*
+How the document describes a board: the fields of a serialized gol::Universe, every engine
+sends at least width, height, generations, live_cells and array. Games in colours send the
+colour of each cell in array, 0 for dead ones, along with variant and populations.
+Never built, the server passes on what the engine serialized.
+*/
+#[derive(ToSchema, Debug, Clone)]
+pub struct Board {
+    pub width: u32,
+    pub height: u32,
+    pub generations: u32,
+    pub live_cells: u32,
+    // Row by row, width cells each
+    pub array: Vec<bool>,
+    pub ages: Vec<u32>,
+    pub activity: Vec<u32>,
+    #[schema(example = "B3/S23")]
+    pub rule: String,
+    // torus or bounded
+    #[schema(example = "torus")]
+    pub topology: String,
+}
+
+// Body of every error the API answers with
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: Status,
+    pub message: String,
+    // Sent as Retry-After, for 429
+    pub retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: Status, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(Status::BadRequest, message)
+    }
+}
+
+impl From<SessionError> for ApiError {
+    fn from(error: SessionError) -> Self {
+        let message = match error {
+            SessionError::UnknownSession => "no game with this id, it may have expired",
+            SessionError::TooManySessions => "too many games are running, try again later",
+            SessionError::TooManyClientSessions => {
+                "you are running too many games, delete one first"
+            }
+            SessionError::Throttled => "too many requests, slow down",
+            SessionError::TooManyCells => "the boards of all games together would be too large",
+        };
+        ApiError::new(error.status(), message)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = ErrorBody {
+            status: self.status.code,
+            error: self.message,
+        };
+        let mut response = (self.status, Json(body)).respond_to(request)?;
+        if let Some(seconds) = self.retry_after {
+            response.set_header(Header::new("Retry-After", seconds.to_string()));
+        }
+        Ok(response)
+    }
+}
+
+impl From<LimitError> for ApiError {
+    fn from(error: LimitError) -> Self {
+        let status = match error {
+            LimitError::Invalid(_) => Status::BadRequest,
+            LimitError::TooLarge(_) => Status::PayloadTooLarge,
+        };
+        ApiError::new(status, error.to_string())
+    }
+}
+
+impl From<PlayerError> for ApiError {
+    fn from(error: PlayerError) -> Self {
+        ApiError::new(error.status(), error.message())
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+// Options of POST /games, all of them may be left out
+#[derive(Deserialize, ToSchema, Debug, Clone, Default)]
+pub struct NewGame {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // New games start randomized unless this is false
+    pub randomize: Option<bool>,
+    // Players in colours, 2 for Immigration or 4 for QuadLife, plain Life if left out
+    pub colours: Option<u8>,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Clone, Copy)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Clone, Copy)]
+pub struct CellChange {
+    pub row: u32,
+    pub column: u32,
+    pub state: u8,
+}
+
+// Body of PATCH /games/<id>/cells
+#[derive(Deserialize, ToSchema, Debug, Clone)]
+pub struct CellChanges {
+    pub cells: Vec<CellChange>,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Clone)]
+pub struct NewPlayer {
+    pub name: String,
+}
+
+// A cell a player brings to life in their colour or kills
+#[derive(Deserialize, ToSchema, Debug, Clone, Copy)]
+pub struct PlacedCell {
+    pub row: u32,
+    pub column: u32,
+    pub alive: bool,
+}
+
+// Body of PATCH /games/<id>/players/<player>/cells
+#[derive(Deserialize, ToSchema, Debug, Clone)]
+pub struct PlacedCells {
+    pub cells: Vec<PlacedCell>,
+}
+
+fn session(sessions: &Sessions, id: &str) -> ApiResult<SessionGame> {
+    sessions.get(id).ok_or(SessionError::UnknownSession.into())
+}
+
+fn resource(id: &str, game: &mut Game) -> GameResource {
+    let board = match serde_json::from_str(&game.serialize()) {
+        Ok(Value::Object(board)) => board,
+        _ => Map::new(),
+    };
+    GameResource {
+        id: id.to_owned(),
+        board,
+    }
+}
+
+/*
+Runs a change on the game of a session, hands the new board to everyone watching it
+and answers with the game
+*/
+fn update(
+    session: &SessionGame,
+    change: impl FnOnce(&mut Game) -> ApiResult<()>,
+) -> ApiResult<Json<GameResource>> {
+    let (board, resource) = {
+        let mut game = session.game.lock().unwrap();
+        change(&mut game)?;
+        (game.serialize(), resource(&session.id, &mut game))
+    };
+    session.player.publish(board);
+    Ok(Json(resource))
+}
+
+pub fn check_cell(game: &Game, row: u32, column: u32, state: u8) -> ApiResult<()> {
+    let automaton = game.automaton();
+    if row >= automaton.height() || column >= automaton.width() {
+        return Err(ApiError::bad_request(format!(
+            "cell ({}, {}) is outside the {}x{} board",
+            row,
+            column,
+            automaton.width(),
+            automaton.height()
+        )));
+    }
+    if state >= automaton.states() {
+        return Err(ApiError::bad_request(format!(
+            "state {} is not one of the {} states of the engine",
+            state,
+            automaton.states()
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    request_body(content = Option<NewGame>, description = "Size of the board and whether it starts randomized"),
+    responses(
+        (status = 201, description = "The new game", body = GameResource,
+            headers(("location" = String, description = "Path of the new game"))),
+        (status = 400, description = "Invalid size", body = ErrorBody),
+        (status = 413, description = "All boards together would be too large", body = ErrorBody),
+        (status = 429, description = "Too many requests or games of the client", body = ErrorBody),
+        (status = 503, description = "Too many games", body = ErrorBody)
+    )
+)]
+#[post("/games", data = "<options>")]
+fn create_game(
+    _throttle: Throttle,
+    client: Client,
+    limits: &State<Limits>,
+    sessions: &State<Sessions>,
+    options: Option<Json<NewGame>>,
+) -> ApiResult<Created<Json<GameResource>>> {
+    let options = options.map(Json::into_inner).unwrap_or_default();
+    let defaults = sessions.board();
+    let (width, height) = (
+        options.width.unwrap_or(defaults.width),
+        options.height.unwrap_or(defaults.height),
+    );
+    limits.check_size(width, height)?;
+    let variant = match options.colours {
+        Some(colours) => Some(Variant::from_colours(colours).map_err(ApiError::bad_request)?),
+        None => None,
+    };
+    let session = sessions.create(client.0)?;
+    if let Err(error) = sessions.reserve_cells(&session.id, width, height) {
+        sessions.delete(&session.id);
+        return Err(error.into());
+    }
+    let resource = {
+        let mut game = session.game.lock().unwrap();
+        if let Some(variant) = variant {
+            let mut board = ColourLife::new(variant, width, height);
+            board.set_rule(defaults.rule);
+            *game = Game::with_automaton(Box::new(board));
+            *session.roster.lock().unwrap() = Roster::new(variant.colours());
+        }
+        game.change_size(width, height);
+        if options.randomize == Some(false) {
+            game.negate_everything();
+        }
+        resource(&session.id, &mut game)
+    };
+    let location = format!("{}/games/{}", BASE, session.id);
+    Ok(Created::new(location).body(Json(resource)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    responses((status = 200, description = "The games of the client, recently used first", body = [SessionInfo]))
+)]
+#[get("/games")]
+fn list_games(
+    _throttle: Throttle,
+    client: Client,
+    sessions: &State<Sessions>,
+) -> Json<Vec<SessionInfo>> {
+    Json(sessions.list(client.0))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    responses(
+        (status = 200, description = "The game", body = GameResource),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+#[get("/games/<id>")]
+fn get_game(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+) -> ApiResult<Json<GameResource>> {
+    let session = session(sessions, id)?;
+    let mut game = session.game.lock().unwrap();
+    Ok(Json(resource(id, &mut game)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    responses(
+        (status = 204, description = "The game is gone"),
+        (status = 404, description = "Unknown or expired game, or one of another client", body = ErrorBody)
+    )
+)]
+#[delete("/games/<id>")]
+fn delete_game(
+    _throttle: Throttle,
+    client: Client,
+    sessions: &State<Sessions>,
+    id: &str,
+) -> ApiResult<Status> {
+    sessions.delete_owned(id, client.0)?;
+    Ok(Status::NoContent)
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    params(("steps" = Option<u32>, Query, description = "Generations to run, 1 by default, at most max_steps of the limits")),
+    responses(
+        (status = 200, description = "The game after the last generation", body = GameResource),
+        (status = 400, description = "Steps out of range", body = ErrorBody),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+// Many generations of a large board take a while, they are run on the blocking threads
+#[post("/games/<id>/tick?<steps>")]
+async fn tick(
+    _throttle: Throttle,
+    limits: &State<Limits>,
+    sessions: &State<Sessions>,
+    id: &str,
+    steps: Option<u32>,
+) -> ApiResult<Json<GameResource>> {
+    let steps = steps.unwrap_or(1);
+    limits.check_steps(steps)?;
+    let session = session(sessions, id)?;
+    task::spawn_blocking(move || {
+        update(&session, |game| {
+            for _ in 0..steps {
+                metrics::tick(game);
+            }
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|_| ApiError::new(Status::InternalServerError, "the generations failed"))?
+}
+
+// Sets cells to the given states, nothing changes if one of them is invalid
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    request_body = CellChanges,
+    responses(
+        (status = 200, description = "The game with the cells set", body = GameResource),
+        (status = 400, description = "A cell outside the board or an invalid state", body = ErrorBody),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+#[patch("/games/<id>/cells", data = "<changes>")]
+fn set_cells(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+    changes: Json<CellChanges>,
+) -> ApiResult<Json<GameResource>> {
+    update(&session(sessions, id)?, |game| {
+        for change in &changes.cells {
+            check_cell(game, change.row, change.column, change.state)?;
+        }
+        for change in &changes.cells {
+            game.automaton_mut()
+                .set_cell(change.row, change.column, change.state);
+        }
+        Ok(())
+    })
+}
+
+// A new board of the given size, randomized like the old NewBoard route did
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    request_body = Size,
+    responses(
+        (status = 200, description = "The game with a new randomized board", body = GameResource),
+        (status = 400, description = "Invalid size", body = ErrorBody),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody),
+        (status = 413, description = "All boards together would be too large", body = ErrorBody)
+    )
+)]
+#[put("/games/<id>/size", data = "<size>")]
+fn resize(
+    _throttle: Throttle,
+    limits: &State<Limits>,
+    sessions: &State<Sessions>,
+    id: &str,
+    size: Json<Size>,
+) -> ApiResult<Json<GameResource>> {
+    let session = session(sessions, id)?;
+    // Some engines round the size up, the board they end up with is the one checked and booked
+    let (width, height) = session
+        .game
+        .lock()
+        .unwrap()
+        .automaton()
+        .fitted_size(size.width, size.height);
+    limits.check_size(width, height)?;
+    sessions.reserve_cells(id, width, height)?;
+    update(&session, |game| {
+        game.change_size(size.width, size.height);
+        Ok(())
+    })
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    responses(
+        (status = 200, description = "The game with a randomized board", body = GameResource),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+#[post("/games/<id>/randomize")]
+fn randomize(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+) -> ApiResult<Json<GameResource>> {
+    update(&session(sessions, id)?, |game| {
+        game.randomize();
+        Ok(())
+    })
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "games",
+    responses(
+        (status = 200, description = "The game with every cell dead", body = GameResource),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+#[post("/games/<id>/clear")]
+fn clear(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+) -> ApiResult<Json<GameResource>> {
+    update(&session(sessions, id)?, |game| {
+        game.negate_everything();
+        Ok(())
+    })
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "players",
+    request_body = NewPlayer,
+    responses(
+        (status = 201, description = "The new player with the colour they play in", body = PlayerInfo,
+            headers(("location" = String, description = "Path of the new player"))),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody),
+        (status = 409, description = "The game has no colours or every colour has a player", body = ErrorBody)
+    )
+)]
+#[post("/games/<id>/players", data = "<player>")]
+fn join_game(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+    player: Json<NewPlayer>,
+) -> ApiResult<Created<Json<PlayerInfo>>> {
+    let session = session(sessions, id)?;
+    let game = session.game.lock().unwrap();
+    let mut roster = session.roster.lock().unwrap();
+    let (player, colour) = roster.join(player.name.trim())?;
+    info!(session = id, player = %player, colour, "player joined");
+    let info = roster
+        .scores(game.automaton())
+        .into_iter()
+        .find(|info| info.id == player)
+        .ok_or(PlayerError::UnknownPlayer)?;
+    let location = format!("{}/games/{}/players/{}", BASE, id, player);
+    Ok(Created::new(location).body(Json(info)))
+}
+
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "players",
+    responses(
+        (status = 200, description = "Every player of the game with their score", body = [PlayerInfo]),
+        (status = 404, description = "Unknown or expired game", body = ErrorBody)
+    )
+)]
+#[get("/games/<id>/players")]
+fn list_players(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+) -> ApiResult<Json<Vec<PlayerInfo>>> {
+    let session = session(sessions, id)?;
+    let game = session.game.lock().unwrap();
+    let players = session.roster.lock().unwrap().scores(game.automaton());
+    Ok(Json(players))
+}
+
+// The colour is free for the next player, the cells of the player stay on the board
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "players",
+    responses(
+        (status = 204, description = "The player left the game"),
+        (status = 404, description = "Unknown game or player", body = ErrorBody)
+    )
+)]
+#[delete("/games/<id>/players/<player>")]
+fn leave_game(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+    player: &str,
+) -> ApiResult<Status> {
+    session(sessions, id)?
+        .roster
+        .lock()
+        .unwrap()
+        .leave(player)?;
+    info!(session = id, player, "player left");
+    Ok(Status::NoContent)
+}
+
+// Like PATCH /games/<id>/cells in the colour of the player, who may only touch dead cells and their own
+#[utoipa::path(
+    context_path = "/api/v1",
+    tag = "players",
+    request_body = PlacedCells,
+    responses(
+        (status = 200, description = "The game with the cells placed", body = GameResource),
+        (status = 400, description = "A cell outside the board", body = ErrorBody),
+        (status = 404, description = "Unknown game or player", body = ErrorBody),
+        (status = 409, description = "A cell belongs to another player", body = ErrorBody)
+    )
+)]
+#[patch("/games/<id>/players/<player>/cells", data = "<changes>")]
+fn place_cells(
+    _throttle: Throttle,
+    sessions: &State<Sessions>,
+    id: &str,
+    player: &str,
+    changes: Json<PlacedCells>,
+) -> ApiResult<Json<GameResource>> {
+    let session = session(sessions, id)?;
+    update(&session, |game| {
+        for change in &changes.cells {
+            check_cell(game, change.row, change.column, 0)?;
+        }
+        let cells: Vec<(u32, u32, bool)> = changes
+            .cells
+            .iter()
+            .map(|change| (change.row, change.column, change.alive))
+            .collect();
+        session
+            .roster
+            .lock()
+            .unwrap()
+            .place(player, game.automaton_mut(), &cells)?;
+        Ok(())
+    })
+}
+
+/*
+OpenAPI document of the routes above, generated from their definitions and served at
+/api/v1/openapi.json. A route added to routes() belongs in paths here too.
+*/
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Game of Life", description = "Games running on the server, one per session. A client making too many requests gets 429 with Retry-After."),
+    paths(
+        create_game, list_games, get_game, delete_game, tick, set_cells, resize, randomize, clear,
+        join_game, list_players, leave_game, place_cells
+    ),
+    components(schemas(
+        GameResource, Board, ErrorBody, NewGame, Size, CellChange, CellChanges, SessionInfo,
+        NewPlayer, PlacedCell, PlacedCells, PlayerInfo
+    )),
+    tags(
+        (name = "games", description = "Create, step, edit and delete games"),
+        (name = "players", description = "Players placing cells in their own colour")
+    )
+)]
+pub struct ApiDoc;
+
+#[get("/openapi.json")]
+fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+// Errors Rocket raises itself under the API, e.g. unknown routes or unreadable JSON, as JSON too
+#[catch(default)]
+fn default_catcher(status: Status, request: &Request) -> ApiError {
+    let mut error = ApiError::new(status, status.reason().unwrap_or("error"));
+    error.retry_after = throttle::retry_after(request);
+    error
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        create_game,
+        list_games,
+        get_game,
+        delete_game,
+        tick,
+        set_cells,
+        resize,
+        randomize,
+        clear,
+        join_game,
+        list_players,
+        leave_game,
+        place_cells,
+        openapi
+    ]
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![default_catcher]
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::ContentType;
+    use rocket::local::blocking::{Client as TestClient, LocalResponse};
+
+    use super::*;
+    use crate::sessions::{BoardSettings, SessionLimits};
+
+    fn client() -> TestClient {
+        let rocket = rocket::build()
+            .manage(Sessions::new(
+                SessionLimits::default(),
+                BoardSettings::default(),
+            ))
+            .manage(Limits::default())
+            .mount(BASE, routes())
+            .register(BASE, catchers());
+        TestClient::tracked(rocket).unwrap()
+    }
+
+    fn json(response: LocalResponse) -> Value {
+        serde_json::from_str(&response.into_string().unwrap()).unwrap()
+    }
+
+    // Creates an empty game and returns its path
+    fn create(client: &TestClient, body: &str) -> String {
+        let response = client
+            .post("/api/v1/games")
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        response.headers().get_one("Location").unwrap().to_owned()
+    }
+
+    #[test]
+    fn create_get_list_and_delete() {
+        let client = client();
+        let path = create(&client, r#"{"width": 12, "height": 8, "randomize": false}"#);
+        let game = json(client.get(&path).dispatch());
+        assert_eq!(game["width"], 12);
+        assert_eq!(game["height"], 8);
+        assert_eq!(game["live_cells"], 0);
+        assert_eq!(
+            path,
+            format!("/api/v1/games/{}", game["id"].as_str().unwrap())
+        );
+
+        let games = json(client.get("/api/v1/games").dispatch());
+        assert_eq!(games.as_array().unwrap().len(), 1);
+
+        assert_eq!(client.delete(&path).dispatch().status(), Status::NoContent);
+        let response = client.get(&path).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(json(response)["status"], 404);
+    }
+
+    #[test]
+    fn other_clients_neither_see_nor_delete_a_game() {
+        let client = client();
+        let path = create(&client, r#"{"randomize": false}"#);
+        let other = "192.0.2.1:4000".parse().unwrap();
+        let games = json(client.get("/api/v1/games").remote(other).dispatch());
+        assert_eq!(games.as_array().unwrap().len(), 0);
+        let response = client.delete(&path).remote(other).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(client.get(&path).dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn a_blinker_ticks() {
+        let client = client();
+        let path = create(&client, r#"{"width": 5, "height": 5, "randomize": false}"#);
+        let cells = r#"{"cells": [
+            {"row": 2, "column": 1, "state": 1},
+            {"row": 2, "column": 2, "state": 1},
+            {"row": 2, "column": 3, "state": 1}
+        ]}"#;
+        let response = client
+            .patch(format!("{}/cells", path))
+            .header(ContentType::JSON)
+            .body(cells)
+            .dispatch();
+        assert_eq!(json(response)["live_cells"], 3);
+
+        let game = json(client.post(format!("{}/tick?steps=3", path)).dispatch());
+        assert_eq!(game["generations"], 3);
+        assert_eq!(game["live_cells"], 3);
+        // Vertical after an odd number of generations
+        let array = game["array"].as_array().unwrap();
+        assert_eq!(array[7], true);
+        assert_eq!(array[11], false);
+    }
+
+    #[test]
+    fn invalid_requests_are_json_errors() {
+        let client = client();
+        let path = create(&client, r#"{"width": 5, "height": 5}"#);
+        let response = client.post(format!("{}/tick?steps=0", path)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let too_many = format!("{}/tick?steps={}", path, Limits::default().max_steps + 1);
+        assert_eq!(
+            client.post(too_many).dispatch().status(),
+            Status::BadRequest
+        );
+
+        let outside = r#"{"cells": [{"row": 5, "column": 0, "state": 1}]}"#;
+        let response = client
+            .patch(format!("{}/cells", path))
+            .header(ContentType::JSON)
+            .body(outside)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert!(json(response)["error"]
+            .as_str()
+            .unwrap()
+            .contains("outside"));
+
+        let response = client
+            .post("/api/v1/games")
+            .header(ContentType::JSON)
+            .body(r#"{"width": 0}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client.get("/api/v1/nothing").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(json(response)["status"], 404);
+    }
+
+    #[test]
+    fn resize_randomize_and_clear() {
+        let client = client();
+        let path = create(&client, r#"{"randomize": false}"#);
+        let response = client
+            .put(format!("{}/size", path))
+            .header(ContentType::JSON)
+            .body(r#"{"width": 30, "height": 20}"#)
+            .dispatch();
+        let game = json(response);
+        assert_eq!(
+            (game["width"].clone(), game["height"].clone()),
+            (30.into(), 20.into())
+        );
+        let game = json(client.post(format!("{}/randomize", path)).dispatch());
+        assert!(game["live_cells"].as_u64().unwrap() > 0);
+        let game = json(client.post(format!("{}/clear", path)).dispatch());
+        assert_eq!(game["live_cells"], 0);
+    }
+
+    #[test]
+    fn players_join_games_in_colours() {
+        let client = client();
+        let plain = create(&client, "{}");
+        let join = |path: &str| {
+            client
+                .post(format!("{}/players", path))
+                .header(ContentType::JSON)
+                .body(r#"{"name": "ada"}"#)
+                .dispatch()
+        };
+        assert_eq!(join(&plain).status(), Status::Conflict);
+
+        let colours = create(&client, r#"{"colours": 2, "randomize": false}"#);
+        let player = json(join(&colours));
+        assert_eq!(player["name"], "ada");
+        let players = json(client.get(format!("{}/players", colours)).dispatch());
+        assert_eq!(players.as_array().unwrap().len(), 1);
+        let leave = format!("{}/players/{}", colours, player["id"].as_str().unwrap());
+        assert_eq!(client.delete(leave).dispatch().status(), Status::NoContent);
+    }
+
+    #[test]
+    fn the_document_lists_every_route() {
+        let document = json(client().get("/api/v1/openapi.json").dispatch());
+        let paths = document["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/api/v1/games"));
+        assert!(paths.contains_key("/api/v1/games/{id}/tick"));
+    }
+}
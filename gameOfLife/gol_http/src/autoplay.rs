@@ -0,0 +1,431 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::tokio::sync::broadcast;
+use rocket::tokio::{self, select};
+use rocket_ws::{Channel, Message, WebSocket};
+use serde::{Deserialize, Serialize};
+
+use gol::Game;
+
+use crate::metrics::{self, METRICS};
+use crate::sessions::SessionGame;
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(750);
+pub const MIN_INTERVAL: Duration = Duration::from_millis(10);
+pub const MAX_INTERVAL: Duration = Duration::from_secs(10);
+// Updates a slow client may fall behind before it skips ahead to the newest one
+const BACKLOG: usize = 16;
+// Boards kept for clients coming back, see Player::subscribe_since
+const HISTORY: usize = 64;
+
+/*
+Runs the game of a session on the server and pushes every generation to the clients
+watching it, so the speed no longer depends on how often a client asks for the next one.
+*/
+pub struct Player {
+    state: Mutex<PlayerState>,
+    updates: broadcast::Sender<Update>,
+}
+
+struct PlayerState {
+    playing: bool,
+    interval: Duration,
+    // Whether a task ticking the game is alive, there is at most one per session
+    running: bool,
+    // Id of the last board published, counting up from 1 over the life of the session
+    sequence: u64,
+    history: VecDeque<(u64, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Update {
+    // A serialized board and its id
+    Board(u64, String),
+    Status(PlayerStatus),
+}
+
+// Sent to the clients whenever playing or the speed changes
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct PlayerStatus {
+    pub playing: bool,
+    pub interval_ms: u64,
+}
+
+/*
+Commands a client sends over the WebSocket as JSON, e.g. {"command": "play"}
+or {"command": "speed", "interval_ms": 200}
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    Play,
+    Pause,
+    Speed { interval_ms: u64 },
+    // A single generation, also pauses
+    Step,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(PlayerState {
+                playing: false,
+                interval: DEFAULT_INTERVAL,
+                running: false,
+                sequence: 0,
+                history: VecDeque::new(),
+            }),
+            updates: broadcast::channel(BACKLOG).0,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.lock().unwrap().playing
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.updates.receiver_count() > 0
+    }
+
+    pub fn status(&self) -> PlayerStatus {
+        let state = self.state.lock().unwrap();
+        PlayerStatus {
+            playing: state.playing,
+            interval_ms: state.interval.as_millis() as u64,
+        }
+    }
+
+    /*
+    Subscribes and returns the boards published after the one with id last_id that are still
+    in the history. A new client, or one that missed nothing the history still holds, gets
+    the current board of the game with the id of the newest one instead. All of it happens
+    under the lock of the player, nothing can be published in between, so a client sees
+    every board once.
+    */
+    pub fn subscribe_since(
+        &self,
+        last_id: Option<u64>,
+        game: &Mutex<Game>,
+    ) -> (Vec<(u64, String)>, broadcast::Receiver<Update>) {
+        let state = self.state.lock().unwrap();
+        let mut backlog: Vec<(u64, String)> = state
+            .history
+            .iter()
+            .filter(|(id, _)| last_id.is_some_and(|last_id| *id > last_id))
+            .cloned()
+            .collect();
+        if backlog.is_empty() && last_id != Some(state.sequence) {
+            backlog.push((state.sequence, game.lock().unwrap().serialize()));
+        }
+        (backlog, self.updates.subscribe())
+    }
+
+    // Hands a board to every client watching, e.g. after a change over plain HTTP
+    pub fn publish(&self, board: String) {
+        METRICS.observe_broadcast(board.len());
+        let mut state = self.state.lock().unwrap();
+        state.sequence += 1;
+        let id = state.sequence;
+        state.history.push_back((id, board.clone()));
+        if state.history.len() > HISTORY {
+            state.history.pop_front();
+        }
+        // Fails only when nobody is watching
+        let _ = self.updates.send(Update::Board(id, board));
+    }
+
+    /*
+    Starts ticking the game every interval, whether or not anybody is watching, until it is
+    paused. Sessions pause their player when they expire or are deleted. The generations are
+    computed on the blocking threads, so a large board does not hold up the other requests.
+    */
+    pub fn play(self: &Arc<Self>, game: Arc<Mutex<Game>>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.playing = true;
+            if state.running {
+                return;
+            }
+            state.running = true;
+        }
+        let player = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let interval = player.state.lock().unwrap().interval;
+                tokio::time::sleep(interval).await;
+                {
+                    let mut state = player.state.lock().unwrap();
+                    if !state.playing {
+                        state.running = false;
+                        break;
+                    }
+                }
+                let game = game.clone();
+                let board = tokio::task::spawn_blocking(move || {
+                    let mut game = game.lock().unwrap();
+                    metrics::tick(&mut game);
+                    game.serialize()
+                })
+                .await;
+                match board {
+                    Ok(board) => player.publish(board),
+                    // The tick panicked, the game cannot go on
+                    Err(_) => {
+                        let mut state = player.state.lock().unwrap();
+                        state.playing = false;
+                        state.running = false;
+                        break;
+                    }
+                }
+            }
+            player.publish_status();
+        });
+    }
+
+    pub fn pause(&self) {
+        self.state.lock().unwrap().playing = false;
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        self.state.lock().unwrap().interval = interval.clamp(MIN_INTERVAL, MAX_INTERVAL);
+    }
+
+    fn publish_status(&self) {
+        let _ = self.updates.send(Update::Status(self.status()));
+    }
+
+    // A step is computed on the blocking threads like the generations of play
+    pub async fn apply(self: &Arc<Self>, command: Command, game: &Arc<Mutex<Game>>) {
+        match command {
+            Command::Play => self.play(game.clone()),
+            Command::Pause => self.pause(),
+            Command::Speed { interval_ms } => self.set_interval(Duration::from_millis(interval_ms)),
+            Command::Step => {
+                self.pause();
+                let game = game.clone();
+                let board = tokio::task::spawn_blocking(move || {
+                    let mut game = game.lock().unwrap();
+                    metrics::tick(&mut game);
+                    game.serialize()
+                })
+                .await;
+                // A tick that panicked has nothing to show
+                if let Ok(board) = board {
+                    self.publish(board);
+                }
+            }
+        }
+        self.publish_status();
+    }
+}
+
+/*
+WebSocket of a session: sends the board and the player status right away, then every new
+generation, and takes play, pause, speed and step commands. Commands it cannot read are
+answered with {"error": ...} to that client only.
+*/
+pub fn watch(ws: WebSocket, session: SessionGame) -> Channel<'static> {
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            let SessionGame { game, player, .. } = session;
+            let (boards, mut updates) = player.subscribe_since(None, &game);
+            for (_, board) in boards {
+                stream.send(Message::Text(board)).await?;
+            }
+            let status = serde_json::to_string(&player.status()).unwrap();
+            stream.send(Message::Text(status)).await?;
+            loop {
+                select! {
+                    message = stream.next() => match message {
+                        Some(Ok(Message::Text(text))) => {
+                            match serde_json::from_str::<Command>(&text) {
+                                Ok(command) => player.apply(command, &game).await,
+                                Err(error) => {
+                                    let error = serde_json::json!({ "error": error.to_string() });
+                                    stream.send(Message::Text(error.to_string())).await?;
+                                }
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | None => break,
+                        Some(Ok(_)) => {}
+                        Some(Err(error)) => return Err(error),
+                    },
+                    update = updates.recv() => match update {
+                        Ok(Update::Board(_, board)) => stream.send(Message::Text(board)).await?,
+                        Ok(Update::Status(status)) => {
+                            let status = serde_json::to_string(&status).unwrap();
+                            stream.send(Message::Text(status)).await?
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+            Ok(())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+
+    use rocket::config::{Config, Shutdown};
+    use rocket::{get, routes};
+    use serde_json::Value;
+    use tokio_tungstenite::tungstenite;
+
+    use super::*;
+    use crate::sessions::{BoardSettings, SessionLimits, Sessions};
+
+    fn game() -> Arc<Mutex<Game>> {
+        Arc::new(Mutex::new(Game::new(Some(8), Some(8))))
+    }
+
+    fn generations(board: &str) -> u64 {
+        let board: Value = serde_json::from_str(board).unwrap();
+        board["generations"].as_u64().unwrap()
+    }
+
+    async fn next_board(updates: &mut broadcast::Receiver<Update>) -> (u64, String) {
+        loop {
+            if let Update::Board(id, board) = updates.recv().await.unwrap() {
+                return (id, board);
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn a_step_reaches_every_watcher_once_and_pauses() {
+        let player = Arc::new(Player::new());
+        let game = game();
+        let (_, mut first) = player.subscribe_since(None, &game);
+        let (_, mut second) = player.subscribe_since(None, &game);
+        player.apply(Command::Play, &game).await;
+        player.apply(Command::Step, &game).await;
+        assert!(!player.is_playing());
+        for updates in [&mut first, &mut second] {
+            let (id, board) = next_board(updates).await;
+            assert_eq!(id, 1);
+            assert_eq!(generations(&board), 1);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn playing_pushes_generations_until_paused() {
+        let player = Arc::new(Player::new());
+        let game = game();
+        let (_, mut updates) = player.subscribe_since(None, &game);
+        player.set_interval(MIN_INTERVAL);
+        player.play(game.clone());
+        let (_, board) = next_board(&mut updates).await;
+        assert_eq!(generations(&board), 1);
+        let (_, board) = next_board(&mut updates).await;
+        assert_eq!(generations(&board), 2);
+        player.pause();
+        // The task publishes its status once it stopped
+        loop {
+            if let Update::Status(status) = updates.recv().await.unwrap() {
+                if !status.playing {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_client_coming_back_gets_what_it_missed() {
+        let player = Player::new();
+        let game = game();
+        for board in ["a", "b", "c"] {
+            player.publish(board.to_owned());
+        }
+        let (backlog, _) = player.subscribe_since(Some(1), &game);
+        assert_eq!(backlog, [(2, String::from("b")), (3, String::from("c"))]);
+        // Nothing missed, nothing sent again
+        let (backlog, _) = player.subscribe_since(Some(3), &game);
+        assert!(backlog.is_empty());
+        // A new client gets the board as it is now, under the id of the newest one
+        let (backlog, _) = player.subscribe_since(None, &game);
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(backlog[0].0, 3);
+        assert_eq!(generations(&backlog[0].1), 0);
+    }
+
+    // The next message of a WebSocket, read as JSON
+    async fn message<S>(socket: &mut S) -> Value
+    where
+        S: StreamExt<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+    {
+        let text = socket.next().await.unwrap().unwrap().into_text().unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[get("/live")]
+    fn live(ws: WebSocket, session: SessionGame) -> Channel<'static> {
+        watch(ws, session)
+    }
+
+    #[rocket::async_test]
+    async fn the_websocket_sends_the_board_and_takes_commands() {
+        let sessions = Sessions::new(SessionLimits::default(), BoardSettings::default());
+        let id = sessions.create(Ipv4Addr::LOCALHOST.into()).unwrap().id;
+        // A port nobody uses right now
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = Config {
+            port,
+            address: Ipv4Addr::LOCALHOST.into(),
+            log_level: rocket::config::LogLevel::Off,
+            shutdown: Shutdown {
+                grace: 0,
+                mercy: 0,
+                ..Shutdown::default()
+            },
+            ..Config::debug_default()
+        };
+        let rocket = rocket::custom(config)
+            .manage(sessions)
+            .mount("/", routes![live])
+            .ignite()
+            .await
+            .unwrap();
+        let shutdown = rocket.shutdown();
+        let server = tokio::spawn(rocket.launch());
+
+        let url = format!("ws://127.0.0.1:{}/live?session={}", port, id);
+        let mut socket = loop {
+            match tokio_tungstenite::connect_async(&url).await {
+                Ok((socket, _)) => break socket,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let board = message(&mut socket).await;
+        assert_eq!(board["width"], 50);
+        assert_eq!(board["generations"], 0);
+        assert_eq!(message(&mut socket).await["playing"], false);
+
+        let step = tungstenite::Message::Text(String::from(r#"{"command": "step"}"#));
+        socket.send(step).await.unwrap();
+        assert_eq!(message(&mut socket).await["generations"], 1);
+
+        let nonsense = tungstenite::Message::Text(String::from(r#"{"command": "fly"}"#));
+        socket.send(nonsense).await.unwrap();
+        // The status of the step comes first
+        while message(&mut socket).await.get("error").is_none() {}
+        shutdown.notify();
+        server.await.unwrap().unwrap();
+    }
+}
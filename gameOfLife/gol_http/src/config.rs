@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::value::Value;
+use rocket::figment::Figment;
+use serde::{Deserialize, Serialize};
+
+use gol::limits::Limits;
+
+use crate::logging::LogSettings;
+use crate::sessions::{BoardSettings, SessionLimits};
+
+// Read from the working directory unless --config or GOL_CONFIG names another file
+pub const CONFIG_FILE: &str = "gol_http.toml";
+// GOL_PORT=9000, nested keys with a double underscore: GOL_BOARD__WIDTH=80
+pub const ENV_PREFIX: &str = "GOL_";
+
+pub const USAGE: &str = "Usage: gol_http [OPTIONS]
+
+Settings come from the defaults, then the config file, then GOL_ environment variables,
+then the flags, later sources win.
+
+Options:
+  --config <FILE>      Config file, gol_http.toml by default
+  --print-config       Print the effective configuration as TOML and exit
+  --help               Print this help and exit
+  --<KEY> <VALUE>      Any setting, e.g. --port 9000, --board.width 80, --log.filter debug
+  --width, --height, --rule, --topology
+                       Short for --board.width, --board.height, --board.rule, --board.topology
+  --log <FILTER>       Short for --log.filter, e.g. --log info,gol_http::metrics=trace
+
+Environment:
+  GOL_CONFIG=<FILE>    Config file
+  GOL_<KEY>=<VALUE>    Any setting, e.g. GOL_PORT=9000, GOL_BOARD__RULE=B36/S23
+";
+
+/*
+Everything gol_http can be set up with, see USAGE for where it comes from.
+The file uses the same names, e.g. port = 8000 and width = 50 in a [board] table.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub address: String,
+    pub port: u16,
+    // Seconds an idle connection stays open, 0 closes each one after its answer
+    pub keep_alive: u32,
+    // Directory index.html, javascript.js and mystyle.css are read from, the copies built into the server if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontend: Option<PathBuf>,
+    pub board: BoardSettings,
+    pub sessions: SessionLimits,
+    pub limits: Limits,
+    pub log: LogSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            address: String::from("0.0.0.0"),
+            port: 8000,
+            // To get compareable results in Wireshark
+            keep_alive: 0,
+            frontend: None,
+            board: BoardSettings::default(),
+            sessions: SessionLimits::default(),
+            limits: Limits::default(),
+            log: LogSettings::default(),
+        }
+    }
+}
+
+// What the command line asked for
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    pub config: Option<PathBuf>,
+    pub print_config: bool,
+    pub help: bool,
+    // Settings given as flags, as key path and value
+    pub settings: Vec<(String, String)>,
+}
+
+impl Arguments {
+    // Reads the arguments after the name of the program
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut arguments = Arguments::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let flag = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {}, see --help", arg))?;
+            let (key, inline_value) = match flag.split_once('=') {
+                Some((key, value)) => (key, Some(value.to_owned())),
+                None => (flag, None),
+            };
+            match key {
+                "help" => arguments.help = true,
+                "print-config" => arguments.print_config = true,
+                _ => {
+                    let value = match inline_value {
+                        Some(value) => value,
+                        None => args
+                            .next()
+                            .cloned()
+                            .ok_or_else(|| format!("--{} needs a value", key))?,
+                    };
+                    if key == "config" {
+                        arguments.config = Some(PathBuf::from(value));
+                    } else {
+                        arguments.settings.push((setting_key(key), value));
+                    }
+                }
+            }
+        }
+        Ok(arguments)
+    }
+}
+
+// Key path of a flag, dashes become underscores, the board settings and the log filter have short forms
+fn setting_key(flag: &str) -> String {
+    let key = flag.replace('-', "_");
+    match key.as_str() {
+        "width" | "height" | "rule" | "topology" => format!("board.{}", key),
+        "log" => String::from("log.filter"),
+        _ => key,
+    }
+}
+
+impl Settings {
+    pub fn load(arguments: &Arguments) -> Result<Self, String> {
+        Self::load_with_prefix(arguments, ENV_PREFIX)
+    }
+
+    // Like load with the environment variables starting with prefix in place of GOL_
+    fn load_with_prefix(arguments: &Arguments, prefix: &str) -> Result<Self, String> {
+        let config = match &arguments.config {
+            Some(path) if !path.is_file() => {
+                return Err(format!("config file {} not found", path.display()))
+            }
+            Some(path) => path.clone(),
+            None => std::env::var(format!("{}CONFIG", prefix))
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(CONFIG_FILE)),
+        };
+        let mut figment = Figment::from(Serialized::defaults(Settings::default()))
+            .merge(Toml::file(&config))
+            .merge(Env::prefixed(prefix).ignore(&["config"]).split("__"));
+        for (key, value) in &arguments.settings {
+            // Read like environment variables, numbers and booleans as such and anything else as a string
+            let value: Value = value.parse().unwrap_or_else(|_| Value::from(value.clone()));
+            figment = figment.merge((key.as_str(), value));
+        }
+        let settings: Settings = figment.extract().map_err(|error| error.to_string())?;
+        settings.check()?;
+        Ok(settings)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.limits
+            .check_size(self.board.width, self.board.height)
+            .map_err(|error| format!("board: {}", error))?;
+        if let Some(frontend) = &self.frontend {
+            if !frontend.is_dir() {
+                return Err(format!(
+                    "frontend directory {} not found",
+                    frontend.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_else(|error| format!("# {}", error))
+    }
+
+    // Rocket's own configuration, with what Rocket.toml and ROCKET_ variables set otherwise
+    pub fn rocket_config(&self) -> rocket::Config {
+        let figment = rocket::Config::figment()
+            .merge(("address", &self.address))
+            .merge(("port", self.port))
+            .merge(("keep_alive", self.keep_alive));
+        rocket::Config::from(figment)
+    }
+}
+
+// The frontend directory as Rocket state, see Settings::frontend
+pub struct Frontend(pub Option<PathBuf>);
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    fn arguments(args: &[&str]) -> Arguments {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Arguments::parse(&args).unwrap()
+    }
+
+    // A config file of its own for every test, so they can run side by side
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("gol_http_{}_{}.toml", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults_without_any_source() {
+        let settings = Settings::load_with_prefix(&arguments(&[]), "GOL_TEST_DEFAULTS_").unwrap();
+        assert_eq!(settings.port, 8000);
+        assert_eq!(settings.board.width, 50);
+    }
+
+    #[test]
+    fn file_then_environment_then_flags() {
+        let path = config_file(
+            "precedence",
+            "port = 9001\nkeep_alive = 5\naddress = \"127.0.0.1\"\n[board]\nwidth = 60\nheight = 40\n",
+        );
+        env::set_var("GOL_TEST_PRECEDENCE_PORT", "9002");
+        env::set_var("GOL_TEST_PRECEDENCE_KEEP_ALIVE", "6");
+        env::set_var("GOL_TEST_PRECEDENCE_BOARD__HEIGHT", "30");
+        let config = path.to_str().unwrap();
+        let arguments = arguments(&["--config", config, "--port", "9003", "--height=20"]);
+        let settings = Settings::load_with_prefix(&arguments, "GOL_TEST_PRECEDENCE_").unwrap();
+        fs::remove_file(&path).unwrap();
+        // Only in the file
+        assert_eq!(settings.address, "127.0.0.1");
+        assert_eq!(settings.board.width, 60);
+        // The environment beats the file
+        assert_eq!(settings.keep_alive, 6);
+        // Flags beat both
+        assert_eq!(settings.port, 9003);
+        assert_eq!(settings.board.height, 20);
+    }
+
+    #[test]
+    fn the_config_file_can_come_from_the_environment() {
+        let path = config_file("environment", "[sessions]\nmax_sessions = 7\n");
+        env::set_var("GOL_TEST_CONFIG_CONFIG", &path);
+        let settings = Settings::load_with_prefix(&arguments(&[]), "GOL_TEST_CONFIG_").unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(settings.sessions.max_sessions, 7);
+    }
+
+    #[test]
+    fn invalid_settings_are_errors() {
+        let prefix = "GOL_TEST_INVALID_";
+        assert!(Settings::load_with_prefix(&arguments(&["--port", "many"]), prefix).is_err());
+        assert!(
+            Settings::load_with_prefix(&arguments(&["--config", "missing.toml"]), prefix).is_err()
+        );
+        let too_wide = arguments(&["--width", "100", "--limits.max_width", "80"]);
+        assert!(Settings::load_with_prefix(&too_wide, prefix).is_err());
+    }
+
+    #[test]
+    fn parses_flags() {
+        let parsed = arguments(&["--print-config", "--log", "debug", "--board.rule=B36/S23"]);
+        assert!(parsed.print_config);
+        assert_eq!(
+            parsed.settings,
+            vec![
+                (String::from("log.filter"), String::from("debug")),
+                (String::from("board.rule"), String::from("B36/S23")),
+            ]
+        );
+        let missing_value: Vec<String> = vec![String::from("--port")];
+        assert!(Arguments::parse(&missing_value).is_err());
+        assert!(Arguments::parse(&[String::from("port")]).is_err());
+    }
+}
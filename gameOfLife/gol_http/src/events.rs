@@ -0,0 +1,144 @@
+use std::convert::Infallible;
+
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::sync::broadcast::error::RecvError;
+
+use crate::autoplay::Update;
+use crate::sessions::SessionGame;
+
+/*
+Id of the last event a reconnecting EventSource has seen, browsers send it on their own.
+Missing or unreadable ids count as a new client.
+*/
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+/*
+Server-sent events of a session: every board published gets its id as event id, changes
+of autoplay come as "status" events. A new client first gets the current board, a client
+coming back with Last-Event-ID gets the boards it missed as long as they are in the history.
+*/
+pub fn stream(session: SessionGame, last_event: LastEventId) -> EventStream![] {
+    let SessionGame { game, player, .. } = session;
+    EventStream! {
+        let (backlog, mut updates) = player.subscribe_since(last_event.0, &game);
+        for (id, board) in backlog {
+            yield Event::data(board).id(id.to_string());
+        }
+        loop {
+            match updates.recv().await {
+                Ok(Update::Board(id, board)) => yield Event::data(board).id(id.to_string()),
+                Ok(Update::Status(status)) => yield Event::json(&status).event("status"),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rocket::get;
+    use rocket::local::asynchronous::{Client, LocalResponse};
+    use rocket::tokio::io::AsyncReadExt;
+    use rocket::{routes, Build, Rocket};
+
+    use super::*;
+    use crate::sessions::{BoardSettings, SessionLimits, Sessions};
+
+    #[get("/events")]
+    fn events(session: SessionGame, last_event: LastEventId) -> EventStream![] {
+        stream(session, last_event)
+    }
+
+    // Sessions with one game that published the boards "a", "b" and "c", and its id
+    fn rocket() -> (Rocket<Build>, String) {
+        let sessions = Sessions::new(SessionLimits::default(), BoardSettings::default());
+        let session = sessions.create(Ipv4Addr::LOCALHOST.into()).unwrap();
+        for board in ["a", "b", "c"] {
+            session.player.publish(board.to_owned());
+        }
+        let rocket = rocket::build().manage(sessions).mount("/", routes![events]);
+        (rocket, session.id)
+    }
+
+    // The next count events of a stream that never ends, as (id, data)
+    async fn read_events(response: &mut LocalResponse<'_>, count: usize) -> Vec<(String, String)> {
+        let mut text = String::new();
+        while text.matches("\n\n").count() < count {
+            let mut buffer = [0; 4096];
+            let read = response.read(&mut buffer).await.unwrap();
+            text.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+        }
+        text.split("\n\n")
+            .take(count)
+            .map(|event| {
+                let field = |name: &str| {
+                    event
+                        .lines()
+                        .filter_map(|line| line.strip_prefix(name))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                (field("id:"), field("data:"))
+            })
+            .collect()
+    }
+
+    #[rocket::async_test]
+    async fn a_new_client_gets_the_current_board_then_every_new_one() {
+        let (rocket, id) = rocket();
+        let client = Client::tracked(rocket).await.unwrap();
+        let session = client
+            .rocket()
+            .state::<Sessions>()
+            .unwrap()
+            .get(&id)
+            .unwrap();
+        let mut response = client
+            .get(format!("/events?session={}", id))
+            .dispatch()
+            .await;
+        let first = read_events(&mut response, 1).await;
+        assert_eq!(first[0].0, "3");
+        let board: serde_json::Value = serde_json::from_str(&first[0].1).unwrap();
+        assert_eq!(board["generations"], 0);
+        session.player.publish(String::from("d"));
+        let next = read_events(&mut response, 1).await;
+        assert_eq!(next, [(String::from("4"), String::from("d"))]);
+    }
+
+    #[rocket::async_test]
+    async fn a_client_coming_back_gets_the_boards_it_missed() {
+        let (rocket, id) = rocket();
+        let client = Client::tracked(rocket).await.unwrap();
+        let mut response = client
+            .get(format!("/events?session={}", id))
+            .header(rocket::http::Header::new("Last-Event-ID", "1"))
+            .dispatch()
+            .await;
+        let missed = read_events(&mut response, 2).await;
+        assert_eq!(
+            missed,
+            [
+                (String::from("2"), String::from("b")),
+                (String::from("3"), String::from("c"))
+            ]
+        );
+    }
+}
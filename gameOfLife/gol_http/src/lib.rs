@@ -1,108 +1,186 @@
-use std::sync::Arc;
-use std::sync::Mutex;
 use rocket::*;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use std::borrow::Cow;
+use std::net::IpAddr;
 
-use gol::Game;
+use gol::limits::{LimitError, Limits};
+use gol_ratelimit::RateLimiter;
 
-struct ManagedGame {
-    game_mutex_arc: Arc<Mutex<Game>>,
+pub mod api;
+pub mod autoplay;
+pub mod config;
+pub mod events;
+pub mod logging;
+pub mod metrics;
+pub mod players;
+pub mod sessions;
+pub mod throttle;
+
+use autoplay::{Command, PlayerStatus};
+use config::{Frontend, Settings};
+use logging::LogHandle;
+use sessions::{CreatedSession, SessionGame, SessionInfo, Sessions};
+use throttle::{Client, Throttle};
+
+// The frontend files, read from the frontend directory if one is configured, see config::Settings
+fn frontend_file(frontend : &Frontend, name : &str, built_in : &'static str) -> Result<Cow<'static, str>, Status>{
+    match &frontend.0 {
+        Some(directory) => std::fs::read_to_string(directory.join(name)).map(Cow::Owned).map_err(|_| Status::NotFound),
+        None => Ok(Cow::Borrowed(built_in)),
+    }
 }
 
 #[get("/")]
-fn index() -> rocket::response::content::Html<&'static str> {
-    const DATA: &'static str = include_str!("../../frontend/index.html");
-    rocket::response::content::Html(DATA)
+fn index(frontend : &State<Frontend>) -> Result<rocket::response::content::RawHtml<Cow<'static, str>>, Status> {
+    const DATA: &str = include_str!("../../frontend/index.html");
+    frontend_file(frontend, "index.html", DATA).map(rocket::response::content::RawHtml)
 }
 
 #[get("/")]
-fn javascript() -> rocket::response::content::JavaScript<&'static str> {
-    const DATA: &'static str = include_str!("../../frontend/javascript.js");
-    rocket::response::content::JavaScript(DATA)
+fn javascript(frontend : &State<Frontend>) -> Result<rocket::response::content::RawJavaScript<Cow<'static, str>>, Status> {
+    const DATA: &str = include_str!("../../frontend/javascript.js");
+    frontend_file(frontend, "javascript.js", DATA).map(rocket::response::content::RawJavaScript)
 }
 
 #[get("/")]
-fn mystyle() -> rocket::response::content::Css<&'static str> {
-    const DATA: &'static str = include_str!("../../frontend/mystyle.css");
-    rocket::response::content::Css(DATA)
+fn mystyle(frontend : &State<Frontend>) -> Result<rocket::response::content::RawCss<Cow<'static, str>>, Status> {
+    const DATA: &str = include_str!("../../frontend/mystyle.css");
+    frontend_file(frontend, "mystyle.css", DATA).map(rocket::response::content::RawCss)
 }
 
+// Advance up to ChangeValue are kept for old clients, new ones use the API under /api/v1, see api.rs
+// The generation is computed on the blocking threads, a large board holds up no other request
 #[get("/")]
-fn advance(manager : &State<ManagedGame>) -> String{
-    let str:String;
-    {
-        let a = manager.game_mutex_arc.clone();
+async fn advance(_throttle : Throttle, session : SessionGame) -> Result<String, Status>{
+    let a = session.game.clone();
+    let str = rocket::tokio::task::spawn_blocking(move || {
         let mut game = a.lock().unwrap();
-        (*game).tick();
-        str = (*game).serialize();
-    }
-    str
+        metrics::tick(&mut game);
+        (*game).serialize()
+    }).await.map_err(|_| Status::InternalServerError)?;
+    session.player.publish(str.clone());
+    Ok(str)
 }
 
 #[get("/")]
-fn randomize(manager : &State<ManagedGame>) -> String{
+fn randomize(_throttle : Throttle, session : SessionGame) -> String{
     let str:String;
     {
-        let a = manager.game_mutex_arc.clone();
+        let a = session.game.clone();
         let mut game = a.lock().unwrap();
         (*game).randomize();
         str = (*game).serialize();
     }
+    session.player.publish(str.clone());
     str
 }
 
 #[get("/")]
-fn reset_board(manager : &State<ManagedGame>) -> String{
+fn reset_board(_throttle : Throttle, session : SessionGame) -> String{
     let str:String;
     {
-        let a = manager.game_mutex_arc.clone();
+        let a = session.game.clone();
         let mut game = a.lock().unwrap();
         str = (*game).negate_everything();
         //(*game).serialize();
     }
+    session.player.publish(str.clone());
     str
 }
 
 #[get("/?<height>&<width>")]
-fn new_board(manager : &State<ManagedGame>, height: u32, width:u32 ) -> String{
+fn new_board(_throttle : Throttle, limits : &State<Limits>, sessions : &State<Sessions>, session : SessionGame, height: u32, width:u32 ) -> Result<String, Status>{
+    // Some engines round the size up, the board they end up with is the one checked and booked
+    let (fitted_width, fitted_height) = session.game.lock().unwrap().automaton().fitted_size(width, height);
+    limits.check_size(fitted_width, fitted_height).map_err(|error| limit_status(&error))?;
+    sessions.reserve_cells(&session.id, fitted_width, fitted_height).map_err(|error| error.status())?;
     let str:String;
     {
-        let a = manager.game_mutex_arc.clone();
+        let a = session.game.clone();
         let mut game = a.lock().unwrap();
-        str = (*game).change_size(height, width);
+        str = (*game).change_size(width, height);
     }
-    str
+    session.player.publish(str.clone());
+    Ok(str)
 }
 
 #[get("/?<id>")]
-fn change_value(manager : &State<ManagedGame>, id : usize ) -> String{
+fn change_value(_throttle : Throttle, session : SessionGame, id : usize ) -> Result<String, Status>{
     let str:String;
     {
-        let a = manager.game_mutex_arc.clone();
+        let a = session.game.clone();
         let mut game = a.lock().unwrap();
-        (*game).change_value(id);
+        (*game).change_value(id).map_err(|_| Status::BadRequest)?;
         str = (*game).serialize();
     }
-    str
+    session.player.publish(str.clone());
+    Ok(str)
 }
 
-#[rocket::main]
-pub async fn rocket_setup() -> Result<(), rocket::Error> {
-    let mut game = Game::new(Some(50), Some(50));
-    game.randomize();
+// WebSocket pushing every generation of the session, see autoplay::watch
+#[get("/")]
+fn live(_throttle : Throttle, ws : rocket_ws::WebSocket, session : SessionGame) -> rocket_ws::Channel<'static>{
+    autoplay::watch(ws, session)
+}
 
-    let game_mutex: std::sync::Mutex<Game> = std::sync::Mutex::new(game);
-    let game_arc : Arc<Mutex<Game>> = std::sync::Arc::new(game_mutex);
-    let manager:ManagedGame = ManagedGame{ game_mutex_arc: game_arc };
-    
-    let config_dummy = rocket::Config::figment()
-                    .merge(("address", "0.0.0.0"))
-                    .merge(("port", 8000))
-                    .merge(("keep_alive", 0)); // To get compareable results in Wireshark
+// Server-sent events of the session, for clients that only watch, see events::stream
+#[get("/")]
+fn event_stream(_throttle : Throttle, session : SessionGame, last_event : events::LastEventId) -> rocket::response::stream::EventStream![]{
+    events::stream(session, last_event)
+}
 
-    let config = rocket::Config::from(config_dummy);
+// Play, pause, speed and step over plain HTTP, the same commands the WebSocket takes
+#[post("/", data = "<command>")]
+async fn control(_throttle : Throttle, session : SessionGame, command : Json<Command>) -> Json<PlayerStatus>{
+    session.player.apply(command.into_inner(), &session.game).await;
+    Json(session.player.status())
+}
+
+// Starts a session of its own, e.g. for every browser tab, its id goes into the session parameter
+#[post("/")]
+fn create_session(_throttle : Throttle, client : Client, sessions : &State<Sessions>) -> Result<(Status, Json<CreatedSession>), Status>{
+    let session = sessions.create(client.0).map_err(|error| error.status())?;
+    Ok((Status::Created, Json(CreatedSession{ id: session.id })))
+}
+
+// Only the sessions the client created, like delete_session
+#[get("/")]
+fn list_sessions(_throttle : Throttle, client : Client, sessions : &State<Sessions>) -> Json<Vec<SessionInfo>>{
+    Json(sessions.list(client.0))
+}
+
+#[delete("/<id>")]
+fn delete_session(_throttle : Throttle, client : Client, sessions : &State<Sessions>, id : &str) -> Status{
+    match sessions.delete_owned(id, client.0) {
+        Ok(()) => Status::NoContent,
+        Err(error) => error.status(),
+    }
+}
+
+fn limit_status(error : &LimitError) -> Status{
+    match error {
+        LimitError::Invalid(_) => Status::BadRequest,
+        LimitError::TooLarge(_) => Status::PayloadTooLarge,
+    }
+}
+
+// Runs the server until it is shut down, on a runtime of its own
+pub fn rocket_setup(settings : Settings, log : LogHandle) -> Result<(), Box<rocket::Error>> {
+    rocket::execute(serve(settings, log))
+}
+
+async fn serve(settings : Settings, log : LogHandle) -> Result<(), Box<rocket::Error>> {
+    let manager = Sessions::new(settings.sessions, settings.board);
+    let limits = settings.limits;
+    let config = settings.rocket_config();
     
     rocket::custom(config)
         .manage(manager)
+        .manage(limits)
+        .manage(RateLimiter::<IpAddr>::from_limits(&limits))
+        .manage(Frontend(settings.frontend))
+        .manage(log)
         .mount("/", routes![index])
         .mount("/javascript.js", routes![javascript])
         .mount("/mystyle.css", routes![mystyle])
@@ -111,6 +189,19 @@ pub async fn rocket_setup() -> Result<(), rocket::Error> {
         .mount("/NewBoard", routes![new_board])
         .mount("/ChangeValue", routes![change_value])
         .mount("/Randomize", routes![randomize])
+        .mount("/live", routes![live])
+        .mount("/events", routes![event_stream])
+        .mount("/control", routes![control])
+        .mount("/sessions", routes![create_session, list_sessions, delete_session])
+        .mount(api::BASE, api::routes())
+        .mount("/metrics", metrics::routes())
+        .mount("/log", logging::routes())
+        .attach(metrics::RequestMetrics)
+        .attach(logging::RequestLogger)
+        .register("/", throttle::catchers())
+        .register(api::BASE, api::catchers())
         .launch()
         .await
-}
\ No newline at end of file
+        .map(|_| ())
+        .map_err(Box::new)
+}
@@ -0,0 +1,364 @@
+use std::io::{self, IsTerminal};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::status::Custom;
+use rocket::response::Response;
+use rocket::{get, put, routes, Data, Route, State};
+use serde::{Deserialize, Serialize};
+use tracing::field::Empty;
+use tracing::{error, info, info_span, warn, Span};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::metrics;
+use crate::sessions::Sessions;
+use crate::throttle::Throttle;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    // One line per event for people
+    #[default]
+    Text,
+    // One JSON object per event for log collectors
+    Json,
+}
+
+/*
+What the server logs and how, e.g. filter = "debug" or "info,gol_http::metrics=trace" for
+every generation. The filter can be changed while the server runs with PUT /log
+if allow_changes is set.
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LogSettings {
+    pub filter: String,
+    pub format: LogFormat,
+    // Off by default, any client could otherwise turn on trace logging and flood the logs
+    pub allow_changes: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            // Rocket would log every request a second time
+            filter: String::from("info,rocket=warn,hyper=warn"),
+            format: LogFormat::Text,
+            allow_changes: false,
+        }
+    }
+}
+
+// Swaps the filter of the running server, see GET and PUT /log
+pub struct LogHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    filter: Mutex<String>,
+    allow_changes: bool,
+}
+
+impl LogHandle {
+    pub fn filter(&self) -> String {
+        self.filter.lock().unwrap().clone()
+    }
+
+    pub fn set_filter(&self, filter: &str) -> Result<(), String> {
+        let parsed = parse_filter(filter)?;
+        self.handle
+            .reload(parsed)
+            .map_err(|error| error.to_string())?;
+        *self.filter.lock().unwrap() = filter.to_owned();
+        Ok(())
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<EnvFilter, String> {
+    EnvFilter::builder()
+        .parse(filter)
+        .map_err(|error| format!("invalid log filter {}: {}", filter, error))
+}
+
+/*
+Sends the logs of the whole process to stdout, Rocket's own included.
+Fails if the filter is invalid or a logger is already set up.
+*/
+pub fn init(settings: &LogSettings) -> Result<LogHandle, String> {
+    let (filter, handle) = reload::Layer::new(parse_filter(&settings.filter)?);
+    let (text, json) = match settings.format {
+        // Colours only for a terminal, not for files or log collectors
+        LogFormat::Text => (
+            Some(fmt::layer().with_ansi(io::stdout().is_terminal())),
+            None,
+        ),
+        LogFormat::Json => (None, Some(fmt::layer().json())),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .try_init()
+        .map_err(|error| error.to_string())?;
+    Ok(LogHandle {
+        handle,
+        filter: Mutex::new(settings.filter.clone()),
+        allow_changes: settings.allow_changes,
+    })
+}
+
+// The span of a request and what is known about it before it is answered
+struct RequestLog {
+    span: Span,
+    start: Instant,
+    session: OnceLock<String>,
+}
+
+fn request_log<'r>(request: &'r Request<'_>) -> &'r RequestLog {
+    request.local_cache(|| RequestLog {
+        span: info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            route = Empty,
+            session = Empty,
+            board = Empty,
+        ),
+        start: Instant::now(),
+        session: OnceLock::new(),
+    })
+}
+
+// Adds the session a request works on to its span, for routes without one in the path
+pub fn record_session(request: &Request, id: &str) {
+    let log = request_log(request);
+    if log.session.set(id.to_owned()).is_ok() {
+        log.span.record("session", id);
+    }
+}
+
+// The segment of the path in place of <id> in the route, e.g. the game of /api/v1/games/<id>
+fn path_session(request: &Request) -> Option<String> {
+    let route = request.route()?;
+    route
+        .uri
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .zip(request.uri().path().segments())
+        .find(|(template, _)| *template == "<id>")
+        .map(|(_, id)| id.to_owned())
+}
+
+/*
+Logs every answer in the span of its request with the route, the session and the size of its
+board, the status and how long it took. Server errors are logged as errors, client errors
+as warnings.
+*/
+pub struct RequestLogger;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        request_log(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let log = request_log(request);
+        let span = &log.span;
+        span.record("route", metrics::route_name(request).as_str());
+        let session = match log.session.get() {
+            Some(session) => Some(session.clone()),
+            None => path_session(request).inspect(|session| {
+                span.record("session", session.as_str());
+            }),
+        };
+        if let Some(session) = session {
+            // Kept with the session, the answer waits for no game
+            let size = request
+                .rocket()
+                .state::<Sessions>()
+                .and_then(|sessions| sessions.board_size(&session));
+            if let Some((width, height)) = size {
+                span.record("board", format!("{}x{}", width, height).as_str());
+            }
+        }
+        let status = response.status();
+        let duration_ms = log.start.elapsed().as_secs_f64() * 1000.0;
+        span.in_scope(|| match status.code {
+            500.. => error!(status = status.code, duration_ms, "answered"),
+            400..=499 => warn!(status = status.code, duration_ms, "answered"),
+            _ => info!(status = status.code, duration_ms, "answered"),
+        });
+    }
+}
+
+#[get("/")]
+fn get_filter(log: &State<LogHandle>) -> String {
+    log.filter()
+}
+
+/*
+Takes the new filter as the body, e.g. curl -X PUT -d debug localhost:8000/log,
+answers 403 unless the log settings allow changes
+*/
+#[put("/", data = "<filter>")]
+fn set_filter(
+    _throttle: Throttle,
+    log: &State<LogHandle>,
+    filter: &str,
+) -> Result<String, Custom<String>> {
+    if !log.allow_changes {
+        let message = String::from("changing the log filter is turned off");
+        return Err(Custom(Status::Forbidden, message));
+    }
+    let filter = filter.trim();
+    log.set_filter(filter)
+        .map_err(|message| Custom(Status::BadRequest, message))?;
+    info!(filter, "log filter changed");
+    Ok(log.filter())
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![get_filter, set_filter]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use rocket::local::blocking::Client;
+
+    use super::*;
+    use crate::sessions::{BoardSettings, SessionGame, SessionLimits};
+
+    // A handle to a filter no subscriber uses, enough for the routes
+    fn client(allow_changes: bool) -> Client {
+        let (_layer, handle) = reload::Layer::new(parse_filter("info").unwrap());
+        let log = LogHandle {
+            handle,
+            filter: Mutex::new(String::from("info")),
+            allow_changes,
+        };
+        let rocket = rocket::build().manage(log).mount("/log", routes());
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn the_filter_cannot_be_changed_by_default() {
+        assert!(!LogSettings::default().allow_changes);
+        let client = client(false);
+        let response = client.put("/log").body("trace").dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(client.get("/log").dispatch().into_string().unwrap(), "info");
+    }
+
+    #[test]
+    fn an_invalid_filter_is_a_bad_request() {
+        let client = client(true);
+        let response = client.put("/log").body("info,[").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[get("/games/<id>")]
+    fn game(id: &str) -> &str {
+        id
+    }
+
+    #[get("/board")]
+    fn board(session: SessionGame) -> String {
+        session.id
+    }
+
+    // Log lines written to a buffer the test reads back
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // The "answered" lines logged as JSON while the requests are made, and the session they got
+    fn answers(requests: impl FnOnce(&Client, &str)) -> (Vec<serde_json::Value>, String) {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .json()
+                .with_writer(move || writer.clone())
+                .with_filter(EnvFilter::new("gol_http=info")),
+        );
+        let id = tracing::subscriber::with_default(subscriber, || {
+            let sessions = Sessions::new(SessionLimits::default(), BoardSettings::default());
+            let id = sessions.create(Ipv4Addr::LOCALHOST.into()).unwrap().id;
+            let rocket = rocket::build()
+                .manage(sessions)
+                .mount("/", rocket::routes![game, board])
+                .attach(RequestLogger);
+            let client = Client::tracked(rocket).unwrap();
+            requests(&client, &id);
+            id
+        });
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines = text
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|line| line["fields"]["message"] == "answered")
+            .collect();
+        (lines, id)
+    }
+
+    #[test]
+    fn answers_carry_the_route_session_and_board() {
+        let (lines, id) = answers(|client, id| {
+            client.get(format!("/games/{}", id)).dispatch();
+            client.get(format!("/board?session={}", id)).dispatch();
+        });
+        assert_eq!(lines.len(), 2);
+        let size = BoardSettings::default();
+        let board = format!("{}x{}", size.width, size.height);
+        for (line, route) in lines.iter().zip(["/games/<id>", "/board"]) {
+            let span = &line["span"];
+            assert_eq!(span["name"], "request");
+            assert_eq!(span["method"], "GET");
+            assert_eq!(span["route"], route);
+            assert_eq!(span["session"], id.as_str());
+            assert_eq!(span["board"], board.as_str());
+            assert_eq!(line["level"], "INFO");
+            assert_eq!(line["fields"]["status"], 200);
+            assert!(line["fields"]["duration_ms"].as_f64().is_some());
+        }
+    }
+
+    #[test]
+    fn unknown_sessions_and_routes_are_warnings_without_a_board() {
+        let (lines, _) = answers(|client, _| {
+            client.get("/games/nobody").dispatch();
+            client.get("/nowhere").dispatch();
+        });
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["span"]["session"], "nobody");
+        assert!(lines[0]["span"].get("board").is_none());
+        assert_eq!(lines[0]["fields"]["status"], 200);
+        assert_eq!(lines[1]["span"]["route"], "unmatched");
+        assert!(lines[1]["span"].get("session").is_none());
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["fields"]["status"], 404);
+    }
+}
@@ -1,12 +1,35 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-
+use std::process;
 use std::thread;
 
-mod lib;
+use gol_http::config::{Arguments, Settings, USAGE};
+use gol_http::logging;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arguments = Arguments::parse(&args).unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if arguments.help {
+        print!("{}", USAGE);
+        return;
+    }
+    let settings = Settings::load(&arguments).unwrap_or_else(|error| {
+        eprintln!("Invalid configuration: {}", error);
+        process::exit(2);
+    });
+    if arguments.print_config {
+        print!("{}", settings.to_toml());
+        return;
+    }
+
+    let log = logging::init(&settings.log).unwrap_or_else(|error| {
+        eprintln!("Invalid configuration: {}", error);
+        process::exit(2);
+    });
+
     let http_handler = thread::spawn(|| {
-        let x = lib::rocket_setup();
+        let x = gol_http::rocket_setup(settings, log);
         x.expect("Rocket Server Blew");
     });
     http_handler.join().expect("Error joining http thread");
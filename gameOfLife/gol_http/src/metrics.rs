@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::request::Request;
+use rocket::response::Response;
+use rocket::{get, routes, Route, State};
+
+use tracing::trace;
+
+use gol::Game;
+
+use crate::sessions::Sessions;
+
+// Version of the Prometheus text format GET /metrics answers in
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Upper bounds of the histogram buckets, a last one for everything larger is implied
+const TICK_SECONDS: [f64; 10] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 1.0,
+];
+const PAYLOAD_BYTES: [f64; 8] = [
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+];
+
+/*
+What the server did since it started, kept for the whole process since games tick in
+tasks of their own as well as in requests. GET /metrics hands it to Prometheus,
+ticks per second are rate(gol_http_ticks_total[1m]) there.
+*/
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Metrics {
+    // Answers by route template and status code
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    ticks: AtomicU64,
+    tick_seconds: Histogram<10>,
+    // Bodies of answers of a known size
+    response_bytes: Histogram<8>,
+    // Boards pushed to the clients watching a game over a WebSocket or server-sent events
+    broadcast_bytes: Histogram<8>,
+}
+
+struct Histogram<const N: usize> {
+    bounds: [f64; N],
+    // Observations per bucket, the one past the bounds counts those larger than all of them
+    buckets: [AtomicU64; N],
+    larger: AtomicU64,
+    // Bits of an f64
+    sum: AtomicU64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(bounds: [f64; N]) -> Self {
+        Self {
+            bounds,
+            buckets: [const { AtomicU64::new(0) }; N],
+            larger: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        match self.bounds.iter().position(|&bound| value <= bound) {
+            Some(bucket) => self.buckets[bucket].fetch_add(1, Ordering::Relaxed),
+            None => self.larger.fetch_add(1, Ordering::Relaxed),
+        };
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    // The samples of the histogram, labels are added to le of every bucket
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut count = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            count += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, count
+            );
+        }
+        count += self.larger.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, count
+        );
+        let sum = f64::from_bits(self.sum.load(Ordering::Relaxed));
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, count);
+    }
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            requests: Mutex::new(BTreeMap::new()),
+            ticks: AtomicU64::new(0),
+            tick_seconds: Histogram::new(TICK_SECONDS),
+            response_bytes: Histogram::new(PAYLOAD_BYTES),
+            broadcast_bytes: Histogram::new(PAYLOAD_BYTES),
+        }
+    }
+
+    pub fn count_request(&self, route: &str, status: u16) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests.entry((route.to_owned(), status)).or_insert(0) += 1;
+    }
+
+    pub fn observe_tick(&self, seconds: f64) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_seconds.observe(seconds);
+    }
+
+    pub fn observe_response(&self, bytes: usize) {
+        self.response_bytes.observe(bytes as f64);
+    }
+
+    pub fn observe_broadcast(&self, bytes: usize) {
+        self.broadcast_bytes.observe(bytes as f64);
+    }
+
+    // Everything in the Prometheus text format, with the sessions alive right now
+    pub fn render(&self, sessions: usize) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP gol_http_sessions Sessions that have not expired.\n");
+        out.push_str("# TYPE gol_http_sessions gauge\n");
+        let _ = writeln!(out, "gol_http_sessions {}", sessions);
+
+        out.push_str("# HELP gol_http_requests_total Requests answered, by route and status.\n");
+        out.push_str("# TYPE gol_http_requests_total counter\n");
+        for ((route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "gol_http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                escape(route),
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP gol_http_ticks_total Generations computed in all games.\n");
+        out.push_str("# TYPE gol_http_ticks_total counter\n");
+        let _ = writeln!(
+            out,
+            "gol_http_ticks_total {}",
+            self.ticks.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP gol_http_tick_duration_seconds Time to compute one generation.\n");
+        out.push_str("# TYPE gol_http_tick_duration_seconds histogram\n");
+        self.tick_seconds
+            .write(&mut out, "gol_http_tick_duration_seconds", "");
+
+        out.push_str(
+            "# HELP gol_http_payload_bytes Size of serialized answers and pushed boards.\n",
+        );
+        out.push_str("# TYPE gol_http_payload_bytes histogram\n");
+        self.response_bytes
+            .write(&mut out, "gol_http_payload_bytes", "kind=\"response\"");
+        self.broadcast_bytes
+            .write(&mut out, "gol_http_payload_bytes", "kind=\"broadcast\"");
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Runs a generation of the game and records how long it took, logged at the trace level
+pub fn tick(game: &mut Game) {
+    let start = Instant::now();
+    game.tick();
+    let seconds = start.elapsed().as_secs_f64();
+    METRICS.observe_tick(seconds);
+    let automaton = game.automaton();
+    trace!(
+        width = automaton.width(),
+        height = automaton.height(),
+        duration_ms = seconds * 1000.0,
+        "tick"
+    );
+}
+
+// Template of the route that answered a request, unmatched if none did
+pub fn route_name(request: &Request) -> String {
+    request
+        .route()
+        .map(|route| route.uri.path().to_string())
+        .unwrap_or_else(|| String::from("unmatched"))
+}
+
+/*
+Counts every answer by the route that made it and its status. Routes go by their template,
+e.g. /api/v1/games/<id>, so every game shares one series.
+*/
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        METRICS.count_request(&route_name(request), response.status().code);
+        // Streams like the server-sent events have no size up front
+        if let Some(bytes) = response.body().preset_size() {
+            METRICS.observe_response(bytes);
+        }
+    }
+}
+
+#[get("/")]
+fn metrics(sessions: &State<Sessions>) -> (ContentType, String) {
+    let content_type = ContentType::parse_flexible(CONTENT_TYPE).unwrap_or(ContentType::Plain);
+    (content_type, METRICS.render(sessions.count()))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![metrics]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    use super::*;
+    use crate::sessions::{BoardSettings, SessionLimits};
+
+    // The value of the sample with this name and labels
+    fn sample(text: &str, series: &str) -> Option<f64> {
+        text.lines()
+            .filter_map(|line| line.rsplit_once(' '))
+            .find(|&(name, _)| name == series)
+            .map(|(_, value)| value.parse().unwrap())
+    }
+
+    #[test]
+    fn every_family_has_help_and_type() {
+        let text = Metrics::new().render(0);
+        for (name, kind) in [
+            ("gol_http_sessions", "gauge"),
+            ("gol_http_requests_total", "counter"),
+            ("gol_http_ticks_total", "counter"),
+            ("gol_http_tick_duration_seconds", "histogram"),
+            ("gol_http_payload_bytes", "histogram"),
+        ] {
+            assert!(text.contains(&format!("# HELP {} ", name)), "{}", name);
+            assert!(
+                text.contains(&format!("# TYPE {} {}\n", name, kind)),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(sample(&text, "gol_http_sessions"), Some(0.0));
+        assert_eq!(sample(&text, "gol_http_ticks_total"), Some(0.0));
+    }
+
+    #[test]
+    fn requests_are_counted_by_route_and_status() {
+        let metrics = Metrics::new();
+        metrics.count_request("/api/v1/games/<id>", 200);
+        metrics.count_request("/api/v1/games/<id>", 200);
+        metrics.count_request("/api/v1/games/<id>", 404);
+        metrics.count_request("/odd\"route", 500);
+        let text = metrics.render(2);
+        assert_eq!(sample(&text, "gol_http_sessions"), Some(2.0));
+        let series = |route: &str, status: u16| {
+            sample(
+                &text,
+                &format!(
+                    "gol_http_requests_total{{route=\"{}\",status=\"{}\"}}",
+                    route, status
+                ),
+            )
+        };
+        assert_eq!(series("/api/v1/games/<id>", 200), Some(2.0));
+        assert_eq!(series("/api/v1/games/<id>", 404), Some(1.0));
+        assert_eq!(series("/odd\\\"route", 500), Some(1.0));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+        metrics.observe_tick(0.0002);
+        metrics.observe_tick(0.003);
+        metrics.observe_tick(5.0);
+        metrics.observe_broadcast(100);
+        let text = metrics.render(0);
+        let tick = |le: &str| {
+            sample(
+                &text,
+                &format!("gol_http_tick_duration_seconds_bucket{{le=\"{}\"}}", le),
+            )
+        };
+        assert_eq!(tick("0.0001"), Some(0.0));
+        assert_eq!(tick("0.00025"), Some(1.0));
+        assert_eq!(tick("0.005"), Some(2.0));
+        assert_eq!(tick("1"), Some(2.0));
+        assert_eq!(tick("+Inf"), Some(3.0));
+        assert_eq!(sample(&text, "gol_http_ticks_total"), Some(3.0));
+        assert_eq!(
+            sample(&text, "gol_http_tick_duration_seconds_count"),
+            Some(3.0)
+        );
+        let sum = sample(&text, "gol_http_tick_duration_seconds_sum").unwrap();
+        assert!((sum - 5.0032).abs() < 1e-9);
+        assert_eq!(
+            sample(
+                &text,
+                "gol_http_payload_bytes_bucket{kind=\"broadcast\",le=\"256\"}"
+            ),
+            Some(1.0)
+        );
+        assert_eq!(
+            sample(&text, "gol_http_payload_bytes_count{kind=\"response\"}"),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn the_route_answers_in_the_text_format() {
+        let sessions = Sessions::new(SessionLimits::default(), BoardSettings::default());
+        sessions.create(Ipv4Addr::LOCALHOST.into()).unwrap();
+        let rocket = rocket::build()
+            .manage(sessions)
+            .mount("/metrics", routes())
+            .attach(RequestMetrics);
+        let client = Client::tracked(rocket).unwrap();
+        let response = client.get("/metrics").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Content-Type"),
+            Some(CONTENT_TYPE)
+        );
+        let text = response.into_string().unwrap();
+        assert_eq!(sample(&text, "gol_http_sessions"), Some(1.0));
+        // The request itself is counted once it is answered, the next scrape sees it
+        let text = client.get("/metrics").dispatch().into_string().unwrap();
+        let served = sample(
+            &text,
+            "gol_http_requests_total{route=\"/metrics\",status=\"200\"}",
+        );
+        assert!(served.unwrap() >= 1.0);
+    }
+}
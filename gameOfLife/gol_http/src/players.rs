@@ -0,0 +1,263 @@
+use rocket::http::Status;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use gol::CellularAutomaton;
+
+use crate::sessions::new_token;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerError {
+    // The game runs plain Life, there are no colours to hand out
+    NotMultiplayer,
+    // Every colour of the game has a player
+    GameFull,
+    UnknownPlayer,
+    // The cell is alive in the colour of another player
+    CellTaken { row: u32, column: u32, colour: u8 },
+}
+
+impl PlayerError {
+    pub fn status(&self) -> Status {
+        match self {
+            PlayerError::NotMultiplayer | PlayerError::GameFull => Status::Conflict,
+            PlayerError::UnknownPlayer => Status::NotFound,
+            PlayerError::CellTaken { .. } => Status::Conflict,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            PlayerError::NotMultiplayer => {
+                String::from("the game has no colours, create it with colours set to 2 or 4")
+            }
+            PlayerError::GameFull => String::from("every colour of the game has a player"),
+            PlayerError::UnknownPlayer => String::from("no player with this id in the game"),
+            PlayerError::CellTaken {
+                row,
+                column,
+                colour,
+            } => format!(
+                "cell ({}, {}) belongs to the player with colour {}",
+                row, column, colour
+            ),
+        }
+    }
+}
+
+// A player as the API shows it, score counts the live cells in the player's colour right now
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct PlayerInfo {
+    pub id: String,
+    pub name: String,
+    pub colour: u8,
+    pub score: u32,
+    // Cells the player brought to life by hand
+    pub placed: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Seat {
+    id: String,
+    name: String,
+    colour: u8,
+    placed: u32,
+}
+
+/*
+The players of a session, each one owns a colour of the game for as long as they are in it.
+Cells keep their colour after their player left, a new player may pick the colour up again.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Roster {
+    // 0 for games running plain Life
+    colours: u8,
+    seats: Vec<Seat>,
+}
+
+impl Roster {
+    pub fn new(colours: u8) -> Self {
+        Self {
+            colours,
+            seats: Vec::new(),
+        }
+    }
+
+    pub fn colours(&self) -> u8 {
+        self.colours
+    }
+
+    pub fn len(&self) -> usize {
+        self.seats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seats.is_empty()
+    }
+
+    // Seats a player in the lowest colour nobody has
+    pub fn join(&mut self, name: &str) -> Result<(String, u8), PlayerError> {
+        if self.colours == 0 {
+            return Err(PlayerError::NotMultiplayer);
+        }
+        let colour = (1..=self.colours)
+            .find(|&colour| self.seats.iter().all(|seat| seat.colour != colour))
+            .ok_or(PlayerError::GameFull)?;
+        let id = new_token();
+        self.seats.push(Seat {
+            id: id.clone(),
+            name: name.to_owned(),
+            colour,
+            placed: 0,
+        });
+        Ok((id, colour))
+    }
+
+    pub fn leave(&mut self, id: &str) -> Result<(), PlayerError> {
+        let before = self.seats.len();
+        self.seats.retain(|seat| seat.id != id);
+        if self.seats.len() == before {
+            return Err(PlayerError::UnknownPlayer);
+        }
+        Ok(())
+    }
+
+    pub fn colour_of(&self, id: &str) -> Result<u8, PlayerError> {
+        self.seat(id).map(|seat| seat.colour)
+    }
+
+    /*
+    Brings cells to life in the player's colour or kills them. A player may only touch dead
+    cells and their own, so two players clicking the same cell cannot undo each other.
+    Nothing changes if any of the cells belongs to someone else.
+    */
+    pub fn place(
+        &mut self,
+        id: &str,
+        automaton: &mut dyn CellularAutomaton,
+        cells: &[(u32, u32, bool)],
+    ) -> Result<(), PlayerError> {
+        let colour = self.colour_of(id)?;
+        for &(row, column, _) in cells {
+            let state = automaton.get_cell(row, column);
+            if state != 0 && state != colour {
+                return Err(PlayerError::CellTaken {
+                    row,
+                    column,
+                    colour: state,
+                });
+            }
+        }
+        let mut placed = 0;
+        for &(row, column, alive) in cells {
+            if alive && automaton.get_cell(row, column) == 0 {
+                placed += 1;
+            }
+            automaton.set_cell(row, column, if alive { colour } else { 0 });
+        }
+        if let Some(seat) = self.seats.iter_mut().find(|seat| seat.id == id) {
+            seat.placed += placed;
+        }
+        Ok(())
+    }
+
+    // Every player with the live cells of their colour on the board
+    pub fn scores(&self, automaton: &dyn CellularAutomaton) -> Vec<PlayerInfo> {
+        let mut counts = vec![0; self.colours as usize + 1];
+        for row in 0..automaton.height() {
+            for column in 0..automaton.width() {
+                if let Some(count) = counts.get_mut(automaton.get_cell(row, column) as usize) {
+                    *count += 1;
+                }
+            }
+        }
+        self.seats
+            .iter()
+            .map(|seat| PlayerInfo {
+                id: seat.id.clone(),
+                name: seat.name.clone(),
+                colour: seat.colour,
+                score: counts[seat.colour as usize],
+                placed: seat.placed,
+            })
+            .collect()
+    }
+
+    fn seat(&self, id: &str) -> Result<&Seat, PlayerError> {
+        self.seats
+            .iter()
+            .find(|seat| seat.id == id)
+            .ok_or(PlayerError::UnknownPlayer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gol::colours::{ColourLife, Variant};
+
+    use super::*;
+
+    #[test]
+    fn players_get_the_lowest_free_colour_until_the_game_is_full() {
+        let mut roster = Roster::new(2);
+        let (first, colour) = roster.join("ann").unwrap();
+        assert_eq!(colour, 1);
+        assert_eq!(roster.join("bob").unwrap().1, 2);
+        assert_eq!(roster.join("cy"), Err(PlayerError::GameFull));
+        roster.leave(&first).unwrap();
+        assert_eq!(roster.join("cy").unwrap().1, 1);
+        assert_eq!(roster.leave(&first), Err(PlayerError::UnknownPlayer));
+        assert_eq!(
+            Roster::default().join("ann"),
+            Err(PlayerError::NotMultiplayer)
+        );
+    }
+
+    #[test]
+    fn a_cell_of_another_player_cannot_be_touched() {
+        let mut board = ColourLife::new(Variant::Immigration, 6, 6);
+        let mut roster = Roster::new(2);
+        let (ann, _) = roster.join("ann").unwrap();
+        let (bob, _) = roster.join("bob").unwrap();
+        roster
+            .place(&ann, &mut board, &[(1, 1, true), (1, 2, true)])
+            .unwrap();
+        // Bob's cells are all refused as one of them is Ann's
+        let taken = roster.place(&bob, &mut board, &[(3, 3, true), (1, 2, false)]);
+        assert_eq!(
+            taken,
+            Err(PlayerError::CellTaken {
+                row: 1,
+                column: 2,
+                colour: 1
+            })
+        );
+        assert_eq!(taken.unwrap_err().status(), Status::Conflict);
+        assert_eq!(board.get_cell(3, 3), 0);
+        assert_eq!(board.get_cell(1, 2), 1);
+        // Her own cells Ann may kill, dead cells anybody may take
+        roster.place(&ann, &mut board, &[(1, 2, false)]).unwrap();
+        roster.place(&bob, &mut board, &[(1, 2, true)]).unwrap();
+        assert_eq!(board.get_cell(1, 2), 2);
+    }
+
+    #[test]
+    fn scores_count_the_cells_of_each_colour_and_placed_counts_births() {
+        let mut board = ColourLife::new(Variant::Immigration, 6, 6);
+        let mut roster = Roster::new(2);
+        let (ann, _) = roster.join("ann").unwrap();
+        roster
+            .place(&ann, &mut board, &[(0, 0, true), (0, 1, true)])
+            .unwrap();
+        // Placing a live cell of her own again brings nothing to life
+        roster.place(&ann, &mut board, &[(0, 0, true)]).unwrap();
+        roster.place(&ann, &mut board, &[(0, 1, false)]).unwrap();
+        let scores = roster.scores(&board);
+        assert_eq!(scores.len(), 1);
+        assert_eq!((scores[0].score, scores[0].placed), (1, 2));
+        assert_eq!(
+            roster.place("nobody", &mut board, &[(2, 2, true)]),
+            Err(PlayerError::UnknownPlayer)
+        );
+    }
+}
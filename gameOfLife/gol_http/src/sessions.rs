@@ -0,0 +1,528 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rocket::http::{Cookie, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use gol::{Game, Rule, Topology, Universe};
+
+use crate::autoplay::Player;
+use crate::logging;
+use crate::players::Roster;
+use crate::throttle::{self, Throttle};
+
+// Cookie holding the session of a browser, a session query parameter takes precedence
+pub const SESSION_COOKIE: &str = "gol_session";
+pub const SESSION_PARAMETER: &str = "session";
+
+/*
+Bounds on what the sessions may hold together, so many open tabs cannot exhaust the memory
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct SessionLimits {
+    pub max_sessions: usize,
+    // Sessions one IP address may hold, so a single client cannot take all of them
+    pub max_sessions_per_client: usize,
+    // Cells of all boards together
+    pub max_total_cells: u64,
+    // A session unused for this many seconds is dropped
+    pub idle_timeout_seconds: u64,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        Self {
+            max_sessions: 100,
+            max_sessions_per_client: 10,
+            max_total_cells: 4_000_000,
+            idle_timeout_seconds: 30 * 60,
+        }
+    }
+}
+
+impl SessionLimits {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_seconds)
+    }
+}
+
+// The board a new session starts with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct BoardSettings {
+    pub width: u32,
+    pub height: u32,
+    pub rule: Rule,
+    pub topology: Topology,
+}
+
+impl Default for BoardSettings {
+    fn default() -> Self {
+        Self {
+            width: 50,
+            height: 50,
+            rule: Rule::conway(),
+            topology: Topology::Torus,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionError {
+    UnknownSession,
+    TooManySessions,
+    TooManyClientSessions,
+    TooManyCells,
+    // The client made too many requests to be given a session
+    Throttled,
+}
+
+impl SessionError {
+    pub fn status(&self) -> Status {
+        match self {
+            SessionError::UnknownSession => Status::NotFound,
+            SessionError::TooManySessions => Status::ServiceUnavailable,
+            SessionError::TooManyClientSessions | SessionError::Throttled => {
+                Status::TooManyRequests
+            }
+            SessionError::TooManyCells => Status::PayloadTooLarge,
+        }
+    }
+}
+
+struct Session {
+    game: Arc<Mutex<Game>>,
+    player: Arc<Player>,
+    roster: Arc<Mutex<Roster>>,
+    // Who created the session, see SessionLimits::max_sessions_per_client
+    client: IpAddr,
+    // Width and height booked by reserve_cells, for the logs without locking the game
+    size: (u32, u32),
+    cells: u64,
+    last_used: Instant,
+}
+
+// Answer to POST /sessions
+#[derive(Serialize, Debug, Clone)]
+pub struct CreatedSession {
+    pub id: String,
+}
+
+// What GET /sessions shows about a session of the client
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct SessionInfo {
+    pub id: String,
+    pub width: u32,
+    pub height: u32,
+    pub generations: u32,
+    pub playing: bool,
+    pub players: usize,
+    pub idle_seconds: u64,
+}
+
+/*
+Every game the server runs, each under its own random token.
+Idle sessions are dropped whenever sessions are created or listed,
+a session with clients watching it over a WebSocket is never idle.
+*/
+pub struct Sessions {
+    limits: SessionLimits,
+    board: BoardSettings,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Sessions {
+    pub fn new(limits: SessionLimits, board: BoardSettings) -> Self {
+        Self {
+            limits,
+            board,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> SessionLimits {
+        self.limits
+    }
+
+    pub fn board(&self) -> BoardSettings {
+        self.board
+    }
+
+    // Starts a session for a client with a randomized board as set up in BoardSettings
+    pub fn create(&self, client: IpAddr) -> Result<SessionGame, SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        let cells = self.board.width as u64 * self.board.height as u64;
+        if sessions.len() >= self.limits.max_sessions {
+            warn!(
+                sessions = sessions.len(),
+                "no session created, too many sessions"
+            );
+            return Err(SessionError::TooManySessions);
+        }
+        let owned = sessions
+            .values()
+            .filter(|session| session.client == client)
+            .count();
+        if owned >= self.limits.max_sessions_per_client {
+            warn!(%client, sessions = owned, "no session created, too many sessions for the client");
+            return Err(SessionError::TooManyClientSessions);
+        }
+        if total_cells(&sessions) + cells > self.limits.max_total_cells {
+            warn!(cells, "no session created, too many cells");
+            return Err(SessionError::TooManyCells);
+        }
+        let mut universe = Universe::new(self.board.width, self.board.height);
+        universe.set_rule(self.board.rule);
+        universe.set_topology(self.board.topology);
+        universe.randomize();
+        let game = Game::with_automaton(Box::new(universe));
+        let session = Session {
+            game: Arc::new(Mutex::new(game)),
+            player: Arc::new(Player::new()),
+            roster: Arc::new(Mutex::new(Roster::default())),
+            client,
+            size: (self.board.width, self.board.height),
+            cells,
+            last_used: Instant::now(),
+        };
+        let id = new_token();
+        let handle = session.handle(&id);
+        info!(
+            session = %id,
+            width = self.board.width,
+            height = self.board.height,
+            sessions = sessions.len() + 1,
+            "session created"
+        );
+        sessions.insert(id, session);
+        Ok(handle)
+    }
+
+    // A session that has not expired yet, counts as using it
+    pub fn get(&self, id: &str) -> Option<SessionGame> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(id)?;
+        if session.is_idle(self.limits.idle_timeout()) {
+            sessions.remove(id);
+            return None;
+        }
+        session.last_used = Instant::now();
+        Some(session.handle(id))
+    }
+
+    // Sessions that have not expired
+    pub fn count(&self) -> usize {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        sessions.len()
+    }
+
+    pub fn delete(&self, id: &str) -> bool {
+        let deleted = self.sessions.lock().unwrap().remove(id).is_some();
+        if deleted {
+            info!(session = id, "session deleted");
+        }
+        deleted
+    }
+
+    /*
+    Deletes a session for the client that created it. The session of another client is
+    unknown to this one, so nobody learns which ids are in use.
+    */
+    pub fn delete_owned(&self, id: &str, client: IpAddr) -> Result<(), SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(id) {
+            Some(session) if session.client == client => {}
+            _ => return Err(SessionError::UnknownSession),
+        }
+        sessions.remove(id);
+        info!(session = id, "session deleted");
+        Ok(())
+    }
+
+    /*
+    Width and height of the board of a session as last booked, without counting as using it
+    and without waiting for a game that is ticking
+    */
+    pub fn board_size(&self, id: &str) -> Option<(u32, u32)> {
+        Some(self.sessions.lock().unwrap().get(id)?.size)
+    }
+
+    /*
+    The sessions a client created, recently used first. The games are read after the
+    sessions are let go, a long tick of one game holds up no other session.
+    */
+    pub fn list(&self, client: IpAddr) -> Vec<SessionInfo> {
+        let owned: Vec<(SessionGame, u64)> = {
+            let mut sessions = self.sessions.lock().unwrap();
+            self.expire(&mut sessions);
+            sessions
+                .iter()
+                .filter(|(_, session)| session.client == client)
+                .map(|(id, session)| (session.handle(id), session.last_used.elapsed().as_secs()))
+                .collect()
+        };
+        let mut list: Vec<SessionInfo> = owned
+            .into_iter()
+            .map(|(session, idle_seconds)| {
+                let game = session.game.lock().unwrap();
+                let automaton = game.automaton();
+                SessionInfo {
+                    width: automaton.width(),
+                    height: automaton.height(),
+                    generations: automaton.generations(),
+                    playing: session.player.is_playing(),
+                    players: session.roster.lock().unwrap().len(),
+                    idle_seconds,
+                    id: session.id,
+                }
+            })
+            .collect();
+        list.sort_by_key(|info| info.idle_seconds);
+        list
+    }
+
+    /*
+    Books the cells of a new board size for a session, fails if all boards together
+    would grow beyond max_total_cells
+    */
+    pub fn reserve_cells(&self, id: &str, width: u32, height: u32) -> Result<(), SessionError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let total = total_cells(&sessions);
+        let session = sessions.get_mut(id).ok_or(SessionError::UnknownSession)?;
+        let cells = width as u64 * height as u64;
+        if total - session.cells + cells > self.limits.max_total_cells {
+            return Err(SessionError::TooManyCells);
+        }
+        session.cells = cells;
+        session.size = (width, height);
+        Ok(())
+    }
+
+    fn expire(&self, sessions: &mut HashMap<String, Session>) {
+        let timeout = self.limits.idle_timeout();
+        let before = sessions.len();
+        sessions.retain(|_, session| !session.is_idle(timeout));
+        if sessions.len() < before {
+            debug!(expired = before - sessions.len(), "idle sessions dropped");
+        }
+    }
+}
+
+// However a session goes, its game stops playing
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.player.pause();
+    }
+}
+
+impl Session {
+    fn is_idle(&self, timeout: Duration) -> bool {
+        self.last_used.elapsed() > timeout && !self.player.has_subscribers()
+    }
+
+    fn handle(&self, id: &str) -> SessionGame {
+        SessionGame {
+            id: id.to_owned(),
+            game: self.game.clone(),
+            player: self.player.clone(),
+            roster: self.roster.clone(),
+        }
+    }
+}
+
+fn total_cells(sessions: &HashMap<String, Session>) -> u64 {
+    sessions.values().map(|session| session.cells).sum()
+}
+
+// 128 random bits as hex, session ids neither collide nor follow a pattern
+pub(crate) fn new_token() -> String {
+    let bits: u128 = rand::thread_rng().gen();
+    format!("{:032x}", bits)
+}
+
+/*
+The game a request works on: the session named by the session query parameter or cookie.
+An unknown session in the query is an error, a request without a live session otherwise
+gets a new one, which the browser keeps in the cookie. Making one counts against the
+request limit of the client like any other request, see Throttle.
+*/
+pub struct SessionGame {
+    pub id: String,
+    pub game: Arc<Mutex<Game>>,
+    pub player: Arc<Player>,
+    // Nobody plays in colours unless the game was created with some
+    pub roster: Arc<Mutex<Roster>>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionGame {
+    type Error = SessionError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let sessions = match request.rocket().state::<Sessions>() {
+            Some(sessions) => sessions,
+            None => {
+                return Outcome::Error((Status::InternalServerError, SessionError::UnknownSession))
+            }
+        };
+        if let Some(id) = request
+            .query_value::<String>(SESSION_PARAMETER)
+            .and_then(Result::ok)
+        {
+            logging::record_session(request, &id);
+            return match sessions.get(&id) {
+                Some(session) => Outcome::Success(session),
+                None => {
+                    let error = SessionError::UnknownSession;
+                    Outcome::Error((error.status(), error))
+                }
+            };
+        }
+        // A cookie of an expired session is simply replaced
+        let cookie = request
+            .cookies()
+            .get(SESSION_COOKIE)
+            .map(|cookie| cookie.value().to_owned());
+        if let Some(id) = cookie {
+            if let Some(session) = sessions.get(&id) {
+                logging::record_session(request, &id);
+                return Outcome::Success(session);
+            }
+        }
+        if request.guard::<Throttle>().await.is_error() {
+            let error = SessionError::Throttled;
+            return Outcome::Error((error.status(), error));
+        }
+        let result = sessions
+            .create(throttle::client(request))
+            .inspect(|session| {
+                logging::record_session(request, &session.id);
+                request
+                    .cookies()
+                    .add(Cookie::build((SESSION_COOKIE, session.id.clone())).path("/"));
+            });
+        match result {
+            Ok(session) => Outcome::Success(session),
+            Err(error) => Outcome::Error((error.status(), error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    use super::*;
+
+    fn client(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    // Sessions of 10x10 boards under the given limits
+    fn sessions(limits: SessionLimits) -> Sessions {
+        let board = BoardSettings {
+            width: 10,
+            height: 10,
+            ..BoardSettings::default()
+        };
+        Sessions::new(limits, board)
+    }
+
+    #[test]
+    fn there_are_at_most_max_sessions() {
+        let sessions = sessions(SessionLimits {
+            max_sessions: 2,
+            ..SessionLimits::default()
+        });
+        sessions.create(client(1)).unwrap();
+        sessions.create(client(2)).unwrap();
+        assert_eq!(
+            sessions.create(client(3)).err(),
+            Some(SessionError::TooManySessions)
+        );
+        assert_eq!(sessions.count(), 2);
+    }
+
+    #[test]
+    fn a_client_holds_at_most_max_sessions_per_client() {
+        let sessions = sessions(SessionLimits {
+            max_sessions_per_client: 1,
+            ..SessionLimits::default()
+        });
+        let first = sessions.create(client(1)).unwrap();
+        assert_eq!(
+            sessions.create(client(1)).err(),
+            Some(SessionError::TooManyClientSessions)
+        );
+        sessions.create(client(2)).unwrap();
+        // Deleting one makes room again
+        sessions.delete_owned(&first.id, client(1)).unwrap();
+        sessions.create(client(1)).unwrap();
+    }
+
+    #[test]
+    fn all_boards_together_stay_within_max_total_cells() {
+        let sessions = sessions(SessionLimits {
+            max_total_cells: 250,
+            ..SessionLimits::default()
+        });
+        let first = sessions.create(client(1)).unwrap();
+        sessions.create(client(1)).unwrap();
+        assert_eq!(
+            sessions.create(client(1)).err(),
+            Some(SessionError::TooManyCells)
+        );
+        assert_eq!(
+            sessions.reserve_cells(&first.id, 20, 10),
+            Err(SessionError::TooManyCells)
+        );
+        assert_eq!(sessions.board_size(&first.id), Some((10, 10)));
+        sessions.reserve_cells(&first.id, 15, 10).unwrap();
+        assert_eq!(sessions.board_size(&first.id), Some((15, 10)));
+    }
+
+    #[test]
+    fn idle_sessions_expire_unless_somebody_watches() {
+        let sessions = sessions(SessionLimits {
+            idle_timeout_seconds: 0,
+            ..SessionLimits::default()
+        });
+        let idle = sessions.create(client(1)).unwrap();
+        let watched = sessions.create(client(1)).unwrap();
+        let (_, _updates) = watched.player.subscribe_since(None, &watched.game);
+        thread::sleep(Duration::from_millis(5));
+        assert!(sessions.get(&idle.id).is_none());
+        assert!(sessions.get(&watched.id).is_some());
+        assert_eq!(sessions.count(), 1);
+    }
+
+    #[test]
+    fn clients_only_see_and_delete_their_own_sessions() {
+        let sessions = sessions(SessionLimits::default());
+        let first = sessions.create(client(1)).unwrap();
+        let second = sessions.create(client(1)).unwrap();
+        sessions.create(client(2)).unwrap();
+        let list = sessions.list(client(1));
+        let ids: Vec<&str> = list.iter().map(|info| info.id.as_str()).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&first.id.as_str()) && ids.contains(&second.id.as_str()));
+        assert_eq!(list[0].width, 10);
+        assert_eq!(
+            sessions.delete_owned(&first.id, client(2)),
+            Err(SessionError::UnknownSession)
+        );
+        sessions.delete_owned(&first.id, client(1)).unwrap();
+        assert!(sessions.get(&first.id).is_none());
+        assert_eq!(sessions.list(client(1)).len(), 1);
+    }
+}
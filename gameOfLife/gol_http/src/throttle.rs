@@ -0,0 +1,99 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::{catch, Catcher};
+
+use gol_ratelimit::RateLimiter;
+
+/*
+Guard of the routes that do work for a client: fails with 429 once the client used up its
+requests, see gol_ratelimit::RateLimiter. Clients are told apart by their IP address.
+It goes first among the guards of a route, before any of them creates a session.
+A request is only counted once, however many guards ask.
+*/
+pub struct Throttle;
+
+// Seconds until the client may try again, kept for the catcher of the rejected request
+#[derive(Debug, Clone, Copy)]
+struct RetryAfter(Option<u64>);
+
+// Whether the request got through, decided on the first check
+struct Checked(bool);
+
+// Address a client is known by, clients without one share it
+pub fn client(request: &Request) -> IpAddr {
+    request
+        .client_ip()
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+// Guard giving the address of the client, see client
+pub struct Client(pub IpAddr);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Client {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Client(client(request)))
+    }
+}
+
+fn check(request: &Request) -> bool {
+    let limiter = match request.rocket().state::<RateLimiter<IpAddr>>() {
+        Some(limiter) => limiter,
+        None => return true,
+    };
+    match limiter.check(client(request)) {
+        Ok(()) => true,
+        Err(wait) => {
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            request.local_cache(|| RetryAfter(Some(seconds)));
+            false
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Throttle {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if request.local_cache(|| Checked(check(request))).0 {
+            Outcome::Success(Throttle)
+        } else {
+            Outcome::Error((Status::TooManyRequests, ()))
+        }
+    }
+}
+
+// Seconds a client turned away with 429 should wait, None for any other request
+pub fn retry_after(request: &Request) -> Option<u64> {
+    request.local_cache(|| RetryAfter(None)).0
+}
+
+pub struct TooManyRequests(pub Option<u64>);
+
+impl<'r> Responder<'r, 'static> for TooManyRequests {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response =
+            Response::build_from("Too many requests, slow down".respond_to(request)?)
+                .status(Status::TooManyRequests)
+                .finalize();
+        if let Some(seconds) = self.0 {
+            response.set_header(Header::new("Retry-After", seconds.to_string()));
+        }
+        Ok(response)
+    }
+}
+
+#[catch(429)]
+fn too_many_requests(request: &Request) -> TooManyRequests {
+    TooManyRequests(retry_after(request))
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    rocket::catchers![too_many_requests]
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use gol::limits::Limits;
+
+// Clients a rate limiter tracks before it forgets the ones with a full bucket again
+const TRACKED_CLIENTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/*
+Token bucket per client, keyed by whatever tells clients apart, e.g. their IP address.
+Each request takes a token, tokens come back at requests_per_second up to burst.
+*/
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    requests_per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_limits(limits: &Limits) -> Self {
+        Self::new(limits.requests_per_second, limits.burst)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.requests_per_second > 0.0
+    }
+
+    // Takes a token of the client, without one left it tells how long until the next one
+    pub fn check(&self, client: K) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    pub fn check_at(&self, client: K, now: Instant) -> Result<(), Duration> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= TRACKED_CLIENTS {
+            let (rate, burst) = (self.requests_per_second, self.burst);
+            buckets.retain(|_, bucket| refill(bucket, now, rate, burst) < burst);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = refill(bucket, now, self.requests_per_second, self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - bucket.tokens;
+            Err(Duration::from_secs_f64(missing / self.requests_per_second))
+        }
+    }
+}
+
+// Tokens of a bucket at the given time, without taking any
+fn refill(bucket: &Bucket, now: Instant, requests_per_second: f64, burst: f64) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * requests_per_second).min(burst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_burst_is_let_through_then_tokens_come_back_over_time() {
+        let limiter = RateLimiter::new(2.0, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at("a", start), Ok(()));
+        }
+        assert_eq!(
+            limiter.check_at("a", start),
+            Err(Duration::from_millis(500))
+        );
+        // Other clients have buckets of their own
+        assert_eq!(limiter.check_at("b", start), Ok(()));
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.check_at("a", later), Ok(()));
+        assert!(limiter.check_at("a", later).is_err());
+    }
+
+    #[test]
+    fn a_bucket_never_holds_more_than_the_burst() {
+        let limiter = RateLimiter::new(10.0, 2);
+        let start = Instant::now();
+        assert_eq!(limiter.check_at(1, start), Ok(()));
+        let much_later = start + Duration::from_secs(60);
+        assert_eq!(limiter.check_at(1, much_later), Ok(()));
+        assert_eq!(limiter.check_at(1, much_later), Ok(()));
+        assert!(limiter.check_at(1, much_later).is_err());
+    }
+
+    #[test]
+    fn zero_requests_per_second_turns_it_off() {
+        let limiter = RateLimiter::new(0.0, 1);
+        assert!(!limiter.is_enabled());
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.check_at((), now), Ok(()));
+        }
+    }
+
+    #[test]
+    fn clients_with_a_full_bucket_are_forgotten() {
+        let limiter = RateLimiter::new(1.0, 1);
+        let start = Instant::now();
+        for client in 0..TRACKED_CLIENTS {
+            limiter.check_at(client, start).unwrap();
+        }
+        let later = start + Duration::from_secs(2);
+        limiter.check_at(TRACKED_CLIENTS, later).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+}
@@ -1,118 +1,199 @@
 use std::fs;
 use std::io::prelude::*;
-use std::net::TcpListener;
-use std::net::TcpStream;
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+use std::time::Instant;
 
+use tracing::field::Empty;
+use tracing::{debug, error, info, info_span, trace, warn};
+
+use gol::limits::{LimitError, Limits};
 use gol::Game;
+use gol_ratelimit::RateLimiter;
+
+pub mod logging;
 
-pub fn register_socket(port:&str, debug:bool){
+pub fn register_socket(port:&str){
+    register_socket_with_limits(port, Limits::default());
+}
+
+pub fn register_socket_with_limits(port:&str, limits: Limits){
     let mut addr = "0.0.0.0:".to_owned();
     addr.push_str(port);
     let listener = TcpListener::bind(addr).unwrap();
     let mut game = Game::new(Some(50), Some(50));
     game.randomize();
-    if debug{
-        println!("Socket Ready");
-    }
+    let limiter = RateLimiter::from_limits(&limits);
+    info!(port, "Socket Ready");
 
     for stream in listener.incoming() {
         let stream = stream.unwrap();
 
-        handle_connection(stream, &mut game, debug);
+        handle_connection(stream, &mut game, &limits, &limiter);
     }
 }
 
-pub fn handle_connection(mut stream: TcpStream,  game: &mut Game, debug: bool){
-    let mut buffer = [0; 1024];
+// Path of the request line without the query and the leading slash, e.g. NewBoard for GET /NewBoard?height=50&width=50
+fn route(request: &str) -> &str {
+    let target = request.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("/");
+    let path = target.split('?').next().unwrap_or(target);
+    match path.trim_start_matches('/') {
+        route @ ("javascript.js" | "mystyle.css" | "ChangeValue" | "Advance" | "NewBoard" | "ResetBoard" | "Randomize" | "Log") => route,
+        _ => "index",
+    }
+}
+
+// Value of a parameter in the query of the request line, e.g. height in GET /NewBoard?height=50&width=50
+fn query_value<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    let target = request.lines().next()?.split_whitespace().nth(1)?;
+    let (_, query) = target.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
 
-    stream.read(&mut buffer).unwrap();
+fn parse_query<T: std::str::FromStr>(request: &str, name: &str) -> Result<T, String> {
+    let value = query_value(request, name).ok_or(format!("missing parameter {}", name))?;
+    value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
 
-    if debug{
-        println!("Request: {}", String::from_utf8_lossy(&buffer[..]));
+fn limit_response(error: &LimitError) -> String {
+    match error {
+        LimitError::Invalid(message) => error_response("400 Bad Request", message),
+        LimitError::TooLarge(message) => error_response("413 Payload Too Large", message),
     }
-    let mut response: String = String::new();
+}
 
-    let str= String::from_utf8_lossy(&buffer[..]);
+fn error_response(status: &str, message: &str) -> String {
+    format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}", status, message.len(), message)
+}
 
-    if str.contains("javascript.js"){
-        if debug{
-            println!("javascript.js");
+/*
+Answers one request in a span with the route, the client and the size of the board,
+logs its status and how long it took. Requests and answers themselves are logged at the trace level.
+*/
+pub fn handle_connection(mut stream: TcpStream,  game: &mut Game, limits: &Limits, limiter: &RateLimiter<IpAddr>){
+    let start = Instant::now();
+    let mut buffer = [0; 1024];
+
+    // Only what the client sent, a dropped connection is no reason to take the server down
+    let length = match stream.read(&mut buffer) {
+        Ok(length) => length,
+        Err(error) => {
+            debug!(%error, "reading the request failed");
+            return;
         }
+    };
+
+    let mut response: String = String::new();
+
+    let str= String::from_utf8_lossy(&buffer[..length]);
+    let route = route(&str);
+    let is_static = route == "javascript.js" || route == "mystyle.css";
+    // Clients without an address share one bucket
+    let client = stream.peer_addr().map(|addr| addr.ip()).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let automaton = game.automaton();
+    let span = info_span!("request", route, %client, board = %format!("{}x{}", automaton.width(), automaton.height()), steps = Empty);
+    let _entered = span.enter();
+    trace!(request = %str, "received");
+    let throttled = if is_static { Ok(()) } else { limiter.check(client) };
+
+    if let Err(wait) = throttled {
+        let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+        let message = "Too many requests, slow down";
+        response = format!(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: {}\r\nContent-Length: {}\r\n\r\n{}",
+            seconds,
+            message.len(),
+            message
+        );
+    }
+    else if route == "javascript.js"{
         let contents= fs::read_to_string("frontend/javascript.js").unwrap();
         response += "HTTP/1.1 200 OK\n\n";
         response += &contents;
-        //println!("{}",response);
     }
-    else if str.contains("mystyle.css"){
-        if debug{
-            println!("mystyle.css");
-        }
+    else if route == "mystyle.css"{
         let contents = fs::read_to_string("frontend/mystyle.css").unwrap();
         response += "HTTP/1.1 200 OK\n\n";
         response += &contents;
     }
-    else if str.contains("ChangeValue"){
-        let start = str.find("id=").unwrap();
-        let end = str.find("HTTP").unwrap();
-        let result = &str[start+3..end-1];
-        if debug{
-            println!("Change Call for id");
-            println!("{}", result);
+    else if route == "ChangeValue"{
+        debug!(id = ?query_value(&str, "id"), "change value");
+        match parse_query::<usize>(&str, "id").and_then(|index| game.change_value(index)) {
+            Ok(()) => {
+                let contents = game.serialize();
+                response += "HTTP/1.1 200 OK\n\n";
+                response += &contents;
+            }
+            Err(message) => response = error_response("400 Bad Request", &message),
         }
-        let index : usize = result.parse().unwrap();
-        game.change_value(index);
-        let contents = game.serialize();
-        response += "HTTP/1.1 200 OK\n\n";
-        response += &contents;
     }
-    else if str.contains("Advance"){
-        if debug{
-            println!("Advance Call");
+    else if route == "Advance"{
+        // Advance?steps=n runs several generations at once
+        let steps = match query_value(&str, "steps") {
+            None => Ok(1),
+            Some(_) => parse_query::<u32>(&str, "steps"),
+        };
+        match steps.map_err(LimitError::Invalid).and_then(|steps| limits.check_steps(steps).map(|_| steps)) {
+            Ok(steps) => {
+                span.record("steps", steps);
+                let ticks = Instant::now();
+                for _ in 0..steps {
+                    game.tick();
+                }
+                debug!(tick_ms = ticks.elapsed().as_secs_f64() * 1000.0 / steps as f64, "ticked");
+                let contents = game.serialize();
+                response += "HTTP/1.1 200 OK\n\n";
+                response += &contents;
+            }
+            Err(error) => response = limit_response(&error),
         }
-        game.tick();
-        let contents = game.serialize();
-        //println!("Recieved call for next generation of board");
-        response += "HTTP/1.1 200 OK\n\n";
-        response += &contents;
-
-        //println!("{}",response);
     }
-    else if str.contains("NewBoard"){
-        if debug{
-            println!("NewBoard/Resize Call");
+    else if route == "NewBoard"{
+        let size = parse_query::<u32>(&str, "height")
+            .and_then(|height| parse_query::<u32>(&str, "width").map(|width| (width, height)))
+            .map_err(LimitError::Invalid)
+            .and_then(|(width, height)| limits.check_size(width, height).map(|_| (width, height)));
+
+        match size {
+            Ok((u32_width, u32_height)) => {
+                debug!(width = u32_width, height = u32_height, "new board");
+                response += "HTTP/1.1 200 OK\n\n";
+                response += &game.change_size(u32_width, u32_height);
+            }
+            Err(error) => response = limit_response(&error),
         }
-        response += "HTTP/1.1 200 OK\n\n";
-
-        let heigth_start = str.find("height=").unwrap_or(50);
-        let heigth_end = str.find("&").unwrap_or(str.len()-1);
-        let height_result = &str[heigth_start..heigth_end];
-        let height_result_after = height_result.split("=").last().unwrap();
-        let u32_height: u32 = height_result_after.parse().unwrap();
-
-        let width_start = str.find("width=").unwrap_or(50);
-        let width_end = str.find("HTTP/1.1").unwrap_or(str.len())-1;
-        let width_result = &str[width_start..width_end];
-        let width_result_after = width_result.split("=").last().unwrap();
-        let u32_width: u32 = width_result_after.parse().unwrap();
-
-        if debug{
-            println!("Height: {}", u32_height);
-            println!("Width: {}", u32_width);
-        }
-        response += &game.change_size(u32_width, u32_height);
 
     }
-    else if str.contains("ResetBoard"){
+    else if route == "ResetBoard"{
         response += "HTTP/1.1 200 OK\n\n";
         response += &game.negate_everything();
     }
-    else if str.contains("Randomize"){
+    else if route == "Randomize"{
         game.randomize();
         let contents = game.serialize();
-        //println!("Recieved call for next generation of board");
         response += "HTTP/1.1 200 OK\n\n";
         response += &contents;
     }
+    else if route == "Log"{
+        // Log shows the log filter, Log?filter=debug changes it while the server runs if GOL_LOG__ALLOW_CHANGES is set
+        let changed = match query_value(&str, "filter") {
+            Some(_) if !logging::changes_allowed() => Err(("403 Forbidden", String::from("changing the log filter is turned off"))),
+            Some(filter) => logging::set_filter(filter)
+                .inspect(|_| info!(filter, "log filter changed"))
+                .map_err(|message| ("400 Bad Request", message)),
+            None => Ok(()),
+        };
+        match changed {
+            Ok(()) => {
+                let filter = logging::filter().unwrap_or_default();
+                response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", filter.len(), filter);
+            }
+            Err((status, message)) => response = error_response(status, &message),
+        }
+    }
     else{
         let contents = fs::read_to_string("frontend/index.html").unwrap();
 
@@ -124,10 +205,17 @@ pub fn handle_connection(mut stream: TcpStream,  game: &mut Game, debug: bool){
     }
     response += "\n";
 
-    if debug{
-        println!("{}", response);
+    trace!(response = %response, "answering");
+    // The status code of the status line, e.g. 200 for HTTP/1.1 200 OK
+    let status: u16 = response.split_whitespace().nth(1).and_then(|code| code.parse().ok()).unwrap_or(0);
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    match status {
+        500.. => error!(status, duration_ms, "answered"),
+        400..=499 => warn!(status, duration_ms, "answered"),
+        _ => info!(status, duration_ms, "answered"),
     }
 
-    stream.write(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+    if let Err(error) = stream.write_all(response.as_bytes()).and_then(|_| stream.flush()) {
+        debug!(%error, "writing the answer failed");
+    }
 }
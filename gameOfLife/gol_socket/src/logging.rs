@@ -0,0 +1,99 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::{Mutex, OnceLock};
+
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+// Same names as the settings of gol_http, e.g. GOL_LOG__FILTER=debug GOL_LOG__FORMAT=json
+pub const FILTER_VARIABLE: &str = "GOL_LOG__FILTER";
+pub const FORMAT_VARIABLE: &str = "GOL_LOG__FORMAT";
+// Clients may only change the filter if this is true, see allow_changes in gol_http
+pub const ALLOW_CHANGES_VARIABLE: &str = "GOL_LOG__ALLOW_CHANGES";
+pub const DEFAULT_FILTER: &str = "info";
+
+struct LogHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    filter: Mutex<String>,
+    allow_changes: bool,
+}
+
+// Set up once by init, the Log request changes the filter through it
+static LOG: OnceLock<LogHandle> = OnceLock::new();
+
+fn parse_filter(filter: &str) -> Result<EnvFilter, String> {
+    EnvFilter::builder()
+        .parse(filter)
+        .map_err(|error| format!("invalid log filter {}: {}", filter, error))
+}
+
+/*
+Sends the logs to stdout, filtered and formatted as GOL_LOG__FILTER and GOL_LOG__FORMAT
+(text or json) say. Fails if one of them is invalid or a logger is already set up.
+GOL_LOG__ALLOW_CHANGES=true lets clients change the filter, nobody may by default.
+*/
+pub fn init() -> Result<(), String> {
+    let filter = env::var(FILTER_VARIABLE).unwrap_or_else(|_| String::from(DEFAULT_FILTER));
+    let json = match env::var(FORMAT_VARIABLE).as_deref() {
+        Err(_) | Ok("text") => false,
+        Ok("json") => true,
+        Ok(format) => {
+            return Err(format!(
+                "invalid log format {}, expected text or json",
+                format
+            ))
+        }
+    };
+    let allow_changes = match env::var(ALLOW_CHANGES_VARIABLE).as_deref() {
+        Err(_) | Ok("false") => false,
+        Ok("true") => true,
+        Ok(value) => {
+            return Err(format!(
+                "invalid {} {}, expected true or false",
+                ALLOW_CHANGES_VARIABLE, value
+            ))
+        }
+    };
+    let (layer, handle) = reload::Layer::new(parse_filter(&filter)?);
+    let (text, json) = if json {
+        (None, Some(fmt::layer().json()))
+    } else {
+        (
+            Some(fmt::layer().with_ansi(io::stdout().is_terminal())),
+            None,
+        )
+    };
+    tracing_subscriber::registry()
+        .with(layer)
+        .with(text)
+        .with(json)
+        .try_init()
+        .map_err(|error| error.to_string())?;
+    let _ = LOG.set(LogHandle {
+        handle,
+        filter: Mutex::new(filter),
+        allow_changes,
+    });
+    Ok(())
+}
+
+// The filter in use, None if init was not called
+pub fn filter() -> Option<String> {
+    LOG.get().map(|log| log.filter.lock().unwrap().clone())
+}
+
+// Whether clients may change the filter, false if init was not called
+pub fn changes_allowed() -> bool {
+    LOG.get().is_some_and(|log| log.allow_changes)
+}
+
+pub fn set_filter(filter: &str) -> Result<(), String> {
+    let log = LOG
+        .get()
+        .ok_or_else(|| String::from("logging is not set up"))?;
+    log.handle
+        .reload(parse_filter(filter)?)
+        .map_err(|error| error.to_string())?;
+    *log.filter.lock().unwrap() = filter.to_owned();
+    Ok(())
+}
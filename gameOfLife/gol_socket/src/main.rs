@@ -1,10 +1,14 @@
+use std::process;
 use std::thread;
 
-mod lib;
-
 fn main() {
+    if let Err(error) = gol_socket::logging::init() {
+        eprintln!("Invalid configuration: {}", error);
+        process::exit(2);
+    }
+
     let socket_handler = thread::spawn(|| {
-        lib::register_socket("8001", false);
+        gol_socket::register_socket("8001");
     });
     socket_handler.join().expect("Error joining Socket Thread");
 